@@ -0,0 +1,712 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, Metadata},
+    io::{self, BufReader, BufWriter, Read, Write},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use tar::EntryType;
+use walkdir::WalkDir;
+use xz2::{read::XzDecoder, write::XzEncoder};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    error::{FsOperation, FsRsError, SearchError},
+    fsutil::temporary_path,
+    search::{EntryKind, SearchResult, SearchWarning},
+};
+
+/// Separates the archive path from the member path, e.g. `bundle.tar.gz!/dir/file.txt`.
+pub static MEMBER_SEPARATOR: &str = "!/";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Tar,
+    TarGz,
+    TarZst,
+    TarXz,
+    Zip,
+    Gz,
+    Zst,
+    Xz,
+}
+
+/// A virtual entry read from inside an archive or compressed file.
+#[derive(Clone, Debug)]
+pub struct ArchiveEntry {
+    pub archive: PathBuf,
+    pub member_path: String,
+    pub name: String,
+    pub kind: EntryKind,
+    /// `None` for plain compressed files, their size is only known once they
+    /// are decompressed to read the content.
+    pub size: Option<u64>,
+    pub content: Vec<u8>,
+}
+
+/// An entry on the filesystem to pack, with its member path inside the archive.
+#[derive(Clone, Debug)]
+pub struct ArchiveInput {
+    pub path: PathBuf,
+    pub name: PathBuf,
+}
+
+/// How far [`create_archive`] got, reported after every packed entry.
+#[derive(Clone, Debug, Default)]
+pub struct ArchiveProgress {
+    pub entries_written: u64,
+    pub entries_total: u64,
+    pub bytes_written: u64,
+    pub current_path: PathBuf,
+}
+
+impl ArchiveEntry {
+    #[must_use]
+    pub fn path(&self) -> PathBuf {
+        member_path(&self.archive, &self.member_path)
+    }
+}
+
+impl ArchiveKind {
+    #[must_use]
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+
+        let kind = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            ArchiveKind::TarGz
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            ArchiveKind::TarZst
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            ArchiveKind::TarXz
+        } else if name.ends_with(".tar") {
+            ArchiveKind::Tar
+        } else if name.ends_with(".zip") {
+            ArchiveKind::Zip
+        } else if name.ends_with(".gz") {
+            ArchiveKind::Gz
+        } else if name.ends_with(".zst") {
+            ArchiveKind::Zst
+        } else if name.ends_with(".xz") {
+            ArchiveKind::Xz
+        } else {
+            return None;
+        };
+
+        Some(kind)
+    }
+
+    /// Whether [`create_archive`] can write this kind, plain compressed files
+    /// only hold a single file.
+    #[must_use]
+    pub fn can_create(self) -> bool {
+        matches!(
+            self,
+            ArchiveKind::Tar
+                | ArchiveKind::TarGz
+                | ArchiveKind::TarZst
+                | ArchiveKind::TarXz
+                | ArchiveKind::Zip
+        )
+    }
+}
+
+#[must_use]
+pub fn member_path(archive: &Path, member: &str) -> PathBuf {
+    let mut path = archive.as_os_str().to_os_string();
+    path.push(MEMBER_SEPARATOR);
+    path.push(member);
+    PathBuf::from(path)
+}
+
+/// Reads the members of the archive at `path` and hands them to `callback`,
+/// until it returns [`ControlFlow::Break`].
+///
+/// The content of a file member is only read into memory if `wants_content`
+/// returns true for it, otherwise it is empty. Plain compressed files (`.gz`,
+/// `.zst`, `.xz`) yield a single member named after the file without its
+/// compression extension.
+pub fn read_members(
+    path: &Path,
+    kind: ArchiveKind,
+    mut wants_content: impl FnMut(&ArchiveEntry) -> bool,
+    mut callback: impl FnMut(ArchiveEntry) -> ControlFlow<()>,
+) -> io::Result<()> {
+    let open = || File::open(path).map(BufReader::new);
+    let (wants_content, callback) = (&mut wants_content, &mut callback);
+
+    match kind {
+        ArchiveKind::Tar => read_tar_members(open()?, path, wants_content, callback),
+        ArchiveKind::TarGz => {
+            read_tar_members(GzDecoder::new(open()?), path, wants_content, callback)
+        }
+        ArchiveKind::TarZst => read_tar_members(
+            zstd::Decoder::with_buffer(open()?)?,
+            path,
+            wants_content,
+            callback,
+        ),
+        ArchiveKind::TarXz => {
+            read_tar_members(XzDecoder::new(open()?), path, wants_content, callback)
+        }
+        ArchiveKind::Zip => read_zip_members(open()?, path, wants_content, callback),
+        ArchiveKind::Gz => read_compressed_member(
+            || Ok(GzDecoder::new(open()?)),
+            path,
+            wants_content,
+            callback,
+        ),
+        ArchiveKind::Zst => read_compressed_member(
+            || zstd::Decoder::with_buffer(open()?),
+            path,
+            wants_content,
+            callback,
+        ),
+        ArchiveKind::Xz => read_compressed_member(
+            || Ok(XzDecoder::new(open()?)),
+            path,
+            wants_content,
+            callback,
+        ),
+    }
+}
+
+/// Reads the content of a single member, returns `None` if the archive does not contain it.
+pub fn read_member(archive: &Path, member: &str) -> io::Result<Option<Vec<u8>>> {
+    let Some(kind) = ArchiveKind::detect(archive) else {
+        return Ok(None);
+    };
+
+    let mut content = None;
+    let is_member =
+        |entry: &ArchiveEntry| entry.kind == EntryKind::File && entry.member_path == member;
+
+    read_members(archive, kind, is_member, |entry| {
+        if is_member(&entry) {
+            content = Some(entry.content);
+            return ControlFlow::Break(());
+        }
+
+        ControlFlow::Continue(())
+    })?;
+
+    Ok(content)
+}
+
+fn read_tar_members(
+    reader: impl Read,
+    archive: &Path,
+    wants_content: &mut impl FnMut(&ArchiveEntry) -> bool,
+    callback: &mut impl FnMut(ArchiveEntry) -> ControlFlow<()>,
+) -> io::Result<()> {
+    let mut tar = tar::Archive::new(reader);
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+
+        let kind = match entry.header().entry_type() {
+            EntryType::Directory => EntryKind::Directory,
+            // hard links have no data of their own, they point to an earlier member
+            EntryType::Symlink | EntryType::Link => EntryKind::SymLink,
+            EntryType::Regular | EntryType::Continuous => EntryKind::File,
+            _ => continue,
+        };
+
+        let member_path = entry
+            .path()?
+            .to_string_lossy()
+            .trim_end_matches('/')
+            .to_string();
+
+        let mut member = ArchiveEntry {
+            archive: archive.to_path_buf(),
+            name: member_name(&member_path),
+            member_path,
+            kind,
+            size: Some(entry.size()),
+            content: Vec::new(),
+        };
+
+        if kind == EntryKind::File && wants_content(&member) {
+            entry.read_to_end(&mut member.content)?;
+        }
+
+        if callback(member).is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_zip_members(
+    reader: BufReader<File>,
+    archive: &Path,
+    wants_content: &mut impl FnMut(&ArchiveEntry) -> bool,
+    callback: &mut impl FnMut(ArchiveEntry) -> ControlFlow<()>,
+) -> io::Result<()> {
+    let mut zip = ZipArchive::new(reader)?;
+
+    for index in 0..zip.len() {
+        let mut file = zip.by_index(index)?;
+
+        let kind = if file.is_dir() {
+            EntryKind::Directory
+        } else if file.is_symlink() {
+            EntryKind::SymLink
+        } else {
+            EntryKind::File
+        };
+
+        let member_path = file.name().trim_end_matches('/').to_string();
+
+        let mut member = ArchiveEntry {
+            archive: archive.to_path_buf(),
+            name: member_name(&member_path),
+            member_path,
+            kind,
+            size: Some(file.size()),
+            content: Vec::new(),
+        };
+
+        if kind == EntryKind::File && wants_content(&member) {
+            file.read_to_end(&mut member.content)?;
+        }
+
+        if callback(member).is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// The size of a compressed file is only known after decompressing it, so it
+/// is only known if the content is wanted.
+fn read_compressed_member<R: Read>(
+    open: impl FnOnce() -> io::Result<R>,
+    path: &Path,
+    wants_content: &mut impl FnMut(&ArchiveEntry) -> bool,
+    callback: &mut impl FnMut(ArchiveEntry) -> ControlFlow<()>,
+) -> io::Result<()> {
+    let name = path
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut member = ArchiveEntry {
+        archive: path.to_path_buf(),
+        member_path: name.clone(),
+        name,
+        kind: EntryKind::File,
+        size: None,
+        content: Vec::new(),
+    };
+
+    if wants_content(&member) {
+        open()?.read_to_end(&mut member.content)?;
+        member.size = Some(member.content.len() as u64);
+    }
+
+    // the only member, there is nothing left to stop reading
+    let _ = callback(member);
+
+    Ok(())
+}
+
+fn member_name(member_path: &str) -> String {
+    member_path
+        .rsplit('/')
+        .next()
+        .unwrap_or(member_path)
+        .to_string()
+}
+
+/// Collects the entries to pack for the search results.
+///
+/// Member paths are relative to the search root of each result, directories
+/// are packed with everything below them. Archive members and `output`
+/// itself are skipped. Entries below the directories that could not be read
+/// are returned as warnings.
+#[must_use]
+pub fn archive_inputs(
+    results: &[SearchResult],
+    output: &Path,
+) -> (Vec<ArchiveInput>, Vec<SearchWarning>) {
+    let output = fs::canonicalize(output).unwrap_or_else(|_| output.to_path_buf());
+    let mut seen = HashSet::new();
+    let mut inputs = Vec::new();
+    let mut warnings = Vec::new();
+
+    let mut push = |path: PathBuf, name: PathBuf| {
+        let is_output = fs::canonicalize(&path).is_ok_and(|x| x == output);
+        if !name.as_os_str().is_empty() && !is_output && seen.insert(path.clone()) {
+            inputs.push(ArchiveInput { path, name });
+        }
+    };
+
+    for result in results.iter().filter(|x| x.archive().is_none()) {
+        let name = result.relative_path();
+
+        if result.kind() != EntryKind::Directory {
+            push(result.path().to_path_buf(), name);
+            continue;
+        }
+
+        for entry in WalkDir::new(result.path()) {
+            let entry = match entry {
+                Ok(e) => e,
+                Err(why) => {
+                    warnings.push(SearchWarning {
+                        path: why.path().map(Path::to_path_buf),
+                        error: SearchError::WalkDir(why),
+                    });
+                    continue;
+                }
+            };
+
+            let Ok(suffix) = entry.path().strip_prefix(result.path()) else {
+                continue;
+            };
+            push(entry.path().to_path_buf(), name.join(suffix));
+        }
+    }
+
+    (inputs, warnings)
+}
+
+/// Packs the entries into a new archive at `output`.
+///
+/// The archive is written to a temporary file next to `output` first and
+/// only replaces it once complete. Symlinks are stored as links, not
+/// followed.
+pub fn create_archive(
+    output: &Path,
+    kind: ArchiveKind,
+    inputs: &[ArchiveInput],
+    mut progress: impl FnMut(&ArchiveProgress),
+) -> Result<(), FsRsError> {
+    let temp_path = temporary_path(output, "archive");
+    let file = File::create(&temp_path)
+        .map_err(|e| FsRsError::filesystem(FsOperation::Write, output, e))?;
+    let writer = BufWriter::new(file);
+
+    let mut state = ArchiveProgress {
+        entries_total: inputs.len() as u64,
+        ..ArchiveProgress::default()
+    };
+    let mut report = |input: &ArchiveInput, metadata: &Metadata| {
+        state.entries_written += 1;
+        if metadata.is_file() {
+            state.bytes_written += metadata.len();
+        }
+        state.current_path.clone_from(&input.path);
+        progress(&state);
+    };
+
+    let written = match kind {
+        ArchiveKind::Tar => write_tar(writer, inputs, &mut report),
+        ArchiveKind::TarGz => write_tar(
+            GzEncoder::new(writer, Compression::default()),
+            inputs,
+            &mut report,
+        )
+        .and_then(|x| x.finish().map_err(write_error)),
+        ArchiveKind::TarZst => zstd::Encoder::new(writer, 0)
+            .map_err(write_error)
+            .and_then(|x| write_tar(x, inputs, &mut report))
+            .and_then(|x| x.finish().map_err(write_error)),
+        ArchiveKind::TarXz => write_tar(XzEncoder::new(writer, 6), inputs, &mut report)
+            .and_then(|x| x.finish().map_err(write_error)),
+        ArchiveKind::Zip => write_zip(writer, inputs, &mut report),
+        ArchiveKind::Gz | ArchiveKind::Zst | ArchiveKind::Xz => Err(EntryError::Write(
+            io::Error::other("the format cannot hold several entries"),
+        )),
+    };
+
+    let result = written
+        .and_then(|x| x.into_inner().map_err(|e| write_error(e.into_error())))
+        .and_then(|x| x.sync_all().map_err(write_error))
+        .and_then(|()| fs::rename(&temp_path, output).map_err(write_error));
+
+    result.map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        match e {
+            EntryError::Read(path, e) => FsRsError::filesystem(FsOperation::Read, path, e),
+            EntryError::Write(e) => FsRsError::filesystem(FsOperation::Write, output, e),
+        }
+    })
+}
+
+/// Tells apart failures reading an input from failures writing the archive.
+enum EntryError {
+    Read(PathBuf, io::Error),
+    Write(io::Error),
+}
+
+fn write_error(e: io::Error) -> EntryError {
+    EntryError::Write(e)
+}
+
+fn write_tar<W: Write>(
+    writer: W,
+    inputs: &[ArchiveInput],
+    report: &mut impl FnMut(&ArchiveInput, &Metadata),
+) -> Result<W, EntryError> {
+    let mut tar = tar::Builder::new(writer);
+    tar.follow_symlinks(false);
+
+    for input in inputs {
+        let metadata = fs::symlink_metadata(&input.path)
+            .map_err(|e| EntryError::Read(input.path.clone(), e))?;
+
+        tar.append_path_with_name(&input.path, &input.name)
+            .map_err(|e| EntryError::Read(input.path.clone(), e))?;
+        report(input, &metadata);
+    }
+
+    tar.into_inner().map_err(write_error)
+}
+
+fn write_zip<W: Write + io::Seek>(
+    writer: W,
+    inputs: &[ArchiveInput],
+    report: &mut impl FnMut(&ArchiveInput, &Metadata),
+) -> Result<W, EntryError> {
+    let mut zip = ZipWriter::new(writer);
+
+    for input in inputs {
+        let read_error = |e| EntryError::Read(input.path.clone(), e);
+        let metadata = fs::symlink_metadata(&input.path).map_err(read_error)?;
+        let name = zip_name(&input.name);
+        let options = zip_options(&metadata);
+
+        if metadata.is_dir() {
+            zip.add_directory(name, options)
+                .map_err(|e| write_error(e.into()))?;
+        } else if metadata.is_symlink() {
+            let target = fs::read_link(&input.path).map_err(read_error)?;
+            zip.add_symlink(name, target.to_string_lossy(), options)
+                .map_err(|e| write_error(e.into()))?;
+        } else {
+            let mut file = File::open(&input.path).map_err(read_error)?;
+            zip.start_file(name, options)
+                .map_err(|e| write_error(e.into()))?;
+            io::copy(&mut file, &mut zip).map_err(read_error)?;
+        }
+
+        report(input, &metadata);
+    }
+
+    zip.finish().map_err(|e| write_error(e.into()))
+}
+
+/// Zip members always use `/` as separator.
+fn zip_name(name: &Path) -> String {
+    name.iter()
+        .map(|x| x.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn zip_options(metadata: &Metadata) -> SimpleFileOptions {
+    let mut options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(metadata.len() >= u64::from(u32::MAX));
+
+    if let Some(mode) = unix_mode(metadata) {
+        options = options.unix_permissions(mode);
+    }
+
+    let modified = metadata.modified().map(DateTime::<Local>::from);
+    if let Some(time) = modified.ok().and_then(|x| {
+        zip::DateTime::from_date_and_time(
+            u16::try_from(x.year()).ok()?,
+            u8::try_from(x.month()).ok()?,
+            u8::try_from(x.day()).ok()?,
+            u8::try_from(x.hour()).ok()?,
+            u8::try_from(x.minute()).ok()?,
+            u8::try_from(x.second()).ok()?,
+        )
+        .ok()
+    }) {
+        options = options.last_modified_time(time);
+    }
+
+    options
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_: &Metadata) -> Option<u32> {
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SearchBuilder;
+
+    /// A member as `(path, kind, size, content)`.
+    type Member = (String, EntryKind, Option<u64>, Vec<u8>);
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fs-rs-archive-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn members(path: &Path, wants_content: bool) -> Vec<Member> {
+        let mut members = Vec::new();
+        read_members(path, ArchiveKind::detect(path).unwrap(), |_| wants_content, |x| {
+            members.push((x.member_path, x.kind, x.size, x.content));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        members
+    }
+
+    fn write_tar(path: &Path) {
+        let mut tar = tar::Builder::new(File::create(path).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(EntryType::Directory);
+        header.set_size(0);
+        tar.append_data(&mut header, "dir/", io::empty()).unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(11);
+        tar.append_data(&mut header, "dir/a.txt", &b"hello world"[..]).unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(EntryType::Symlink);
+        header.set_size(0);
+        tar.append_link(&mut header, "soft", "dir/a.txt").unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(EntryType::Link);
+        header.set_size(0);
+        tar.append_link(&mut header, "hard", "dir/a.txt").unwrap();
+        tar.finish().unwrap();
+    }
+
+    fn write_zip(path: &Path) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        zip.add_directory("dir/", SimpleFileOptions::default()).unwrap();
+        zip.start_file("dir/b.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"zipped content").unwrap();
+        zip.finish().unwrap();
+    }
+
+    fn write_gz(path: &Path) {
+        let mut gz = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+        gz.write_all(b"compressed content").unwrap();
+        gz.finish().unwrap();
+    }
+
+    #[test]
+    fn read_tar_members() {
+        let dir = test_dir("tar");
+        write_tar(&dir.join("bundle.tar"));
+        let members = members(&dir.join("bundle.tar"), true);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            members,
+            [
+                ("dir".into(), EntryKind::Directory, Some(0), Vec::new()),
+                ("dir/a.txt".into(), EntryKind::File, Some(11), b"hello world".to_vec()),
+                ("soft".into(), EntryKind::SymLink, Some(0), Vec::new()),
+                ("hard".into(), EntryKind::SymLink, Some(0), Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_zip_members() {
+        let dir = test_dir("zip");
+        write_zip(&dir.join("bundle.zip"));
+        let members = members(&dir.join("bundle.zip"), false);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            members,
+            [
+                ("dir".into(), EntryKind::Directory, Some(0), Vec::new()),
+                ("dir/b.txt".into(), EntryKind::File, Some(14), Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_compressed_member_only_when_wanted() {
+        let dir = test_dir("gz");
+        write_gz(&dir.join("notes.txt.gz"));
+        let without = members(&dir.join("notes.txt.gz"), false);
+        let with = members(&dir.join("notes.txt.gz"), true);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(without, [("notes.txt".into(), EntryKind::File, None, Vec::new())]);
+        assert_eq!(
+            with,
+            [("notes.txt".into(), EntryKind::File, Some(18), b"compressed content".to_vec())]
+        );
+    }
+
+    #[test]
+    fn read_members_stops_at_break() {
+        let dir = test_dir("break");
+        write_tar(&dir.join("bundle.tar"));
+        let mut count = 0;
+        read_members(&dir.join("bundle.tar"), ArchiveKind::Tar, |_| false, |_| {
+            count += 1;
+            ControlFlow::Break(())
+        })
+        .unwrap();
+        let content = read_member(&dir.join("bundle.tar"), "dir/a.txt").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(count, 1);
+        assert_eq!(content.as_deref(), Some(&b"hello world"[..]));
+    }
+
+    #[test]
+    fn search_matches_member_contents_and_sizes() {
+        let dir = test_dir("search");
+        write_tar(&dir.join("bundle.tar"));
+        write_zip(&dir.join("bundle.zip"));
+        write_gz(&dir.join("notes.txt.gz"));
+
+        let search = |builder: SearchBuilder| {
+            let mut paths: Vec<PathBuf> = builder
+                .root(&dir)
+                .search_archives(true)
+                .build()
+                .run()
+                .results
+                .iter()
+                .filter(|x| x.archive().is_some())
+                .map(|x| x.path().strip_prefix(&dir).unwrap().to_path_buf())
+                .collect();
+            paths.sort();
+            paths
+        };
+        let content = search(SearchBuilder::new().content_any(["content"]));
+        let size = search(SearchBuilder::new().min_size(12).max_size(20));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            content,
+            [PathBuf::from("bundle.zip!/dir/b.txt"), PathBuf::from("notes.txt.gz!/notes.txt")]
+        );
+        assert_eq!(size, content);
+    }
+}
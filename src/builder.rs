@@ -0,0 +1,590 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::Metadata,
+    ops::ControlFlow,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use globset::Glob;
+
+use crate::{
+    cli::{MatchOption, ResultFilter},
+    filetype::{FileType, FileTypes},
+    filter::{
+        AclFilter, ChangedSinceFilter, EntryTypeFilter, ExtensionFilter, FileContentFilter,
+        FileTypeFilter, FilenameFilter, GitStatusFilter, LinkCountFilter, MimeFilter,
+        ModifiedFilter, SameFileFilter, SearchFilter, SecurityContextFilter, SizeFilter,
+        XattrFilter,
+    },
+    git::{GitState, GitStatus},
+    links::LinkCount,
+    index::FileIndex,
+    search::{
+        CancellationToken, FileSearcher, ProgressCallback, SearchEvent, SearchMode,
+        SearchOptions, SearchOutput, SearchProgress, SearchRoot, TraversalOptions,
+    },
+    xattrs::XattrPattern,
+};
+
+/// Fluent construction of a search for embedding fs-rs as a library.
+///
+/// ```no_run
+/// use fs_rs::{builder::SearchBuilder, cli::ResultFilter};
+///
+/// let output = SearchBuilder::new()
+///     .root("/var/log")
+///     .name_any(["error", "warn"])
+///     .content_all(["timeout"])
+///     .kind(ResultFilter::File)
+///     .max_depth(3)
+///     .build()
+///     .run();
+///
+/// for result in &output.results {
+///     println!("{}", result.path().display());
+/// }
+/// ```
+#[derive(Default)]
+pub struct SearchBuilder {
+    roots: Vec<(PathBuf, Option<TraversalOptions>)>,
+    files: Vec<PathBuf>,
+    options: SearchOptions,
+    names: Vec<(Vec<OsString>, MatchOption)>,
+    contents: Vec<(Vec<OsString>, MatchOption)>,
+    case_sensitiv: bool,
+    kind: Option<ResultFilter>,
+    file_types: Vec<FileType>,
+    extensions: Vec<String>,
+    mime_types: Vec<String>,
+    type_definitions: Option<FileTypes>,
+    same_file: Option<Metadata>,
+    links: Option<LinkCount>,
+    xattrs: Vec<XattrPattern>,
+    acl: bool,
+    security_context: Option<Glob>,
+    git_status: Option<(Arc<GitState>, Vec<GitStatus>)>,
+    changed_since: Option<Arc<GitState>>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    older_than: Option<Duration>,
+    newer_than: Option<Duration>,
+    filters: Vec<Box<dyn SearchFilter>>,
+    cancellation: Option<CancellationToken>,
+    progress: Option<ProgressCallback>,
+}
+
+/// A search created by [`SearchBuilder::build`], it can be run any number of times.
+pub struct PreparedSearch {
+    searcher: FileSearcher,
+    roots: Vec<SearchRoot>,
+    files: Vec<PathBuf>,
+}
+
+impl SearchBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a path to search with the traversal options of the builder.
+    #[must_use]
+    pub fn root(mut self, path: impl Into<PathBuf>) -> Self {
+        self.roots.push((path.into(), None));
+        self
+    }
+
+    #[must_use]
+    pub fn roots<P: Into<PathBuf>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.roots
+            .extend(paths.into_iter().map(|x| (x.into(), None)));
+        self
+    }
+
+    /// Adds a path to search with traversal options of its own.
+    #[must_use]
+    pub fn search_root(mut self, root: SearchRoot) -> Self {
+        self.roots.push((root.path, Some(root.options)));
+        self
+    }
+
+    /// Adds entries to check without walking into them, e.g. the output of
+    /// `git ls-files` read with [`crate::search::read_file_list`].
+    #[must_use]
+    pub fn files<P: Into<PathBuf>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.files.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Matches entries whose name contains the words as `match_option` requires.
+    #[must_use]
+    pub fn names<S: AsRef<OsStr>>(
+        mut self,
+        words: impl IntoIterator<Item = S>,
+        match_option: MatchOption,
+    ) -> Self {
+        self.names.push((to_os_strings(words), match_option));
+        self
+    }
+
+    #[must_use]
+    pub fn name_any<S: AsRef<OsStr>>(self, words: impl IntoIterator<Item = S>) -> Self {
+        self.names(words, MatchOption::Any)
+    }
+
+    #[must_use]
+    pub fn name_all<S: AsRef<OsStr>>(self, words: impl IntoIterator<Item = S>) -> Self {
+        self.names(words, MatchOption::All)
+    }
+
+    #[must_use]
+    pub fn name_none<S: AsRef<OsStr>>(self, words: impl IntoIterator<Item = S>) -> Self {
+        self.names(words, MatchOption::None)
+    }
+
+    /// Matches files whose content contains the words as `match_option` requires.
+    #[must_use]
+    pub fn contents<S: AsRef<OsStr>>(
+        mut self,
+        words: impl IntoIterator<Item = S>,
+        match_option: MatchOption,
+    ) -> Self {
+        self.contents.push((to_os_strings(words), match_option));
+        self
+    }
+
+    #[must_use]
+    pub fn content_any<S: AsRef<OsStr>>(self, words: impl IntoIterator<Item = S>) -> Self {
+        self.contents(words, MatchOption::Any)
+    }
+
+    #[must_use]
+    pub fn content_all<S: AsRef<OsStr>>(self, words: impl IntoIterator<Item = S>) -> Self {
+        self.contents(words, MatchOption::All)
+    }
+
+    #[must_use]
+    pub fn content_none<S: AsRef<OsStr>>(self, words: impl IntoIterator<Item = S>) -> Self {
+        self.contents(words, MatchOption::None)
+    }
+
+    /// Matches names and contents case sensitiv, defaults to `false`.
+    #[must_use]
+    pub fn case_sensitiv(mut self, case_sensitiv: bool) -> Self {
+        self.case_sensitiv = case_sensitiv;
+        self
+    }
+
+    #[must_use]
+    pub fn kind(mut self, kind: ResultFilter) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Matches files of any of the types, e.g. from [`FileTypes::get`].
+    #[must_use]
+    pub fn file_types(mut self, file_types: impl IntoIterator<Item = FileType>) -> Self {
+        self.file_types.extend(file_types);
+        self
+    }
+
+    /// Matches files with any of the extensions, e.g. `md` or `tar.gz`.
+    #[must_use]
+    pub fn extensions<S: Into<String>>(mut self, extensions: impl IntoIterator<Item = S>) -> Self {
+        self.extensions
+            .extend(extensions.into_iter().map(Into::into));
+        self
+    }
+
+    /// Matches files with any of the MIME types, e.g. `text/*` or `image/png`.
+    #[must_use]
+    pub fn mime_types<S: Into<String>>(mut self, patterns: impl IntoIterator<Item = S>) -> Self {
+        self.mime_types
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// The definitions used to detect MIME types, defaults to [`FileTypes::builtin`].
+    #[must_use]
+    pub fn type_definitions(mut self, file_types: FileTypes) -> Self {
+        self.type_definitions = Some(file_types);
+        self
+    }
+
+    /// Matches the hard links to the file with `metadata`, see [`SameFileFilter`].
+    #[must_use]
+    pub fn same_file(mut self, metadata: Metadata) -> Self {
+        self.same_file = Some(metadata);
+        self
+    }
+
+    /// Matches entries with this number of hard links.
+    #[must_use]
+    pub fn links(mut self, count: LinkCount) -> Self {
+        self.links = Some(count);
+        self
+    }
+
+    /// Matches entries with an extended attribute like `user.project=alpha`,
+    /// all of the added attributes are required.
+    #[must_use]
+    pub fn xattr(mut self, pattern: XattrPattern) -> Self {
+        self.xattrs.push(pattern);
+        self
+    }
+
+    /// Matches entries carrying a POSIX ACL.
+    #[must_use]
+    pub fn acl(mut self, acl: bool) -> Self {
+        self.acl = acl;
+        self
+    }
+
+    /// Matches entries whose SELinux context matches the glob.
+    #[must_use]
+    pub fn security_context(mut self, context: Glob) -> Self {
+        self.security_context = Some(context);
+        self
+    }
+
+    /// Matches entries with any of the statuses in `state`, read with [`GitState::read`].
+    #[must_use]
+    pub fn git_status(mut self, state: Arc<GitState>, statuses: Vec<GitStatus>) -> Self {
+        self.git_status = Some((state, statuses));
+        self
+    }
+
+    /// Matches entries changed since the revision `state` was read with.
+    #[must_use]
+    pub fn changed_since(mut self, state: Arc<GitState>) -> Self {
+        self.changed_since = Some(state);
+        self
+    }
+
+    #[must_use]
+    pub fn min_size(mut self, min_size: u64) -> Self {
+        self.min_size = Some(min_size);
+        self
+    }
+
+    #[must_use]
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    #[must_use]
+    pub fn older_than(mut self, age: Duration) -> Self {
+        self.older_than = Some(age);
+        self
+    }
+
+    #[must_use]
+    pub fn newer_than(mut self, age: Duration) -> Self {
+        self.newer_than = Some(age);
+        self
+    }
+
+    /// Adds a filter of its own, e.g. one the library does not provide. It runs
+    /// after the filters on names and metadata and before the content filter.
+    #[must_use]
+    pub fn filter(mut self, filter: impl SearchFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    #[must_use]
+    pub fn options(mut self, options: SearchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    #[must_use]
+    pub fn traversal(mut self, traversal: TraversalOptions) -> Self {
+        self.options.traversal = traversal;
+        self
+    }
+
+    #[must_use]
+    pub fn min_depth(mut self, min_depth: usize) -> Self {
+        self.options.traversal.min_depth = min_depth;
+        self
+    }
+
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.options.traversal.max_depth = max_depth;
+        self
+    }
+
+    #[must_use]
+    pub fn top_level(mut self, top_level: bool) -> Self {
+        self.options.traversal.mode = if top_level {
+            SearchMode::TopLevelOnly
+        } else {
+            SearchMode::Recursive
+        };
+        self
+    }
+
+    /// Leaves out the entries matching any of the globs and everything below
+    /// them, see [`crate::exclude::Exclusions`].
+    #[must_use]
+    pub fn exclude(mut self, globs: impl IntoIterator<Item = Glob>) -> Self {
+        for glob in globs {
+            self.options.traversal.exclude.add_pattern(glob);
+        }
+        self
+    }
+
+    /// Does not walk into the directories whose name matches any of the globs.
+    #[must_use]
+    pub fn exclude_dir(mut self, globs: impl IntoIterator<Item = Glob>) -> Self {
+        for glob in globs {
+            self.options.traversal.exclude.add_directory(glob);
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn follow_links(mut self, follow_links: bool) -> Self {
+        self.options.traversal.follow_links = follow_links;
+        self
+    }
+
+    #[must_use]
+    pub fn same_file_system(mut self, same_file_system: bool) -> Self {
+        self.options.traversal.same_file_system = same_file_system;
+        self
+    }
+
+    #[must_use]
+    pub fn sort_by_name(mut self, sort_by_name: bool) -> Self {
+        self.options.traversal.sort_by_name = sort_by_name;
+        self
+    }
+
+    #[must_use]
+    pub fn search_archives(mut self, search_archives: bool) -> Self {
+        self.options.search_archives = search_archives;
+        self
+    }
+
+    /// Stops the search once `token` is canceled, see [`FileSearcher::with_cancellation`].
+    #[must_use]
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Reports the progress of the search, see [`FileSearcher::with_progress`].
+    #[must_use]
+    pub fn progress(mut self, progress: impl Fn(&SearchProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Creates the filters of the search, the searcher of the library is
+    /// available through [`PreparedSearch::searcher`].
+    #[must_use]
+    pub fn build(self) -> PreparedSearch {
+        let mut filters: Vec<Box<dyn SearchFilter>> = Vec::new();
+
+        for (words, match_option) in self.names.into_iter().filter(|x| !x.0.is_empty()) {
+            filters.push(Box::new(FilenameFilter::new(
+                &words,
+                match_option,
+                self.case_sensitiv,
+            )));
+        }
+
+        if let Some(kind) = self.kind {
+            filters.push(Box::new(EntryTypeFilter::new(kind)));
+        }
+
+        if !self.extensions.is_empty() {
+            filters.push(Box::new(ExtensionFilter::new(&self.extensions)));
+        }
+
+        if let Some(metadata) = &self.same_file {
+            filters.push(Box::new(SameFileFilter::new(metadata)));
+        }
+
+        if let Some(count) = self.links {
+            filters.push(Box::new(LinkCountFilter::new(count)));
+        }
+
+        for pattern in self.xattrs {
+            filters.push(Box::new(XattrFilter::new(pattern)));
+        }
+
+        if self.acl {
+            filters.push(Box::new(AclFilter));
+        }
+
+        if let Some(context) = &self.security_context {
+            filters.push(Box::new(SecurityContextFilter::new(context)));
+        }
+
+        if let Some((state, statuses)) = self.git_status.filter(|x| !x.1.is_empty()) {
+            filters.push(Box::new(GitStatusFilter::new(state, statuses)));
+        }
+
+        if let Some(state) = self.changed_since {
+            filters.push(Box::new(ChangedSinceFilter::new(state)));
+        }
+
+        if self.min_size.is_some() || self.max_size.is_some() {
+            filters.push(Box::new(SizeFilter::new(self.min_size, self.max_size)));
+        }
+
+        if self.older_than.is_some() || self.newer_than.is_some() {
+            filters.push(Box::new(ModifiedFilter::new(
+                self.older_than,
+                self.newer_than,
+            )));
+        }
+
+        // the filters run in this order and stop at the first mismatch, those
+        // that read the head or the content of files come last
+        if !self.file_types.is_empty() {
+            filters.push(Box::new(FileTypeFilter::new(self.file_types)));
+        }
+
+        if !self.mime_types.is_empty() {
+            filters.push(Box::new(MimeFilter::new(
+                &self.mime_types,
+                self.type_definitions.unwrap_or_default(),
+            )));
+        }
+
+        filters.extend(self.filters);
+
+        for (words, match_option) in self.contents.into_iter().filter(|x| !x.0.is_empty()) {
+            filters.push(Box::new(FileContentFilter::new(
+                &words,
+                match_option,
+                self.case_sensitiv,
+            )));
+        }
+
+        let roots = self
+            .roots
+            .into_iter()
+            .map(|(path, options)| {
+                SearchRoot::new(
+                    path,
+                    options.unwrap_or_else(|| self.options.traversal.clone()),
+                )
+            })
+            .collect();
+
+        let mut searcher = FileSearcher::with_options(filters, self.options);
+        if let Some(token) = self.cancellation {
+            searcher = searcher.with_cancellation(token);
+        }
+        if let Some(progress) = self.progress {
+            searcher = searcher.with_progress(progress);
+        }
+
+        PreparedSearch {
+            searcher,
+            roots,
+            files: self.files,
+        }
+    }
+}
+
+impl PreparedSearch {
+    /// Searches the roots and then checks the files.
+    #[must_use]
+    pub fn run(&self) -> SearchOutput {
+        let mut output = SearchOutput::default();
+        self.run_with(|x| output.push(x));
+        output
+    }
+
+    /// Hands each result and warning to `callback`, see [`FileSearcher::search_roots_with`].
+    pub fn run_with(&self, mut callback: impl FnMut(SearchEvent) -> ControlFlow<()>) {
+        let mut stopped = false;
+        self.searcher.search_roots_with(&self.roots, |x| {
+            let flow = callback(x);
+            stopped = flow.is_break();
+            flow
+        });
+
+        if !stopped {
+            self.searcher.search_files_with(&self.files, callback);
+        }
+    }
+
+    /// Answers the search from `index`, roots it does not cover are searched on
+    /// disk, the files are always checked on disk.
+    #[must_use]
+    pub fn run_with_index(&self, index: &FileIndex) -> SearchOutput {
+        let mut output = SearchOutput::default();
+        self.run_index_with(index, |x| output.push(x));
+        output
+    }
+
+    /// Like [`PreparedSearch::run_with_index`], but hands each result and warning to `callback`.
+    pub fn run_index_with(
+        &self,
+        index: &FileIndex,
+        mut callback: impl FnMut(SearchEvent) -> ControlFlow<()>,
+    ) {
+        let mut stopped = false;
+        self.searcher.search_index_with(index, &self.roots, |x| {
+            let flow = callback(x);
+            stopped = flow.is_break();
+            flow
+        });
+
+        if !stopped {
+            self.searcher.search_files_with(&self.files, callback);
+        }
+    }
+
+    #[must_use]
+    pub fn searcher(&self) -> &FileSearcher {
+        &self.searcher
+    }
+
+    #[must_use]
+    pub fn roots(&self) -> &[SearchRoot] {
+        &self.roots
+    }
+
+    #[must_use]
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Runs the search on the blocking thread pool of tokio, see [`crate::stream::search_stream`].
+    #[cfg(feature = "async")]
+    #[must_use]
+    pub fn into_stream(self, capacity: usize) -> crate::stream::SearchStream {
+        crate::stream::prepared_search_stream(self, capacity)
+    }
+
+    /// The cancellation token of the searcher, a new one is set if it has none.
+    #[cfg(feature = "async")]
+    pub(crate) fn cancellation_token(&mut self) -> CancellationToken {
+        self.searcher.cancellation_token()
+    }
+
+    /// Splits the search, e.g. to hand the searcher to a [`crate::watch::SearchWatcher`],
+    /// the files are dropped.
+    #[must_use]
+    pub fn into_parts(self) -> (FileSearcher, Vec<SearchRoot>) {
+        (self.searcher, self.roots)
+    }
+}
+
+fn to_os_strings<S: AsRef<OsStr>>(words: impl IntoIterator<Item = S>) -> Vec<OsString> {
+    words
+        .into_iter()
+        .map(|x| x.as_ref().to_os_string())
+        .collect()
+}
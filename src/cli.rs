@@ -1,30 +1,179 @@
-use std::fmt::Display;
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    fs::File,
+    io,
+    path::PathBuf,
+    time::Duration,
+};
 
-use clap::{command, Args, Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use globset::Glob;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive::ArchiveKind,
+    error::{FsOperation, FsRsError},
+    exclude::Exclusions,
+    git::GitStatus,
+    links::LinkCount,
+    lint::LintCheck,
+    permissions::{ModeChange, OwnerChange, PermissionRule},
+    search::{read_file_list, SearchMode, SearchRoot, TraversalOptions},
+    xattrs::XattrPattern,
+};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about)]
 pub struct Cli {
     #[arg(short, long)]
-    pub search_paths: Vec<String>,
+    pub search_paths: Vec<PathBuf>,
     #[arg(short, long, default_value_t = 10000)]
     pub depth: usize,
     #[arg(short, long, default_value_t = 10)]
     pub max_results: usize,
+    /// Skip entries less deep than this, the search paths themselves have depth 0.
+    #[arg(long, default_value_t = 0)]
+    pub min_depth: usize,
+    /// Only search the direct children of the search paths.
+    #[arg(long, default_value_t = false)]
+    pub top_level: bool,
+    /// Follow symbolic links, link loops are reported as errors.
+    #[arg(short = 'L', long, default_value_t = false)]
+    pub follow: bool,
+    /// Do not descend into directories on other filesystems.
+    #[arg(long, visible_alias = "xdev", default_value_t = false)]
+    pub one_file_system: bool,
+    /// Visit the entries of each directory sorted by name.
+    #[arg(long, default_value_t = false)]
+    pub sort: bool,
+    /// Skip the entries matching this glob and everything below them, e.g.
+    /// `*.log`. Globs with a `/` match the path, e.g. `target/debug` or `/home/*/.cache`.
+    #[arg(long)]
+    pub exclude: Vec<Glob>,
+    /// Do not walk into the directories with this name, e.g. `node_modules`, globs are allowed.
+    #[arg(long)]
+    pub exclude_dir: Vec<Glob>,
+    /// Traversal options for a single search path, e.g. `/etc=top-level` or
+    /// `~/src=max-depth:50,follow`. Options are `top-level`, `recursive`,
+    /// `min-depth:N`, `max-depth:N`, `follow`, `one-file-system` and `sort`.
+    #[arg(long, value_parser = parse_root_options)]
+    pub root_options: Vec<RootOptions>,
+    /// Check the paths read from stdin without walking into them, separated by
+    /// newlines or NUL, e.g. `git ls-files -z | fs-rs --from-stdin name -n .rs`.
+    #[arg(long, default_value_t = false, conflicts_with = "files_from")]
+    pub from_stdin: bool,
+    /// Check the paths listed in this file, see `--from-stdin`.
+    #[arg(long)]
+    pub files_from: Option<PathBuf>,
+    #[arg(long, default_value_t = false)]
+    pub search_archives: bool,
+    /// Answer name, size and time queries from the file index.
+    #[arg(long, default_value_t = false)]
+    pub use_index: bool,
+    /// Location of the file index, defaults to `$XDG_CACHE_HOME/fs-rs/index.db`.
+    #[arg(long)]
+    pub index_file: Option<PathBuf>,
+    /// List every entry that could not be searched instead of a summary.
+    #[arg(long, default_value_t = false)]
+    pub show_errors: bool,
+    /// Print the results as JSON instead of showing the interactive menu.
+    #[arg(long, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// Run the search saved under this name in the config file.
+    #[arg(short, long)]
+    pub profile: Option<String>,
+    /// Location of the config file, defaults to `$XDG_CONFIG_HOME/fs-rs/config.toml`.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// Write the results to this file, they can be opened again with `--load-results`.
+    #[arg(long)]
+    pub save_results: Option<PathBuf>,
+    /// Open the results saved with `--save-results` instead of searching.
+    #[arg(long)]
+    pub load_results: Option<PathBuf>,
+    /// Do not add the search to the history.
+    #[arg(long, default_value_t = false)]
+    pub no_history: bool,
+    /// Rename the results instead of showing them, e.g. `{stem}-{n:03}{ext}`.
+    /// Placeholders are `{name}`, `{stem}`, `{ext}`, `{n}`, `{n:03}` and
+    /// `{mtime:%Y-%m-%d}`, `{{` and `}}` are literal braces.
+    #[arg(long)]
+    pub rename: Option<String>,
+    /// Only replace the matches of this regex in the names, the `--rename`
+    /// template may refer to its captures as `$1` or `${name}`.
+    #[arg(long, requires = "rename")]
+    pub rename_pattern: Option<String>,
+    /// Convert the case of the new names.
+    #[arg(long, requires = "rename")]
+    pub rename_case: Option<CaseConversion>,
+    /// Pack the results into this archive instead of showing them, the format
+    /// follows the extension: `.tar`, `.tar.gz`, `.tar.zst`, `.tar.xz` or `.zip`.
+    #[arg(long, value_parser = parse_archive_path, conflicts_with = "rename")]
+    pub archive: Option<PathBuf>,
+    /// Change the mode of the results instead of showing them, octal like
+    /// `755` or symbolic like `u+x,go-w`.
+    #[arg(long, value_parser = parse_mode, conflicts_with_all = ["rename", "archive"])]
+    pub chmod: Option<ModeChange>,
+    /// Change the owner of the results instead of showing them, `user`,
+    /// `user:group`, `user:` or `:group`.
+    #[arg(long, value_parser = parse_owner, conflicts_with_all = ["rename", "archive"])]
+    pub chown: Option<OwnerChange>,
+    /// Apply `--chmod` and `--chown` to everything inside of the found directories.
+    #[arg(long, default_value_t = false)]
+    pub recursive: bool,
+    /// Only show the changes of actions like `--rename` and `replace`.
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+    /// Apply actions like `--rename` and `replace` without asking, `--archive`
+    /// overwrites an existing file.
+    #[arg(short, long, default_value_t = false)]
+    pub yes: bool,
     #[command(subcommand)]
     pub search: Option<Search>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootOptions {
+    pub path: PathBuf,
+    pub options: Vec<RootOption>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RootOption {
+    TopLevel,
+    Recursive,
+    MinDepth(usize),
+    MaxDepth(usize),
+    Follow,
+    OneFileSystem,
+    Sort,
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum Search {
     Name(SearchTypeArgs),
     Content(SearchTypeArgs),
+    Dupes(DupesArgs),
+    Usage(UsageArgs),
+    /// Find dangling and cyclic symlinks, empty files and other entries to clean up.
+    Lint(LintArgs),
+    Index(IndexArgs),
+    Watch(WatchArgs),
+    /// Replace text in the contents of the found files, after a diff preview.
+    Replace(ReplaceArgs),
+    History(HistoryArgs),
+    Results(ResultsArgs),
+    /// List and undo the changes of `--chmod` and `--chown`.
+    Permissions(PermissionsArgs),
+    /// List the file types of `--type`, with those of the config file.
+    Types,
 }
 
 #[derive(Debug, Args, Clone)]
 pub struct SearchTypeArgs {
     #[arg(short, long)]
-    pub names: Vec<String>,
+    pub names: Vec<OsString>,
     #[arg(short, long, default_value_t = true)]
     pub case_sensisitiv: bool,
     #[arg(short, long, default_value_t = MatchOption::Any)]
@@ -33,9 +182,198 @@ pub struct SearchTypeArgs {
     pub order_by: Option<OrderBy>,
     #[arg(short, long, default_value_t = ResultFilter::All)]
     pub result_filter: ResultFilter,
+    #[command(flatten)]
+    pub filters: FilterArgs,
 }
 
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+/// The filters on the type, metadata and git status of entries, shared by the
+/// searches and `fs-rs usage`.
+#[derive(Debug, Args, Clone)]
+#[command(about = None, long_about = None)]
+pub struct FilterArgs {
+    /// Only show files of these types, e.g. `rust` or `image`, see `fs-rs types`.
+    #[arg(short = 't', long = "type", value_delimiter = ',')]
+    pub types: Vec<String>,
+    /// Only show files with these extensions, e.g. `md,txt`.
+    #[arg(long = "ext", value_delimiter = ',')]
+    pub extensions: Vec<String>,
+    /// Only show files with these MIME types, e.g. `text/*` or `image/png`.
+    #[arg(long, value_delimiter = ',')]
+    pub mime: Vec<String>,
+    /// Only show the hard links to this file, like `find -samefile`.
+    #[arg(long)]
+    pub same_file: Option<PathBuf>,
+    /// Only show entries with this number of hard links, `+N` for more and `-N` for less.
+    #[arg(long, value_parser = parse_links, allow_hyphen_values = true)]
+    pub links: Option<LinkCount>,
+    /// Only show entries with this extended attribute, `NAME` or `NAME=VALUE`,
+    /// e.g. `user.project=alpha` or `user.*`. All given attributes are required.
+    #[arg(long, value_parser = parse_xattr)]
+    pub xattr: Vec<XattrPattern>,
+    /// Only show entries carrying a POSIX ACL.
+    #[arg(long, default_value_t = false)]
+    pub acl: bool,
+    /// Only show entries whose SELinux context matches this glob, e.g. `*:httpd_sys_content_t:*`.
+    #[arg(long)]
+    pub selinux_context: Option<Glob>,
+    /// Only show entries with any of these statuses in their git repository.
+    #[arg(long, value_delimiter = ',')]
+    pub git_status: Vec<GitStatus>,
+    /// Only show entries changed since this git revision, like `git diff --name-only REV`.
+    #[arg(long, value_name = "REV")]
+    pub changed_since: Option<String>,
+    /// Minimum file size, e.g. `100`, `10K`, `100M` or `1G`.
+    #[arg(long, value_parser = parse_size)]
+    pub min_size: Option<u64>,
+    /// Maximum file size, e.g. `100`, `10K`, `100M` or `1G`.
+    #[arg(long, value_parser = parse_size)]
+    pub max_size: Option<u64>,
+    /// Only show entries last modified more than this many days ago.
+    #[arg(long)]
+    pub older_than: Option<u64>,
+    /// Only show entries last modified less than this many days ago.
+    #[arg(long)]
+    pub newer_than: Option<u64>,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct DupesArgs {
+    pub paths: Vec<PathBuf>,
+    #[arg(short, long)]
+    pub names: Vec<OsString>,
+    #[arg(short, long, default_value_t = true)]
+    pub case_sensisitiv: bool,
+    #[arg(short, long, default_value_t = MatchOption::Any)]
+    pub match_option: MatchOption,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct UsageArgs {
+    pub paths: Vec<PathBuf>,
+    #[arg(short, long)]
+    pub names: Vec<OsString>,
+    #[arg(short, long, default_value_t = true)]
+    pub case_sensisitiv: bool,
+    #[arg(short, long, default_value_t = MatchOption::Any)]
+    pub match_option: MatchOption,
+    #[command(flatten)]
+    pub filters: FilterArgs,
+    /// How many of the largest directories, files and extensions to show.
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+    /// Count the size of a file with several hard links at each of them, like `du -l`.
+    #[arg(short = 'l', long, default_value_t = false)]
+    pub count_links: bool,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct LintArgs {
+    pub paths: Vec<PathBuf>,
+    /// The checks to run, all by default.
+    #[arg(short, long, value_delimiter = ',')]
+    pub checks: Vec<LintCheck>,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct ReplaceArgs {
+    pub paths: Vec<PathBuf>,
+    /// Only replace in files whose name contains these words.
+    #[arg(short, long)]
+    pub names: Vec<OsString>,
+    #[arg(short, long, default_value_t = MatchOption::Any)]
+    pub match_option: MatchOption,
+    /// The text to replace.
+    #[arg(short, long)]
+    pub find: String,
+    /// The new text, with `--regex` it can refer to captures as `$1` or `${name}`.
+    #[arg(short = 'w', long = "with")]
+    pub replacement: String,
+    /// Read `--find` as a regex instead of literal text.
+    #[arg(short, long, default_value_t = false)]
+    pub regex: bool,
+    /// Match the text and the names case insensitive.
+    #[arg(short, long, default_value_t = false)]
+    pub ignore_case: bool,
+    /// Keep the original of every changed file with this suffix, e.g. `.bak`.
+    #[arg(short, long)]
+    pub backup: Option<String>,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct IndexArgs {
+    #[command(subcommand)]
+    pub action: IndexAction,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum IndexAction {
+    /// Index the given paths from scratch.
+    Build { paths: Vec<PathBuf> },
+    /// Re-index changed directories of the given paths, or of all indexed paths.
+    Update { paths: Vec<PathBuf> },
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub action: Option<HistoryAction>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum HistoryAction {
+    /// List the searches in the history, the default.
+    List,
+    /// Run the search with the given id again.
+    Run { id: u64 },
+    /// Remove all searches from the history.
+    Clear,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct PermissionsArgs {
+    #[command(subcommand)]
+    pub action: Option<PermissionsAction>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PermissionsAction {
+    /// List the recorded permission changes, the default.
+    List,
+    /// Restore the permissions before the change with the given id, the latest by default.
+    Undo { id: Option<u64> },
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct ResultsArgs {
+    #[command(subcommand)]
+    pub action: ResultsAction,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ResultsAction {
+    /// Open the results of the search with the given id from the history.
+    Load { id: u64 },
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct WatchArgs {
+    pub paths: Vec<PathBuf>,
+    #[arg(short, long)]
+    pub names: Vec<OsString>,
+    #[arg(short, long)]
+    pub words: Vec<OsString>,
+    #[arg(short, long, default_value_t = true)]
+    pub case_sensisitiv: bool,
+    #[arg(short, long, default_value_t = MatchOption::Any)]
+    pub match_option: MatchOption,
+    #[arg(short, long, default_value_t = ResultFilter::All)]
+    pub result_filter: ResultFilter,
+    #[arg(short, long, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 pub enum MatchOption {
     All,
     Any,
@@ -50,7 +388,8 @@ pub enum OrderBy {
     Size,
 }
 
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 pub enum ResultFilter {
     All,
     Directory,
@@ -58,6 +397,22 @@ pub enum ResultFilter {
     SymLink,
 }
 
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaseConversion {
+    Lower,
+    Upper,
+    Title,
+}
+
 impl Display for MatchOption {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -78,3 +433,270 @@ impl Display for ResultFilter {
         }
     }
 }
+
+impl Display for CaseConversion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaseConversion::Lower => write!(f, "lower"),
+            CaseConversion::Upper => write!(f, "upper"),
+            CaseConversion::Title => write!(f, "title"),
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Ndjson => write!(f, "ndjson"),
+        }
+    }
+}
+
+/// Parses sizes like `512`, `10K`, `100M` or `2G` into bytes.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, factor) = match value.chars().last().map(|x| x.to_ascii_uppercase()) {
+        Some('K') => (&value[..value.len() - 1], 1024),
+        Some('M') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        Some('T') => (&value[..value.len() - 1], 1024 * 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+
+    number
+        .trim()
+        .parse::<u64>()
+        .map_err(|why| format!("invalid size '{value}': {why}"))?
+        .checked_mul(factor)
+        .ok_or_else(|| format!("invalid size '{value}': number too large to fit in target type"))
+}
+
+/// The age of `--older-than` and `--newer-than` given in days, huge values
+/// saturate instead of overflowing.
+#[must_use]
+pub fn days(count: u64) -> Duration {
+    Duration::from_secs(count.saturating_mul(60 * 60 * 24))
+}
+
+pub fn parse_links(value: &str) -> Result<LinkCount, String> {
+    LinkCount::parse(value)
+}
+
+pub fn parse_xattr(value: &str) -> Result<XattrPattern, String> {
+    XattrPattern::parse(value)
+}
+
+/// Accepts paths with the extension of an archive format that can be written.
+pub fn parse_archive_path(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+
+    match ArchiveKind::detect(&path) {
+        Some(kind) if kind.can_create() => Ok(path),
+        _ => Err(format!(
+            "unsupported archive '{value}', use .tar, .tar.gz, .tar.zst, .tar.xz or .zip"
+        )),
+    }
+}
+
+pub fn parse_mode(value: &str) -> Result<ModeChange, String> {
+    ModeChange::parse(value).map_err(|why| why.to_string())
+}
+
+/// Resolves the user and group names, so unknown ones fail before searching.
+pub fn parse_owner(value: &str) -> Result<OwnerChange, String> {
+    OwnerChange::parse(value).map_err(|why| why.to_string())
+}
+
+impl Cli {
+    /// The permission changes given with `--chmod` and `--chown`.
+    #[must_use]
+    pub fn permission_rule(&self) -> PermissionRule {
+        PermissionRule {
+            mode: self.chmod.clone(),
+            owner: self.chown,
+        }
+    }
+
+    /// The traversal options given by the global flags.
+    #[must_use]
+    pub fn traversal_options(&self) -> TraversalOptions {
+        TraversalOptions {
+            mode: if self.top_level {
+                SearchMode::TopLevelOnly
+            } else {
+                SearchMode::Recursive
+            },
+            min_depth: self.min_depth,
+            max_depth: self.depth,
+            follow_links: self.follow,
+            same_file_system: self.one_file_system,
+            sort_by_name: self.sort,
+            exclude: Exclusions::new(
+                self.exclude.iter().cloned(),
+                self.exclude_dir.iter().cloned(),
+            ),
+        }
+    }
+
+    /// Whether paths to check are given with `--from-stdin` or `--files-from`.
+    #[must_use]
+    pub fn reads_file_list(&self) -> bool {
+        self.from_stdin || self.files_from.is_some()
+    }
+
+    /// Reads the paths given with `--from-stdin` or `--files-from`, empty if there are none.
+    pub fn read_file_list(&self) -> Result<Vec<PathBuf>, FsRsError> {
+        if self.from_stdin {
+            return read_file_list(io::stdin().lock())
+                .map_err(|e| FsRsError::filesystem(FsOperation::Read, "<stdin>", e));
+        }
+
+        match &self.files_from {
+            Some(path) => File::open(path)
+                .and_then(read_file_list)
+                .map_err(|e| FsRsError::filesystem(FsOperation::Read, path, e)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Combines `paths` with the `--root-options`, paths only given there are searched too.
+    #[must_use]
+    pub fn search_roots(&self, paths: &[PathBuf]) -> Vec<SearchRoot> {
+        let mut roots: Vec<SearchRoot> = paths
+            .iter()
+            .map(|x| SearchRoot::new(x.clone(), self.traversal_options()))
+            .collect();
+
+        for root_options in &self.root_options {
+            let root = match roots.iter_mut().find(|x| x.path == root_options.path) {
+                Some(r) => r,
+                None => {
+                    roots.push(SearchRoot::new(
+                        root_options.path.clone(),
+                        self.traversal_options(),
+                    ));
+                    roots.last_mut().expect("root was just added")
+                }
+            };
+
+            for option in &root_options.options {
+                option.apply(&mut root.options);
+            }
+        }
+
+        roots
+    }
+}
+
+impl RootOption {
+    pub fn apply(self, options: &mut TraversalOptions) {
+        match self {
+            RootOption::TopLevel => options.mode = SearchMode::TopLevelOnly,
+            RootOption::Recursive => options.mode = SearchMode::Recursive,
+            RootOption::MinDepth(d) => options.min_depth = d,
+            RootOption::MaxDepth(d) => options.max_depth = d,
+            RootOption::Follow => options.follow_links = true,
+            RootOption::OneFileSystem => options.same_file_system = true,
+            RootOption::Sort => options.sort_by_name = true,
+        }
+    }
+}
+
+/// Parses `PATH=OPTION[,OPTION...]`, see [`Cli::root_options`].
+pub fn parse_root_options(value: &str) -> Result<RootOptions, String> {
+    let (path, options) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected PATH=OPTIONS, got '{value}'"))?;
+
+    let options = options
+        .split(',')
+        .map(|option| {
+            let (name, argument) = match option.split_once(':') {
+                Some((n, a)) => (n, Some(a)),
+                None => (option, None),
+            };
+            let depth = || {
+                argument
+                    .ok_or_else(|| format!("'{name}' needs a depth, e.g. '{name}:3'"))?
+                    .parse::<usize>()
+                    .map_err(|why| format!("invalid depth for '{name}': {why}"))
+            };
+
+            match name.trim() {
+                "top-level" => Ok(RootOption::TopLevel),
+                "recursive" => Ok(RootOption::Recursive),
+                "min-depth" => Ok(RootOption::MinDepth(depth()?)),
+                "max-depth" => Ok(RootOption::MaxDepth(depth()?)),
+                "follow" => Ok(RootOption::Follow),
+                "one-file-system" | "xdev" => Ok(RootOption::OneFileSystem),
+                "sort" => Ok(RootOption::Sort),
+                _ => Err(format!("unknown traversal option '{option}'")),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(RootOptions {
+        path: PathBuf::from(path),
+        options,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size(" 100m "), Ok(100 * 1024 * 1024));
+        assert_eq!(parse_size("2G"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1T"), Ok(1024 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn parse_size_invalid() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("1.5G").is_err());
+    }
+
+    #[test]
+    fn days_saturate() {
+        assert_eq!(days(2), Duration::from_secs(2 * 24 * 60 * 60));
+        assert_eq!(days(999_999_999_999_999_999), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn parse_size_overflow() {
+        assert!(parse_size("99999999999999999G").is_err());
+        assert!(parse_size("16777216T").is_err());
+        assert_eq!(parse_size("16777215T"), Ok(16_777_215 * 1024 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn usage_takes_the_search_filters() {
+        let cli = Cli::try_parse_from([
+            "fs-rs", "usage", "/var", "--ext", "log", "--min-size", "1K", "--older-than", "30",
+            "--top", "5",
+        ])
+        .unwrap();
+
+        let Some(Search::Usage(args)) = cli.search else {
+            panic!("not the usage subcommand");
+        };
+        assert_eq!(args.paths, [PathBuf::from("/var")]);
+        assert_eq!(args.filters.extensions, ["log"]);
+        assert_eq!(args.filters.min_size, Some(1024));
+        assert_eq!(args.filters.older_than, Some(30));
+        assert_eq!(args.top, 5);
+    }
+
+    #[test]
+    fn command_is_valid() {
+        <Cli as clap::CommandFactory>::command().debug_assert();
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use clap::{parser::ValueSource, ArgMatches};
+use globset::Glob;
+use serde::{Deserialize, Deserializer, Serialize};
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::{
+    builder::SearchBuilder,
+    cli::{days, parse_size, Cli, MatchOption, OutputFormat, ResultFilter},
+    error::{ConfigError, FsOperation, FsRsError},
+    filetype::{FileTypeDefinition, FileTypes},
+    filter::{FileContentFilter, FilenameFilter},
+};
+
+/// The config file with defaults for the command line and saved search profiles.
+///
+/// ```toml
+/// [defaults]
+/// max-results = 20
+/// exclude-dir = ["node_modules", ".git"]
+///
+/// [profiles.todos]
+/// paths = ["src"]
+/// kind = "file"
+/// names = { words = [".rs"] }
+/// contents = { words = ["TODO", "FIXME"], match = "any", case-sensitiv = true }
+///
+/// [profiles.large-logs]
+/// paths = ["/var/log"]
+/// min-size = "100M"
+/// format = "json"
+///
+/// [types.web]
+/// include = ["html", "css", "js"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Defaults for the global options, options given on the command line take precedence.
+    pub defaults: Settings,
+    pub profiles: BTreeMap<String, Profile>,
+    /// File types for `--type`, they extend the built-in types of the same name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub types: BTreeMap<String, FileTypeDefinition>,
+}
+
+/// Global options of the command line, `None` keeps the value of the command line.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_results: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_level: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub one_file_system: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_archives: Option<bool>,
+    /// Globs of the entries to skip, e.g. `["*.log", "target/debug"]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Vec<Glob>>,
+    /// Names of the directories not to walk into, e.g. `["node_modules", ".git"]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_dir: Option<Vec<Glob>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_index: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_errors: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+}
+
+/// A saved search, run with `fs-rs --profile NAME`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Profile {
+    /// Searched if no paths are given on the command line.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ResultFilter>,
+    /// Minimum file size, e.g. `1024` or `"100M"`.
+    #[serde(
+        deserialize_with = "deserialize_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub min_size: Option<u64>,
+    #[serde(
+        deserialize_with = "deserialize_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_size: Option<u64>,
+    /// Days since the last modification.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub older_than: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newer_than: Option<u64>,
+    #[serde(flatten)]
+    pub settings: Settings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub names: Option<WordFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contents: Option<WordFilter>,
+}
+
+/// Words to look for in the names or contents of the entries.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WordFilter {
+    pub words: Vec<String>,
+    #[serde(rename = "match", default = "default_match_option")]
+    pub match_option: MatchOption,
+    #[serde(default)]
+    pub case_sensitiv: bool,
+}
+
+impl Config {
+    /// Default location of the config, `$XDG_CONFIG_HOME/fs-rs/config.toml`.
+    #[must_use]
+    pub fn default_path() -> PathBuf {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|x| PathBuf::from(x).join(".config")))
+            .unwrap_or_else(env::temp_dir);
+
+        config_dir.join("fs-rs").join("config.toml")
+    }
+
+    /// Loads the config at `path`, a missing file is an empty config.
+    pub fn load(path: &Path) -> Result<Self, FsRsError> {
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(why) if why.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(why) => return Err(FsRsError::filesystem(FsOperation::Read, path, why)),
+        };
+
+        toml::from_str(&content).map_err(|why| {
+            FsRsError::Config(ConfigError::Parse {
+                path: path.to_path_buf(),
+                source: why,
+            })
+        })
+    }
+
+    /// Adds the profile to the config at `path`, or replaces the one with the same
+    /// name, leaving the comments and formatting of the rest of the file as they are.
+    pub fn save_profile(path: &Path, name: &str, profile: &Profile) -> Result<(), FsRsError> {
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(why) if why.kind() == ErrorKind::NotFound => String::new(),
+            Err(why) => return Err(FsRsError::filesystem(FsOperation::Read, path, why)),
+        };
+        let edit_error = |why| {
+            FsRsError::Config(ConfigError::Edit {
+                path: path.to_path_buf(),
+                source: why,
+            })
+        };
+
+        let mut document = content.parse::<DocumentMut>().map_err(edit_error)?;
+        let mut profile = toml::to_string_pretty(profile)
+            .map_err(|why| FsRsError::Config(ConfigError::Serialize(why)))?
+            .parse::<DocumentMut>()
+            .map_err(edit_error)?
+            .into_table();
+        clear_positions(&mut profile);
+
+        let profiles = document
+            .entry("profiles")
+            .or_insert_with(|| {
+                let mut profiles = Table::new();
+                profiles.set_implicit(true);
+                Item::Table(profiles)
+            })
+            .as_table_like_mut()
+            .ok_or_else(|| FsRsError::Config(ConfigError::NotATable("profiles".to_string())))?;
+        profiles.insert(name, Item::Table(profile));
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| FsRsError::filesystem(FsOperation::CreateDirectory, parent, e))?;
+        }
+
+        fs::write(path, document.to_string())
+            .map_err(|e| FsRsError::filesystem(FsOperation::Write, path, e))
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, FsRsError> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| FsRsError::Config(ConfigError::UnknownProfile(name.to_string())))
+    }
+
+    /// The built-in file types together with those of the config.
+    #[must_use]
+    pub fn file_types(&self) -> FileTypes {
+        FileTypes::builtin().with_definitions(&self.types)
+    }
+}
+
+impl Settings {
+    /// Overrides the options of `cli` that were not given on the command line.
+    pub fn apply(&self, cli: &mut Cli, matches: &ArgMatches) {
+        set(matches, "depth", &mut cli.depth, self.depth);
+        set(matches, "max_results", &mut cli.max_results, self.max_results);
+        set(matches, "min_depth", &mut cli.min_depth, self.min_depth);
+        set(matches, "top_level", &mut cli.top_level, self.top_level);
+        set(matches, "follow", &mut cli.follow, self.follow);
+        set(matches, "one_file_system", &mut cli.one_file_system, self.one_file_system);
+        set(matches, "sort", &mut cli.sort, self.sort);
+        set(matches, "search_archives", &mut cli.search_archives, self.search_archives);
+        set(matches, "exclude", &mut cli.exclude, self.exclude.clone());
+        set(matches, "exclude_dir", &mut cli.exclude_dir, self.exclude_dir.clone());
+        set(matches, "use_index", &mut cli.use_index, self.use_index);
+        set(matches, "index_file", &mut cli.index_file, self.index_file.clone().map(Some));
+        set(matches, "show_errors", &mut cli.show_errors, self.show_errors);
+        set(matches, "format", &mut cli.format, self.format);
+    }
+}
+
+impl Profile {
+    /// Overrides the options of `cli` that were not given on the command line,
+    /// the paths of the profile are used if there are none and no file list.
+    pub fn apply(&self, cli: &mut Cli, matches: &ArgMatches) {
+        self.settings.apply(cli, matches);
+
+        if cli.search_paths.is_empty() && cli.root_options.is_empty() && !cli.reads_file_list() {
+            cli.search_paths.clone_from(&self.paths);
+        }
+    }
+
+    /// Adds the filters of the profile to `search`.
+    #[must_use]
+    pub fn apply_filters(&self, mut search: SearchBuilder) -> SearchBuilder {
+        if let Some(names) = self.names.as_ref().filter(|x| !x.words.is_empty()) {
+            search = search.filter(FilenameFilter::new(
+                &names.words,
+                names.match_option,
+                names.case_sensitiv,
+            ));
+        }
+
+        if let Some(contents) = self.contents.as_ref().filter(|x| !x.words.is_empty()) {
+            search = search.filter(FileContentFilter::new(
+                &contents.words,
+                contents.match_option,
+                contents.case_sensitiv,
+            ));
+        }
+
+        if let Some(kind) = self.kind {
+            search = search.kind(kind);
+        }
+
+        if let Some(min_size) = self.min_size {
+            search = search.min_size(min_size);
+        }
+
+        if let Some(max_size) = self.max_size {
+            search = search.max_size(max_size);
+        }
+
+        if let Some(older_than) = self.older_than {
+            search = search.older_than(days(older_than));
+        }
+
+        if let Some(newer_than) = self.newer_than {
+            search = search.newer_than(days(newer_than));
+        }
+
+        search
+    }
+}
+
+fn set<T>(matches: &ArgMatches, id: &str, target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        if matches.value_source(id) != Some(ValueSource::CommandLine) {
+            *target = value;
+        }
+    }
+}
+
+fn default_match_option() -> MatchOption {
+    MatchOption::Any
+}
+
+/// Sizes can be given as number of bytes or with a suffix like `"100M"`.
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(b) => Ok(Some(b)),
+        Size::Text(t) => parse_size(&t).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Forgets where the tables were parsed, so they are written after the tables
+/// before them in the document they are inserted into.
+fn clear_positions(table: &mut Table) {
+    table.set_position(None);
+
+    for (_, item) in table.iter_mut() {
+        if let Some(table) = item.as_table_mut() {
+            clear_positions(table);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_profile_keeps_the_rest_of_the_file() {
+        let dir = env::temp_dir().join(format!("fs-rs-config-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            &path,
+            "# my defaults\n[defaults]\nmax-results = 20 # enough\n\n\
+             [profiles.old]\npaths = [\"/tmp\"]\n\n[types.web] # web\ninclude = [\"html\"]\n",
+        )
+        .unwrap();
+
+        let profile = Profile {
+            paths: vec![PathBuf::from("/var/log")],
+            min_size: Some(1024),
+            names: Some(WordFilter {
+                words: vec!["log".to_string()],
+                match_option: MatchOption::Any,
+                case_sensitiv: false,
+            }),
+            ..Default::default()
+        };
+        Config::save_profile(&path, "logs", &profile).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let config = Config::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(content.starts_with("# my defaults\n[defaults]\nmax-results = 20 # enough\n"));
+        assert!(content.ends_with("[types.web] # web\ninclude = [\"html\"]\n"));
+        assert!(content.find("[profiles.old]") < content.find("[profiles.logs]"));
+        assert!(content.find("[profiles.logs]") < content.find("[profiles.logs.names]"));
+        assert_eq!(config.defaults.max_results, Some(20));
+        assert_eq!(config.profiles.len(), 2);
+        assert_eq!(config.profiles["logs"], profile);
+    }
+
+    #[test]
+    fn save_profile_creates_the_file() {
+        let dir = env::temp_dir().join(format!("fs-rs-config-new-{}", std::process::id()));
+        let path = dir.join("fs-rs").join("config.toml");
+
+        Config::save_profile(&path, "all", &Profile::default()).unwrap();
+        let config = Config::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.profiles["all"], Profile::default());
+    }
+}
@@ -1,13 +1,47 @@
-use std::fs::{self, copy, remove_file};
+use std::{
+    error::Error,
+    fs::{self, copy, remove_file},
+    io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
-use inquire::{Confirm, MultiSelect, Text};
+use inquire::{Confirm, MultiSelect, Select, Text};
+use walkdir::WalkDir;
 
 use crate::{
-    displaying::{print_error, print_message, print_search_result, print_warning},
-    error::FsRsError,
-    search::SearchResult,
+    archive::{self, ArchiveKind},
+    cli::CaseConversion,
+    displaying::{
+        clear_progress, print_archive_progress, print_error, print_file_edit_header, print_hunk,
+        print_last_commit, print_link_group, print_message, print_not_reverted,
+        print_permission_plan, print_rename_plan, print_search_result, print_search_warnings,
+        print_warning, print_xattrs, PROGRESS_INTERVAL,
+    },
+    duplicates::DuplicateGroup,
+    error::{FsOperation, FsRsError, RenameError},
+    fsutil::temporary_path,
+    git::CommitLookup,
+    links::linked_paths,
+    permissions::{ModeChange, OwnerChange, PermissionJournal, PermissionPlan, PermissionRule},
+    rename::{RenamePlan, RenameRule},
+    replace::{FileEdit, Replacer},
+    search::{ArchiveOrigin, EntryKind, SearchResult},
+    xattrs::list_xattrs,
 };
 
+static KEEP_CASE: &str = "Keep the case";
+static LOWER_CASE: &str = "lower case";
+static UPPER_CASE: &str = "UPPER CASE";
+static TITLE_CASE: &str = "Title Case";
+
+static REPLACE_HUNK: &str = "Replace";
+static SKIP_HUNK: &str = "Skip";
+static REPLACE_FILE: &str = "Replace the rest of this file";
+static SKIP_FILE: &str = "Skip the rest of this file";
+static REPLACE_ALL: &str = "Replace all remaining changes";
+static STOP_REPLACING: &str = "Stop";
+
 pub struct CopyEntriesDialogue;
 
 pub struct ShowEntriesDialogue;
@@ -20,6 +54,16 @@ pub struct MoveEntriesDialogue;
 
 pub struct DeleteEntriesDialogue;
 
+pub struct HardLinkDuplicatesDialogue;
+
+pub struct RenameEntriesDialogue;
+
+pub struct ReplaceContentDialogue;
+
+pub struct ArchiveEntriesDialogue;
+
+pub struct ChangePermissionsDialogue;
+
 impl CopyEntriesDialogue {
     pub fn show(results: &[SearchResult], max_results: usize) -> Result<(), FsRsError> {
         let selected = MultiSelect::new("Which entries do you want to copy?", results.to_vec())
@@ -40,26 +84,23 @@ impl CopyEntriesDialogue {
         let move_to = Text::new("Where do you want to move it too?").prompt_skippable()?;
 
         if let Some(file_name) = move_to {
-            match entry {
-                SearchResult::Directory {
-                    path: _,
-                    name: _,
-                    metadata: _,
-                } => todo!(),
-                SearchResult::File {
-                    path,
-                    name: _,
-                    metadata: _,
-                } => {
-                    if let Err(why) = copy(path, file_name) {
+            match (entry.kind(), entry.archive()) {
+                (_, Some(origin)) => Self::extract_member(origin, &file_name)?,
+                (EntryKind::Directory, None) => {
+                    if let Err(why) = copy_dir(entry.path(), Path::new(&file_name)) {
+                        print_error(&format!("Could not copy directory: {why}"))?;
+                    }
+                }
+                (EntryKind::File, None) => {
+                    if let Err(why) = copy(entry.path(), file_name) {
                         print_error(&format!("Could not copy file: {why}"))?;
                     }
                 }
-                SearchResult::SymLink {
-                    path: _,
-                    name: _,
-                    metadata: _,
-                } => todo!(),
+                (EntryKind::SymLink, None) => {
+                    if let Err(why) = copy_link(entry.path(), Path::new(&file_name)) {
+                        print_error(&format!("Could not copy link: {why}"))?;
+                    }
+                }
             }
 
             print_message("Done!")?;
@@ -67,6 +108,20 @@ impl CopyEntriesDialogue {
 
         Ok(())
     }
+
+    fn extract_member(origin: &ArchiveOrigin, file_name: &str) -> Result<(), FsRsError> {
+        match archive::read_member(&origin.archive, &origin.member) {
+            Ok(Some(c)) => {
+                if let Err(why) = fs::write(file_name, c) {
+                    print_error(&format!("Could not copy file: {why}"))?;
+                }
+            }
+            Ok(None) => print_error("Could not find the entry inside of the archive.")?,
+            Err(why) => print_error(&format!("Could not read archive: {why}"))?,
+        }
+
+        Ok(())
+    }
 }
 
 impl ShowEntriesDialogue {
@@ -79,8 +134,15 @@ impl ShowEntriesDialogue {
         .prompt_skippable()?;
 
         if let Some(entries) = selected {
+            let mut commits = CommitLookup::default();
+
             for entry in &entries {
                 print_search_result(entry)?;
+                print_link_group(entry, &linked_paths(entry, results))?;
+                if entry.archive().is_none() {
+                    print_xattrs(&list_xattrs(entry.path()))?;
+                    print_last_commit(commits.last_commit(entry.path()).as_ref())?;
+                }
             }
         }
 
@@ -98,7 +160,7 @@ impl OpenEntriesDialogue {
             for entry in &entries {
                 print_warning(&format!("Opening: {:?}!", entry.path()))?;
 
-                if let Err(why) = opener::open(entry.path()) {
+                if let Err(why) = opener::open(entry.fs_path()) {
                     print_error(&format!(
                         "Failed to open entry with default program, why {why}."
                     ))?;
@@ -122,7 +184,7 @@ impl RevealEntriesDialogue {
             for entry in &entries {
                 print_warning(&format!("Opening: {:?}!", entry.path()))?;
 
-                if let Err(why) = opener::reveal(entry.path()) {
+                if let Err(why) = opener::reveal(entry.fs_path()) {
                     print_error(&format!(
                         "Failed to open entry with default program, why {why}."
                     ))?;
@@ -156,25 +218,31 @@ impl MoveEntriesDialogue {
         let move_to = Text::new("Where do you want to move it too?").prompt_skippable()?;
 
         if let Some(file_name) = move_to {
-            match entry {
-                SearchResult::Directory {
-                    path: _,
-                    name: _,
-                    metadata: _,
-                } => todo!(),
-                SearchResult::File {
-                    path,
-                    name: _,
-                    metadata: _,
-                } => match copy(path, file_name) {
-                    Ok(_) => remove_file(path)?,
+            let path = entry.path();
+
+            match (entry.kind(), entry.archive()) {
+                (_, Some(_)) => print_error("Entries inside of archives can not be moved!")?,
+                // a rename fails across file systems, the entry is copied then
+                (EntryKind::Directory, None) if fs::rename(path, &file_name).is_err() => {
+                    match copy_dir(path, Path::new(&file_name)) {
+                        Ok(()) => fs::remove_dir_all(path)
+                            .map_err(|e| FsRsError::filesystem(FsOperation::Delete, path, e))?,
+                        Err(why) => print_error(&format!("Could not move directory: {why}"))?,
+                    }
+                }
+                (EntryKind::File, None) => match copy(path, file_name) {
+                    Ok(_) => remove_file(path)
+                        .map_err(|e| FsRsError::filesystem(FsOperation::Delete, path, e))?,
                     Err(why) => print_error(&format!("Could not move file: {why}"))?,
                 },
-                SearchResult::SymLink {
-                    path: _,
-                    name: _,
-                    metadata: _,
-                } => todo!(),
+                (EntryKind::SymLink, None) if fs::rename(path, &file_name).is_err() => {
+                    match copy_link(path, Path::new(&file_name)) {
+                        Ok(()) => remove_file(path)
+                            .map_err(|e| FsRsError::filesystem(FsOperation::Delete, path, e))?,
+                        Err(why) => print_error(&format!("Could not move link: {why}"))?,
+                    }
+                }
+                (EntryKind::Directory | EntryKind::SymLink, None) => {}
             }
 
             print_message("Done!")?;
@@ -201,29 +269,494 @@ impl DeleteEntriesDialogue {
     }
 
     fn delete_entry(entry: &SearchResult) -> Result<(), FsRsError> {
+        if entry.archive().is_some() {
+            return print_error("Entries inside of archives can not be deleted!");
+        }
+
         print_warning(&format!("Attempting to delete: {:?}!", entry.path()))?;
         let confirmation = Confirm::new("Are you sure?").with_default(false).prompt()?;
 
         if confirmation {
-            match entry {
-                SearchResult::Directory {
-                    path,
-                    name: _,
-                    metadata: _,
-                } => fs::remove_dir_all(path),
-                SearchResult::File {
-                    path,
-                    name: _,
-                    metadata: _,
+            match entry.kind() {
+                EntryKind::Directory => fs::remove_dir_all(entry.path()),
+                EntryKind::File | EntryKind::SymLink => fs::remove_file(entry.path()),
+            }
+            .map_err(|e| FsRsError::filesystem(FsOperation::Delete, entry.path(), e))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl HardLinkDuplicatesDialogue {
+    pub fn show(groups: &[DuplicateGroup], max_results: usize) -> Result<(), FsRsError> {
+        let redundant: Vec<SearchResult> = groups
+            .iter()
+            .flat_map(|x| x.redundant().iter().cloned())
+            .collect();
+
+        let selected = MultiSelect::new(
+            "Which duplicates do you want to replace with hard links?",
+            redundant,
+        )
+        .with_page_size(max_results)
+        .prompt_skippable()?;
+
+        if let Some(entries) = selected {
+            for entry in &entries {
+                let group = groups
+                    .iter()
+                    .find(|x| x.redundant().iter().any(|y| y.path() == entry.path()));
+
+                if let Some(g) = group {
+                    Self::link_entry(g.original(), entry)?;
                 }
-                | SearchResult::SymLink {
-                    path,
-                    name: _,
-                    metadata: _,
-                } => fs::remove_file(path),
-            }?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn link_entry(original: &SearchResult, entry: &SearchResult) -> Result<(), FsRsError> {
+        print_warning(&format!(
+            "Replacing {:?} with a hard link to {:?}.",
+            entry.path(),
+            original.path()
+        ))?;
+
+        let path = entry.path();
+        let temp_path = temporary_path(path, "link");
+
+        let linked = fs::hard_link(original.path(), &temp_path)
+            .and_then(|()| fs::rename(&temp_path, path));
+
+        if let Err(why) = linked {
+            let _ = fs::remove_file(&temp_path);
+            print_error(&format!("Could not create hard link: {why}"))?;
+        } else {
+            print_message("Done!")?;
         }
 
         Ok(())
     }
 }
+
+impl RenameEntriesDialogue {
+    pub fn show(results: &[SearchResult], max_results: usize) -> Result<(), FsRsError> {
+        let selected = MultiSelect::new("Which entries do you want to rename?", results.to_vec())
+            .with_page_size(max_results)
+            .prompt_skippable()?;
+
+        if let Some(entries) = selected {
+            Self::rename_entries(&entries)?;
+        }
+
+        Ok(())
+    }
+
+    fn rename_entries(entries: &[SearchResult]) -> Result<(), FsRsError> {
+        let pattern = Text::new("Which part of the names do you want to replace?")
+            .with_help_message("A regex like `IMG_(\\d+)`, leave empty to replace the whole name")
+            .prompt_skippable()?
+            .filter(|x| !x.is_empty());
+
+        let Some(template) = Text::new("What should the new names be?")
+            .with_help_message("e.g. `{stem}-{n:03}{ext}`, `{mtime:%Y-%m-%d}_{name}` or `photo-$1`")
+            .prompt_skippable()?
+        else {
+            return Ok(());
+        };
+
+        let case = Select::new(
+            "Do you want to convert the case?",
+            vec![KEEP_CASE, LOWER_CASE, UPPER_CASE, TITLE_CASE],
+        )
+        .prompt_skippable()?;
+        let case = match case {
+            Some(x) if x == LOWER_CASE => Some(CaseConversion::Lower),
+            Some(x) if x == UPPER_CASE => Some(CaseConversion::Upper),
+            Some(x) if x == TITLE_CASE => Some(CaseConversion::Title),
+            _ => None,
+        };
+
+        let rule = match RenameRule::new(pattern.as_deref(), &template, case) {
+            Ok(r) => r,
+            Err(why) => return print_error(&format!("Could not rename the entries: {why}.")),
+        };
+
+        let plan = RenamePlan::new(&rule, entries);
+        print_rename_plan(&plan)?;
+
+        if !plan.conflicts().is_empty() || plan.renames().is_empty() {
+            return Ok(());
+        }
+
+        let confirmation = Confirm::new(&format!(
+            "Do you want to rename '{}' entries?",
+            plan.renames().len()
+        ))
+        .with_default(false)
+        .prompt()?;
+
+        if confirmation {
+            match plan.apply() {
+                Ok(()) => print_message("Done!")?,
+                Err(FsRsError::Rename(RenameError::NotReverted { source, entries })) => {
+                    print_error(&format!("Could not rename the entries: {source}"))?;
+                    print_not_reverted(&entries)?;
+                }
+                Err(why) => print_error(&format!(
+                    "Could not rename the entries, the renames were reverted: {why}"
+                ))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ReplaceContentDialogue {
+    pub fn show(results: &[SearchResult], max_results: usize) -> Result<(), FsRsError> {
+        let files: Vec<SearchResult> = results
+            .iter()
+            .filter(|x| x.kind() == EntryKind::File && x.archive().is_none())
+            .cloned()
+            .collect();
+
+        if files.is_empty() {
+            return print_warning("There are no files to replace text in!");
+        }
+
+        let selected = MultiSelect::new("In which files do you want to replace text?", files)
+            .with_page_size(max_results)
+            .prompt_skippable()?;
+
+        if let Some(entries) = selected {
+            Self::replace_content(&entries)?;
+        }
+
+        Ok(())
+    }
+
+    fn replace_content(entries: &[SearchResult]) -> Result<(), FsRsError> {
+        let Some(find) = Text::new("What do you want to replace?").prompt_skippable()? else {
+            return Ok(());
+        };
+
+        let regex = Confirm::new("Is it a regex?").with_default(false).prompt()?;
+        let case_sensitiv = Confirm::new("Do you want to match the case?")
+            .with_default(true)
+            .prompt()?;
+
+        let Some(replacement) = Text::new("What do you want to replace it with?")
+            .with_help_message(if regex {
+                "Captures can be used as `$1` or `${name}`"
+            } else {
+                "The text is inserted as is"
+            })
+            .prompt_skippable()?
+        else {
+            return Ok(());
+        };
+
+        let backup = Text::new("Which suffix should backups of the files get?")
+            .with_help_message("e.g. `.bak`, leave empty for no backups")
+            .prompt_skippable()?
+            .filter(|x| !x.is_empty());
+
+        let replacer = match Replacer::new(&find, &replacement, regex, case_sensitiv) {
+            Ok(r) => r,
+            Err(why) => return print_error(&format!("Could not replace the text: {why}.")),
+        };
+
+        let (edits, warnings) = replacer.plan_results(entries);
+        print_search_warnings(&warnings, true)?;
+
+        if edits.is_empty() {
+            return print_message("None of the files contain the text.");
+        }
+
+        let (replaced, files) = Self::review(&edits, backup.as_deref())?;
+        print_message(&format!("Replaced '{replaced}' matches in '{files}' files."))
+    }
+
+    /// Shows the diff of every edit and asks for each hunk whether to apply it.
+    ///
+    /// Returns how many replacements were written to how many files, a file
+    /// that could not be written is reported and skipped.
+    pub fn review(edits: &[FileEdit], backup: Option<&str>) -> Result<(usize, usize), FsRsError> {
+        let options = vec![
+            REPLACE_HUNK,
+            SKIP_HUNK,
+            REPLACE_FILE,
+            SKIP_FILE,
+            REPLACE_ALL,
+            STOP_REPLACING,
+        ];
+
+        let mut replace_all = false;
+        let mut stop = false;
+        let mut replaced = 0;
+        let mut files = 0;
+
+        for edit in edits {
+            print_file_edit_header(edit)?;
+
+            let mut accepted = vec![false; edit.hunks().len()];
+            let mut rest_of_file = replace_all.then_some(true);
+
+            for (index, hunk) in edit.hunks().iter().enumerate() {
+                print_hunk(hunk)?;
+
+                accepted[index] = match rest_of_file {
+                    Some(answer) => answer,
+                    None => match Select::new("Do you want to apply this change?", options.clone())
+                        .prompt_skippable()?
+                    {
+                        Some(x) if x == REPLACE_HUNK => true,
+                        Some(x) if x == REPLACE_FILE => {
+                            rest_of_file = Some(true);
+                            true
+                        }
+                        Some(x) if x == SKIP_FILE => {
+                            rest_of_file = Some(false);
+                            false
+                        }
+                        Some(x) if x == REPLACE_ALL => {
+                            replace_all = true;
+                            rest_of_file = Some(true);
+                            true
+                        }
+                        Some(x) if x == SKIP_HUNK => false,
+                        // STOP_REPLACING or canceled
+                        _ => {
+                            stop = true;
+                            break;
+                        }
+                    },
+                };
+            }
+
+            let count = edit.accepted_count(&accepted);
+            if count > 0 {
+                match edit.apply(&accepted, backup) {
+                    Ok(()) => {
+                        replaced += count;
+                        files += 1;
+                    }
+                    Err(why) => print_error(&match why.source() {
+                        Some(source) => format!("Could not replace the text: {why}, {source}."),
+                        None => format!("Could not replace the text: {why}."),
+                    })?,
+                }
+            }
+
+            if stop {
+                break;
+            }
+        }
+
+        Ok((replaced, files))
+    }
+}
+
+impl ArchiveEntriesDialogue {
+    pub fn show(results: &[SearchResult], max_results: usize) -> Result<(), FsRsError> {
+        let selected = MultiSelect::new("Which entries do you want to archive?", results.to_vec())
+            .with_page_size(max_results)
+            .prompt_skippable()?;
+
+        if let Some(entries) = selected {
+            Self::archive_entries(&entries)?;
+        }
+
+        Ok(())
+    }
+
+    fn archive_entries(entries: &[SearchResult]) -> Result<(), FsRsError> {
+        let Some(output) = Text::new("Where do you want to save the archive?")
+            .with_help_message(
+                "The extension picks the format: .tar, .tar.gz, .tar.zst, .tar.xz or .zip",
+            )
+            .prompt_skippable()?
+            .map(PathBuf::from)
+        else {
+            return Ok(());
+        };
+
+        let Some(kind) = ArchiveKind::detect(&output).filter(|x| x.can_create()) else {
+            return print_error(&format!("Unsupported archive format: {output:?}."));
+        };
+
+        if fs::symlink_metadata(&output).is_ok() {
+            let overwrite = Confirm::new(&format!("{output:?} already exists, overwrite it?"))
+                .with_default(false)
+                .prompt()?;
+
+            if !overwrite {
+                return Ok(());
+            }
+        }
+
+        let (inputs, warnings) = archive::archive_inputs(entries, &output);
+        print_search_warnings(&warnings, true)?;
+        if inputs.is_empty() {
+            return print_warning("There are no entries to archive.");
+        }
+
+        let mut last_update = Instant::now();
+        let result = archive::create_archive(&output, kind, &inputs, |progress| {
+            if last_update.elapsed() >= PROGRESS_INTERVAL {
+                last_update = Instant::now();
+                let _ = print_archive_progress(progress);
+            }
+        });
+        clear_progress()?;
+
+        match result {
+            Ok(()) => print_message(&format!(
+                "Archived '{}' entries into {output:?}.",
+                inputs.len()
+            )),
+            Err(why) => print_error(&match why.source() {
+                Some(source) => format!("Could not archive the entries: {why}, {source}."),
+                None => format!("Could not archive the entries: {why}."),
+            }),
+        }
+    }
+}
+
+impl ChangePermissionsDialogue {
+    pub fn show(results: &[SearchResult], max_results: usize) -> Result<(), FsRsError> {
+        let selected = MultiSelect::new(
+            "Which entries do you want to change the permissions of?",
+            results.to_vec(),
+        )
+        .with_page_size(max_results)
+        .prompt_skippable()?;
+
+        if let Some(entries) = selected {
+            Self::change_permissions(&entries)?;
+        }
+
+        Ok(())
+    }
+
+    fn change_permissions(entries: &[SearchResult]) -> Result<(), FsRsError> {
+        let mode = Text::new("What should the new mode be?")
+            .with_help_message(
+                "Octal like `755` or symbolic like `u+x,go-w`, leave empty to keep it",
+            )
+            .prompt_skippable()?
+            .filter(|x| !x.is_empty());
+        let owner = Text::new("Who should the new owner be?")
+            .with_help_message("`user`, `user:group` or `:group`, leave empty to keep it")
+            .prompt_skippable()?
+            .filter(|x| !x.is_empty());
+
+        let parsed = mode
+            .as_deref()
+            .map(ModeChange::parse)
+            .transpose()
+            .and_then(|m| Ok((m, owner.as_deref().map(OwnerChange::parse).transpose()?)));
+        let rule = match parsed {
+            Ok((mode, owner)) => PermissionRule { mode, owner },
+            Err(why) => return print_error(&format!("Could not change the permissions: {why}.")),
+        };
+
+        if rule.is_empty() {
+            return Ok(());
+        }
+
+        let recursive = entries.iter().any(|x| x.kind() == EntryKind::Directory)
+            && Confirm::new("Do you also want to change everything inside of the directories?")
+                .with_default(false)
+                .prompt()?;
+
+        let (plan, warnings) = PermissionPlan::collect(&rule, entries, recursive);
+        print_search_warnings(&warnings, true)?;
+        print_permission_plan(&plan)?;
+
+        if plan.changes().is_empty() {
+            return Ok(());
+        }
+
+        let confirmation = Confirm::new(&format!(
+            "Do you want to change the permissions of '{}' entries?",
+            plan.changes().len()
+        ))
+        .with_default(false)
+        .prompt()?;
+
+        if confirmation {
+            match plan.apply(&PermissionJournal::new(PermissionJournal::default_dir())) {
+                Ok(id) => print_message(&format!(
+                    "Done! Undo it with `fs-rs permissions undo {id}`."
+                ))?,
+                Err(why) => print_error(&match why.source() {
+                    Some(source) => format!(
+                        "Could not change the permissions, they were reverted: {why}, {source}."
+                    ),
+                    None => format!("Could not change the permissions, they were reverted: {why}."),
+                })?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Copies a directory with its contents, links inside of it are copied as links.
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in WalkDir::new(from) {
+        let entry = entry?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap_or(entry.path()));
+
+        if entry.file_type().is_dir() {
+            fs::create_dir(target)?;
+        } else if entry.file_type().is_symlink() {
+            copy_link(entry.path(), &target)?;
+        } else {
+            copy(entry.path(), target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a link at `to` pointing to the same target as the link at `from`.
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_link(_: &Path, _: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "links can only be copied on unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_dir_keeps_links() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-dialogue-{}", std::process::id()));
+        fs::create_dir_all(dir.join("from/sub")).unwrap();
+        fs::write(dir.join("from/sub/file"), "content").unwrap();
+        std::os::unix::fs::symlink("sub/file", dir.join("from/link")).unwrap();
+
+        let copied = copy_dir(&dir.join("from"), &dir.join("to"));
+        let content = fs::read_to_string(dir.join("to/sub/file"));
+        let target = fs::read_link(dir.join("to/link"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        copied.unwrap();
+        assert_eq!(content.unwrap(), "content");
+        assert_eq!(target.unwrap(), Path::new("sub/file"));
+    }
+}
@@ -1,16 +1,54 @@
-use std::io::stdout;
+use std::{
+    error::Error,
+    ffi::OsString,
+    io::stdout,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use crossterm::{
+    cursor::MoveToColumn,
     execute,
     style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+};
+
+use crate::{
+    archive::{ArchiveInput, ArchiveProgress},
+    cli::OutputFormat,
+    duplicates::DuplicateGroup,
+    error::FsRsError,
+    filetype::FileTypes,
+    git::LastCommit,
+    history::HistoryEntry,
+    lint::LintFinding,
+    permissions::{group_name, user_name, EntryPermissions, PermissionPlan, PermissionRecord},
+    rename::{RenameConflict, RenamePlan},
+    replace::{DiffLine, FileEdit, Hunk},
+    search::{EntryKind, SearchOutput, SearchProgress, SearchResult, SearchWarning},
+    usage::{UsageEntry, UsageReport},
+    watch::WatchEvent,
+    xattrs::format_xattr_value,
 };
 
-use crate::{error::FsRsError, search::SearchResult};
+/// How often progress lines like [`print_progress`] are redrawn.
+pub static PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
 
 pub fn print_error(message: &str) -> Result<(), FsRsError> {
     print_log(message, Color::Red)
 }
 
+/// Prints `error` and its sources to stderr, without relying on the terminal styling.
+pub fn print_fatal_error(error: &FsRsError) {
+    eprintln!("Error: {error}");
+
+    let mut source = error.source();
+    while let Some(s) = source {
+        eprintln!("  caused by: {s}");
+        source = s.source();
+    }
+}
+
 pub fn print_warning(message: &str) -> Result<(), FsRsError> {
     print_log(message, Color::Yellow)
 }
@@ -23,31 +61,656 @@ pub fn print_log(message: &str, color: Color) -> Result<(), FsRsError> {
         Print("\n"),
         ResetColor
     )
-    .map_err(FsRsError::Crossterm)
+    .map_err(FsRsError::Terminal)
 }
 
 pub fn print_message(message: &str) -> Result<(), FsRsError> {
     print_log(message, Color::Grey)
 }
 
+/// Replaces the current terminal line with the progress of a running search.
+pub fn print_progress(progress: &SearchProgress) -> Result<(), FsRsError> {
+    let status = format!(
+        "Searching... {} entries, {} directories, {} read: ",
+        progress.entries_checked,
+        progress.directories_visited,
+        format_size(progress.bytes_read)
+    );
+    let width = terminal::size().map_or(80, |(w, _)| usize::from(w));
+    let path = progress.current_path.to_string_lossy();
+    let path = truncate_start(&path, width.saturating_sub(status.chars().count() + 1));
+
+    execute!(
+        stdout(),
+        MoveToColumn(0),
+        Clear(ClearType::CurrentLine),
+        SetForegroundColor(Color::Grey),
+        Print(status),
+        Print(path),
+        ResetColor
+    )
+    .map_err(FsRsError::Terminal)
+}
+
+/// Replaces the current terminal line with the progress of a running archive.
+pub fn print_archive_progress(progress: &ArchiveProgress) -> Result<(), FsRsError> {
+    let status = format!(
+        "Archiving... {}/{} entries, {} written: ",
+        progress.entries_written,
+        progress.entries_total,
+        format_size(progress.bytes_written)
+    );
+    let width = terminal::size().map_or(80, |(w, _)| usize::from(w));
+    let path = progress.current_path.to_string_lossy();
+    let path = truncate_start(&path, width.saturating_sub(status.chars().count() + 1));
+
+    execute!(
+        stdout(),
+        MoveToColumn(0),
+        Clear(ClearType::CurrentLine),
+        SetForegroundColor(Color::Grey),
+        Print(status),
+        Print(path),
+        ResetColor
+    )
+    .map_err(FsRsError::Terminal)
+}
+
+/// Removes the progress line printed by [`print_progress`] or [`print_archive_progress`].
+pub fn clear_progress() -> Result<(), FsRsError> {
+    execute!(stdout(), MoveToColumn(0), Clear(ClearType::CurrentLine)).map_err(FsRsError::Terminal)
+}
+
+/// Keeps the last `max_chars` characters, the end of a path is more telling than its start.
+fn truncate_start(s: &str, max_chars: usize) -> &str {
+    let skip = s.chars().count().saturating_sub(max_chars);
+    match s.char_indices().nth(skip) {
+        Some((idx, _)) => &s[idx..],
+        None => "",
+    }
+}
+
 pub fn print_search_result(search_result: &SearchResult) -> Result<(), FsRsError> {
-    let message = match search_result {
-        SearchResult::Directory {
-            path,
-            name: _,
-            metadata: _,
-        } => format!("(D) Opening: {path:?}"),
-        SearchResult::File {
-            path,
-            name: _,
-            metadata: _,
-        } => format!("(F) Opening: {path:?}"),
-        SearchResult::SymLink {
-            path,
-            name: _,
-            metadata: _,
-        } => format!("(S) Opening: {path:?}"),
+    let path = search_result.path();
+    let message = match (search_result.kind(), search_result.archive()) {
+        (_, Some(_)) => format!("(A) Opening: {path:?}"),
+        (EntryKind::Directory, None) => format!("(D) Opening: {path:?}"),
+        (EntryKind::File, None) => format!("(F) Opening: {path:?}"),
+        (EntryKind::SymLink, None) => format!("(S) Opening: {path:?}"),
     };
 
     print_message(&message)
 }
+
+/// Lists the other hard links of a file with several of them, `linked` are
+/// those found among the results.
+pub fn print_link_group(search_result: &SearchResult, linked: &[PathBuf]) -> Result<(), FsRsError> {
+    let links = match (search_result.kind(), search_result.link_count()) {
+        (EntryKind::File, Some(links)) if links > 1 => links,
+        _ => return Ok(()),
+    };
+
+    print_message(&format!("    {links} hard links:"))?;
+
+    for path in linked {
+        print_message(&format!("        {path:?}"))?;
+    }
+
+    let outside = (links - 1).saturating_sub(linked.len() as u64);
+    if outside > 0 {
+        print_message(&format!(
+            "        '{outside}' not among the results, find them with `--same-file`."
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Lists the extended attributes of an entry with their values.
+pub fn print_xattrs(attributes: &[(OsString, Vec<u8>)]) -> Result<(), FsRsError> {
+    if attributes.is_empty() {
+        return Ok(());
+    }
+
+    print_message("    Extended attributes:")?;
+
+    for (name, value) in attributes {
+        print_message(&format!(
+            "        {} = {}",
+            name.to_string_lossy(),
+            format_xattr_value(value)
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Shows the last commit that changed an entry in its git repository.
+pub fn print_last_commit(commit: Option<&LastCommit>) -> Result<(), FsRsError> {
+    let Some(commit) = commit else {
+        return Ok(());
+    };
+
+    print_message(&format!(
+        "    Last commit: {} {} by {}",
+        commit.id,
+        commit.time.format("%Y-%m-%d %H:%M"),
+        commit.author
+    ))?;
+    print_message(&format!("        {}", commit.summary))
+}
+
+pub fn print_duplicate_group(group: &DuplicateGroup) -> Result<(), FsRsError> {
+    print_warning(&format!(
+        "{} files of {} each, wasting {}:",
+        group.entries.len(),
+        format_size(group.size),
+        format_size(group.wasted_space())
+    ))?;
+
+    for entry in &group.entries {
+        print_message(&format!("    {:?}", entry.path()))?;
+    }
+
+    Ok(())
+}
+
+pub fn print_watch_event(event: &WatchEvent, format: OutputFormat) -> Result<(), FsRsError> {
+    let (event_name, path, kind, color) = match event {
+        WatchEvent::Added(result) => (
+            "add",
+            result.path(),
+            Some(result.kind().to_string()),
+            Color::Green,
+        ),
+        WatchEvent::Removed(path) => ("remove", path.as_path(), None, Color::Red),
+    };
+
+    match format {
+        OutputFormat::Text => {
+            let sign = if kind.is_some() { "+" } else { "-" };
+            print_log(&format!("{sign} {path:?}"), color)
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let line = serde_json::json!({
+                "event": event_name,
+                "path": path.to_string_lossy(),
+                "kind": kind,
+            });
+
+            execute!(stdout(), Print(line), Print("\n")).map_err(FsRsError::Terminal)
+        }
+    }
+}
+
+/// Prints a summary of the entries that could not be searched, or all of them if `show_all`.
+pub fn print_search_warnings(warnings: &[SearchWarning], show_all: bool) -> Result<(), FsRsError> {
+    if warnings.is_empty() {
+        return Ok(());
+    }
+
+    if !show_all {
+        return print_warning(&format!(
+            "'{}' entries could not be searched, use --show-errors to list them.",
+            warnings.len()
+        ));
+    }
+
+    print_warning(&format!("'{}' entries could not be searched:", warnings.len()))?;
+
+    for warning in warnings {
+        print_error(&format!("    {warning}"))?;
+    }
+
+    Ok(())
+}
+
+pub fn print_search_output(output: &SearchOutput, format: OutputFormat) -> Result<(), FsRsError> {
+    let results = output.results.iter().map(search_result_json);
+    let warnings = output.warnings.iter().map(search_warning_json);
+
+    print_json_output(results, warnings, format)
+}
+
+/// Prints the findings of `fs-rs lint` as JSON, each result with its problems.
+pub fn print_lint_output(
+    findings: &[LintFinding],
+    warnings: &[SearchWarning],
+    format: OutputFormat,
+) -> Result<(), FsRsError> {
+    let results = findings.iter().map(|x| {
+        let mut value = search_result_json(&x.result);
+        value["problems"] = serde_json::json!(x.problems);
+        value
+    });
+    let warnings = warnings.iter().map(search_warning_json);
+
+    print_json_output(results, warnings, format)
+}
+
+/// Lists the findings of `fs-rs lint` with their problems.
+pub fn print_lint_findings(findings: &[LintFinding]) -> Result<(), FsRsError> {
+    if findings.is_empty() {
+        return print_message("No problems found.");
+    }
+
+    print_log(&format!("{:<24}  path", "problem"), Color::Yellow)?;
+
+    for finding in findings {
+        let problems: Vec<String> = finding.problems.iter().map(ToString::to_string).collect();
+
+        print_message(&format!(
+            "{:<24}  {}",
+            problems.join(", "),
+            finding.result.path().to_string_lossy()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Prints one document with the results and warnings for [`OutputFormat::Json`],
+/// one line for each of them otherwise.
+fn print_json_output(
+    results: impl Iterator<Item = serde_json::Value>,
+    warnings: impl Iterator<Item = serde_json::Value>,
+    format: OutputFormat,
+) -> Result<(), FsRsError> {
+    let lines: Vec<String> = match format {
+        OutputFormat::Json => {
+            let document = serde_json::json!({
+                "results": results.collect::<Vec<_>>(),
+                "warnings": warnings.collect::<Vec<_>>(),
+            });
+            vec![document.to_string()]
+        }
+        _ => results.chain(warnings).map(|x| x.to_string()).collect(),
+    };
+
+    for line in lines {
+        execute!(stdout(), Print(line), Print("\n")).map_err(FsRsError::Terminal)?;
+    }
+
+    Ok(())
+}
+
+fn search_result_json(search_result: &SearchResult) -> serde_json::Value {
+    serde_json::json!({
+        "type": "result",
+        "path": search_result.path().to_string_lossy(),
+        "kind": search_result.kind().to_string(),
+        "size": search_result.size(),
+        "archive": search_result.archive().map(|x| x.archive.to_string_lossy()),
+    })
+}
+
+fn search_warning_json(warning: &SearchWarning) -> serde_json::Value {
+    serde_json::json!({
+        "type": "warning",
+        "path": warning.path.as_ref().map(|x| x.to_string_lossy()),
+        "error": warning.cause(),
+    })
+}
+
+pub fn print_history(entries: &[HistoryEntry]) -> Result<(), FsRsError> {
+    if entries.is_empty() {
+        return print_message("The history is empty.");
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+
+    print_log(
+        &format!("{:>6}  {:>10}  {:>8}  command", "id", "age", "results"),
+        Color::Yellow,
+    )?;
+
+    for entry in entries {
+        let arguments: Vec<OsString> = entry.arguments.iter().map(|x| x.0.clone()).collect();
+
+        print_message(&format!(
+            "{:>6}  {:>10}  {:>8}  {}",
+            entry.id,
+            format_age(now.saturating_sub(entry.created)),
+            entry.result_count,
+            format_arguments(&arguments)
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Prints the old and new mode and owner of every entry in `plan`.
+pub fn print_permission_plan(plan: &PermissionPlan) -> Result<(), FsRsError> {
+    if plan.changes().is_empty() {
+        return print_message("No entry gets new permissions.");
+    }
+
+    let rows: Vec<(String, String, String)> = plan
+        .changes()
+        .iter()
+        .map(|x| {
+            (
+                format_change(format_mode(x.old.mode), format_mode(x.new.mode)),
+                format_change(format_owner(x.old), format_owner(x.new)),
+                x.path.to_string_lossy().to_string(),
+            )
+        })
+        .collect();
+    let mode_width = rows.iter().map(|x| x.0.chars().count()).max().unwrap_or(0);
+    let owner_width = rows.iter().map(|x| x.1.chars().count()).max().unwrap_or(0);
+
+    print_log(
+        &format!("{:<mode_width$}  {:<owner_width$}  path", "mode", "owner"),
+        Color::Yellow,
+    )?;
+
+    for (mode, owner, path) in rows {
+        print_message(&format!("{mode:<mode_width$}  {owner:<owner_width$}  {path}"))?;
+    }
+
+    Ok(())
+}
+
+pub fn print_permission_records(records: &[PermissionRecord]) -> Result<(), FsRsError> {
+    if records.is_empty() {
+        return print_message("There are no recorded permission changes.");
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+
+    print_log(
+        &format!("{:>6}  {:>10}  {:>8}  first entry", "id", "age", "entries"),
+        Color::Yellow,
+    )?;
+
+    for record in records {
+        print_message(&format!(
+            "{:>6}  {:>10}  {:>8}  {}",
+            record.id,
+            format_age(now.saturating_sub(record.created)),
+            record.changes.len(),
+            record
+                .changes
+                .first()
+                .map(|x| x.path.0.to_string_lossy().to_string())
+                .unwrap_or_default()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Lists the file types with their MIME type and what their files are recognized by.
+pub fn print_file_types(file_types: &FileTypes) -> Result<(), FsRsError> {
+    print_log(&format!("{:<10}  {:<26}  matches", "type", "mime"), Color::Yellow)?;
+
+    for (name, definition) in file_types.definitions() {
+        let mut matches: Vec<String> = definition
+            .extensions
+            .iter()
+            .map(|x| format!("*.{x}"))
+            .chain(definition.file_names.iter().cloned())
+            .chain(definition.shebangs.iter().map(|x| format!("#!{x}")))
+            .chain(definition.include.iter().map(|x| format!("@{x}")))
+            .collect();
+        if !definition.magic.is_empty() {
+            matches.push("magic bytes".to_string());
+        }
+
+        print_message(&format!(
+            "{:<10}  {:<26}  {}",
+            name,
+            definition.mime.as_deref().unwrap_or("-"),
+            matches.join(", ")
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Formats permission bits like `ls -l`, e.g. `rwxr-sr-t`.
+#[must_use]
+pub fn format_mode(mode: u32) -> String {
+    let class = |shift: u32, special: u32, set: char, unset: char| {
+        let bits = mode >> shift;
+        let execute = match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        };
+
+        format!(
+            "{}{}{execute}",
+            if bits & 0o4 != 0 { 'r' } else { '-' },
+            if bits & 0o2 != 0 { 'w' } else { '-' }
+        )
+    };
+
+    format!(
+        "{}{}{}",
+        class(6, 0o4000, 's', 'S'),
+        class(3, 0o2000, 's', 'S'),
+        class(0, 0o1000, 't', 'T')
+    )
+}
+
+fn format_owner(permissions: EntryPermissions) -> String {
+    format!(
+        "{}:{}",
+        user_name(permissions.uid),
+        group_name(permissions.gid)
+    )
+}
+
+/// `old -> new`, or just `old` if nothing changes.
+fn format_change(old: String, new: String) -> String {
+    if old == new {
+        old
+    } else {
+        format!("{old} -> {new}")
+    }
+}
+
+/// Lists the member paths an archive would get, next to the packed entries.
+pub fn print_archive_inputs(inputs: &[ArchiveInput]) -> Result<(), FsRsError> {
+    if inputs.is_empty() {
+        return print_message("There are no entries to archive.");
+    }
+
+    let width = inputs
+        .iter()
+        .map(|x| x.name.to_string_lossy().chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(6, 60);
+
+    print_log(&format!("{:<width$}  entry", "member"), Color::Yellow)?;
+
+    for input in inputs {
+        print_message(&format!(
+            "{:<width$}  {}",
+            input.name.to_string_lossy(),
+            input.path.to_string_lossy()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Prints the entries of [`RenameError::NotReverted`](crate::error::RenameError) with
+/// their current and original paths.
+pub fn print_not_reverted(entries: &[(PathBuf, PathBuf)]) -> Result<(), FsRsError> {
+    print_error("These entries could not be renamed back:")?;
+    for (path, original) in entries {
+        print_error(&format!("{path:?}, was {original:?}"))?;
+    }
+
+    Ok(())
+}
+
+/// Prints the renames of `plan` as a before and after table, followed by its conflicts.
+pub fn print_rename_plan(plan: &RenamePlan) -> Result<(), FsRsError> {
+    if plan.renames().is_empty() && plan.conflicts().is_empty() {
+        return print_message("No entry gets a new name.");
+    }
+
+    let cycles = plan.cycles();
+    let width = plan
+        .renames()
+        .iter()
+        .map(|x| x.from.to_string_lossy().chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(6, 60);
+
+    print_log(&format!("{:<width$}  after", "before"), Color::Yellow)?;
+
+    for rename in plan.renames() {
+        let name = rename.to.file_name().unwrap_or_default().to_string_lossy();
+        let cycle = if cycles.contains(&rename) { "  (cycle)" } else { "" };
+
+        print_message(&format!(
+            "{:<width$}  {name}{cycle}",
+            rename.from.to_string_lossy()
+        ))?;
+    }
+
+    for conflict in plan.conflicts() {
+        print_error(&match conflict {
+            RenameConflict::SameTarget { to, from } => {
+                format!("{} entries would be renamed to {to:?}.", from.len())
+            }
+            RenameConflict::TargetExists { from, to } => {
+                format!("Can not rename {from:?}, {to:?} already exists.")
+            }
+            RenameConflict::InvalidName { from, name } => {
+                format!("Can not rename {from:?} to the invalid name {name:?}.")
+            }
+            RenameConflict::ArchiveMember(path) => {
+                format!("Can not rename {path:?} inside of an archive.")
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Prints the header of the unified diff of `edit`, its hunks are printed with [`print_hunk`].
+pub fn print_file_edit_header(edit: &FileEdit) -> Result<(), FsRsError> {
+    let path = edit.path().to_string_lossy();
+
+    print_log(&format!("--- {path}"), Color::Yellow)?;
+    print_log(&format!("+++ {path}"), Color::Yellow)
+}
+
+pub fn print_hunk(hunk: &Hunk) -> Result<(), FsRsError> {
+    print_log(
+        &format!(
+            "@@ -{},{} +{},{} @@",
+            hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count
+        ),
+        Color::Cyan,
+    )?;
+
+    for line in &hunk.lines {
+        match line {
+            DiffLine::Context(l) => print_message(&format!(" {}", String::from_utf8_lossy(l)))?,
+            DiffLine::Removed(l) => print_error(&format!("-{}", String::from_utf8_lossy(l)))?,
+            DiffLine::Added(l) => print_log(&format!("+{}", String::from_utf8_lossy(l)), Color::Green)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints all hunks of `edit` as a unified diff.
+pub fn print_file_edit(edit: &FileEdit) -> Result<(), FsRsError> {
+    print_file_edit_header(edit)?;
+
+    for hunk in edit.hunks() {
+        print_hunk(hunk)?;
+    }
+
+    Ok(())
+}
+
+/// Formats command line arguments so they can be pasted into a shell.
+#[must_use]
+pub fn format_arguments(arguments: &[OsString]) -> String {
+    arguments
+        .iter()
+        .map(|x| {
+            let argument = x.to_string_lossy();
+
+            if !argument.is_empty()
+                && argument
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "-_./=:,+@%".contains(c))
+            {
+                argument.to_string()
+            } else {
+                format!("'{}'", argument.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{seconds}s ago"),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86_399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86_400),
+    }
+}
+
+pub fn print_usage_report(report: &UsageReport) -> Result<(), FsRsError> {
+    print_usage_table("Largest directories:", &report.directories)?;
+    print_usage_table("Largest files:", &report.files)?;
+    print_usage_table("Largest extensions:", &report.extensions)?;
+    print_usage_table("Summary:", std::slice::from_ref(&report.total))
+}
+
+fn print_usage_table(title: &str, entries: &[UsageEntry]) -> Result<(), FsRsError> {
+    print_warning(title)?;
+    print_message(&format!(
+        "    {:>12} {:>12} {:>8}  name",
+        "apparent", "on disk", "files"
+    ))?;
+
+    for entry in entries {
+        print_message(&format!(
+            "    {:>12} {:>12} {:>8}  {}",
+            format_size(entry.apparent_size),
+            format_size(entry.disk_size),
+            entry.files,
+            entry.name
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[must_use]
+pub fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{size} {}", units[unit])
+    } else {
+        format!("{value:.1} {}", units[unit])
+    }
+}
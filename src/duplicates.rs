@@ -0,0 +1,187 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, Read},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::search::{EntryKind, SearchResult};
+
+const PARTIAL_HASH_SIZE: u64 = 4096;
+
+/// Files with identical content, the first entry is the one that is kept.
+#[derive(Clone, Debug)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub entries: Vec<SearchResult>,
+}
+
+impl DuplicateGroup {
+    #[must_use]
+    pub fn original(&self) -> &SearchResult {
+        &self.entries[0]
+    }
+
+    #[must_use]
+    pub fn redundant(&self) -> &[SearchResult] {
+        &self.entries[1..]
+    }
+
+    #[must_use]
+    pub fn wasted_space(&self) -> u64 {
+        self.size * self.redundant().len() as u64
+    }
+}
+
+/// Groups the files of `results` by identical content.
+///
+/// Candidates are narrowed down by size, then by a hash of their first bytes and
+/// finally by a hash of the whole content. Hard links to the same file are only
+/// counted once.
+#[must_use]
+pub fn find_duplicates(results: &[SearchResult]) -> Vec<DuplicateGroup> {
+    let mut by_size: HashMap<u64, Vec<SearchResult>> = HashMap::new();
+    let mut file_ids = HashSet::new();
+
+    for result in results {
+        if let (EntryKind::File, None, Some(metadata)) =
+            (result.kind(), result.archive(), result.metadata())
+        {
+            if metadata.len() == 0 || result.file_id().is_some_and(|x| !file_ids.insert(x)) {
+                continue;
+            }
+
+            by_size
+                .entry(metadata.len())
+                .or_default()
+                .push(result.clone());
+        }
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_size
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .flat_map(|(size, entries)| {
+            group_by_hash(entries, |x| hash_file(x, Some(PARTIAL_HASH_SIZE)))
+                .into_iter()
+                .flat_map(|x| group_by_hash(x, |y| hash_file(y, None)))
+                .map(move |entries| DuplicateGroup { size, entries })
+        })
+        .collect();
+
+    for group in &mut groups {
+        group.entries.sort_by(|a, b| a.path().cmp(b.path()));
+    }
+
+    groups.sort_by_key(|x| std::cmp::Reverse(x.wasted_space()));
+    groups
+}
+
+fn group_by_hash(
+    entries: Vec<SearchResult>,
+    hash: impl Fn(&SearchResult) -> io::Result<Vec<u8>>,
+) -> Vec<Vec<SearchResult>> {
+    let mut by_hash: HashMap<Vec<u8>, Vec<SearchResult>> = HashMap::new();
+
+    for entry in entries {
+        if let Ok(h) = hash(&entry) {
+            by_hash.entry(h).or_default().push(entry);
+        }
+    }
+
+    by_hash.into_values().filter(|x| x.len() > 1).collect()
+}
+
+fn hash_file(entry: &SearchResult, limit: Option<u64>) -> io::Result<Vec<u8>> {
+    let file = File::open(entry.path())?;
+    let mut hasher = Sha256::new();
+
+    match limit {
+        Some(l) => io::copy(&mut file.take(l), &mut hasher)?,
+        None => io::copy(&mut { file }, &mut hasher)?,
+    };
+
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SearchBuilder;
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fs-rs-dupes-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn groups(dir: &Path) -> Vec<Vec<String>> {
+        let results = SearchBuilder::new().root(dir).build().run().results;
+
+        find_duplicates(&results)
+            .iter()
+            .map(|x| {
+                x.entries
+                    .iter()
+                    .map(|y| y.path().strip_prefix(dir).unwrap().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_duplicates_by_content() {
+        let dir = test_dir("content");
+        let head = vec![b'x'; PARTIAL_HASH_SIZE as usize];
+        fs::write(dir.join("a"), [&head[..], b"tail"].concat()).unwrap();
+        fs::write(dir.join("b"), [&head[..], b"tail"].concat()).unwrap();
+        // the same size and start, only the full hash tells it apart
+        fs::write(dir.join("c"), [&head[..], b"TAIL"].concat()).unwrap();
+        fs::write(dir.join("small-1"), "small").unwrap();
+        fs::write(dir.join("small-2"), "small").unwrap();
+        fs::write(dir.join("empty-1"), "").unwrap();
+        fs::write(dir.join("empty-2"), "").unwrap();
+
+        let groups = groups(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // sorted by wasted space, the entries by path
+        assert_eq!(groups, [vec!["a", "b"], vec!["small-1", "small-2"]]);
+    }
+
+    #[test]
+    fn find_duplicates_wasted_space() {
+        let dir = test_dir("wasted");
+        for name in ["a", "b", "c"] {
+            fs::write(dir.join(name), "content").unwrap();
+        }
+
+        let results = SearchBuilder::new().root(&dir).build().run().results;
+        let groups = find_duplicates(&results);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].size, 7);
+        assert_eq!(groups[0].original().path(), dir.join("a"));
+        assert_eq!(groups[0].redundant().len(), 2);
+        assert_eq!(groups[0].wasted_space(), 14);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_duplicates_skips_hard_links() {
+        let dir = test_dir("links");
+        fs::write(dir.join("a"), "content").unwrap();
+        fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+
+        let groups = groups(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(groups.is_empty());
+    }
+}
@@ -1,10 +1,29 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
 use inquire::InquireError;
 
+/// Errors of fs-rs, see [`FsRsError::exit_code`] for the process exit codes.
 #[derive(Debug)]
 pub enum FsRsError {
-    SearchError(SearchError),
-    Inquire(InquireError),
-    Crossterm(std::io::Error),
+    Search(SearchError),
+    Filesystem {
+        operation: FsOperation,
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Prompt(InquireError),
+    Terminal(std::io::Error),
+    Watch(notify::Error),
+    Config(ConfigError),
+    Rename(RenameError),
+    Replace(ReplaceError),
+    Git(GitError),
+    /// The user stopped a search with Ctrl-C and did not want its partial results.
+    Interrupted,
 }
 
 #[derive(Debug)]
@@ -13,6 +32,319 @@ pub enum SearchError {
     IO(std::io::Error),
 }
 
+#[derive(Debug)]
+pub enum ConfigError {
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Serialize(toml::ser::Error),
+    Edit {
+        path: PathBuf,
+        source: toml_edit::TomlError,
+    },
+    /// A key of the config that has to be a table is some other value.
+    NotATable(String),
+    UnknownProfile(String),
+    UnknownFileType(String),
+}
+
+#[derive(Debug)]
+pub enum RenameError {
+    Pattern(regex::Error),
+    Template(String),
+    /// The renames of a plan would overwrite entries or each other.
+    Conflicts(usize),
+    /// A rename failed and some of the ones already done could not be reverted,
+    /// `entries` holds the path of each of those entries and its original path.
+    NotReverted {
+        source: Box<FsRsError>,
+        entries: Vec<(PathBuf, PathBuf)>,
+    },
+}
+
+#[derive(Debug)]
+pub enum ReplaceError {
+    Pattern(regex::Error),
+}
+
+#[derive(Debug)]
+pub enum GitError {
+    Read {
+        path: PathBuf,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    Revision {
+        revision: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionError {
+    Mode(String),
+    Owner(String),
+    UnknownUser(String),
+    UnknownGroup(String),
+}
+
+/// The filesystem operation that failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FsOperation {
+    Read,
+    Write,
+    Copy,
+    Move,
+    Delete,
+    Link,
+    Rename,
+    ChangePermissions,
+    Resolve,
+    CreateDirectory,
+}
+
+impl FsRsError {
+    pub fn filesystem(
+        operation: FsOperation,
+        path: impl AsRef<Path>,
+        source: std::io::Error,
+    ) -> Self {
+        FsRsError::Filesystem {
+            operation,
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    /// Exit code of the process when it fails with this error.
+    ///
+    /// | Code | Meaning                                    |
+    /// |------|--------------------------------------------|
+    /// | 1    | The search failed                          |
+    /// | 2    | Invalid command line arguments (by `clap`) |
+    /// | 3    | A filesystem operation failed              |
+    /// | 4    | A prompt failed or was canceled            |
+    /// | 5    | Writing to the terminal failed             |
+    /// | 6    | Watching the filesystem failed             |
+    /// | 7    | The config file is invalid                 |
+    /// | 8    | A rename or replacement is invalid         |
+    /// | 9    | A git repository could not be read         |
+    /// | 130  | A prompt or search was interrupted         |
+    #[must_use]
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            FsRsError::Search(_) => 1,
+            FsRsError::Filesystem { .. } => 3,
+            FsRsError::Prompt(InquireError::OperationInterrupted) | FsRsError::Interrupted => 130,
+            FsRsError::Prompt(_) => 4,
+            FsRsError::Terminal(_) => 5,
+            FsRsError::Watch(_) => 6,
+            FsRsError::Config(_) => 7,
+            FsRsError::Rename(_) | FsRsError::Replace(_) => 8,
+            FsRsError::Git(_) => 9,
+        }
+    }
+}
+
+impl Display for FsOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FsOperation::Read => write!(f, "read"),
+            FsOperation::Write => write!(f, "write"),
+            FsOperation::Copy => write!(f, "copy"),
+            FsOperation::Move => write!(f, "move"),
+            FsOperation::Delete => write!(f, "delete"),
+            FsOperation::Link => write!(f, "link"),
+            FsOperation::Rename => write!(f, "rename"),
+            FsOperation::ChangePermissions => write!(f, "change the permissions of"),
+            FsOperation::Resolve => write!(f, "resolve"),
+            FsOperation::CreateDirectory => write!(f, "create directory"),
+        }
+    }
+}
+
+impl Display for FsRsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FsRsError::Search(_) => write!(f, "search failed"),
+            FsRsError::Filesystem {
+                operation,
+                path,
+                source: _,
+            } => write!(f, "could not {operation} {path:?}"),
+            FsRsError::Prompt(_) => write!(f, "prompt failed"),
+            FsRsError::Terminal(_) => write!(f, "could not write to the terminal"),
+            FsRsError::Watch(_) => write!(f, "could not watch the filesystem"),
+            FsRsError::Config(_) => write!(f, "invalid configuration"),
+            FsRsError::Rename(_) => write!(f, "could not rename the entries"),
+            FsRsError::Replace(_) => write!(f, "could not replace the file contents"),
+            FsRsError::Git(_) => write!(f, "could not read the git repository"),
+            FsRsError::Interrupted => write!(f, "search interrupted"),
+        }
+    }
+}
+
+impl Display for SearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchError::WalkDir(_) => write!(f, "could not traverse the filesystem"),
+            SearchError::IO(_) => write!(f, "could not read a filesystem entry"),
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Parse { path, source: _ } => write!(f, "could not parse {path:?}"),
+            ConfigError::Serialize(_) => write!(f, "could not serialize the config"),
+            ConfigError::Edit { path, source: _ } => write!(f, "could not edit {path:?}"),
+            ConfigError::NotATable(key) => write!(f, "'{key}' in the config is not a table"),
+            ConfigError::UnknownProfile(name) => write!(f, "no profile named '{name}'"),
+            ConfigError::UnknownFileType(name) => write!(f, "no file type named '{name}'"),
+        }
+    }
+}
+
+impl Display for RenameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenameError::Pattern(_) => write!(f, "invalid rename pattern"),
+            RenameError::Template(why) => write!(f, "invalid rename template: {why}"),
+            RenameError::Conflicts(count) => write!(f, "the renames have '{count}' conflicts"),
+            RenameError::NotReverted { entries, source: _ } => write!(
+                f,
+                "a rename failed and '{}' entries could not be renamed back",
+                entries.len()
+            ),
+        }
+    }
+}
+
+impl Display for ReplaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplaceError::Pattern(_) => write!(f, "invalid search pattern"),
+        }
+    }
+}
+
+impl Display for GitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitError::Read { path, source: _ } => {
+                write!(f, "could not read the status of {path:?}")
+            }
+            GitError::Revision {
+                revision,
+                source: _,
+            } => write!(f, "no revision '{revision}'"),
+        }
+    }
+}
+
+impl Display for PermissionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionError::Mode(mode) => write!(f, "invalid mode '{mode}'"),
+            PermissionError::Owner(owner) => write!(f, "invalid owner '{owner}'"),
+            PermissionError::UnknownUser(name) => write!(f, "no user named '{name}'"),
+            PermissionError::UnknownGroup(name) => write!(f, "no group named '{name}'"),
+        }
+    }
+}
+
+impl Error for FsRsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FsRsError::Search(e) => Some(e),
+            FsRsError::Filesystem {
+                operation: _,
+                path: _,
+                source,
+            } => Some(source),
+            FsRsError::Prompt(e) => Some(e),
+            FsRsError::Terminal(e) => Some(e),
+            FsRsError::Watch(e) => Some(e),
+            FsRsError::Config(e) => Some(e),
+            FsRsError::Rename(e) => Some(e),
+            FsRsError::Replace(e) => Some(e),
+            FsRsError::Git(e) => Some(e),
+            FsRsError::Interrupted => None,
+        }
+    }
+}
+
+impl Error for SearchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SearchError::WalkDir(e) => Some(e),
+            SearchError::IO(e) => Some(e),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Parse { path: _, source } => Some(source),
+            ConfigError::Serialize(e) => Some(e),
+            ConfigError::Edit { path: _, source } => Some(source),
+            ConfigError::NotATable(_)
+            | ConfigError::UnknownProfile(_)
+            | ConfigError::UnknownFileType(_) => None,
+        }
+    }
+}
+
+impl Error for RenameError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenameError::Pattern(e) => Some(e),
+            RenameError::NotReverted { source, entries: _ } => Some(source),
+            RenameError::Template(_) | RenameError::Conflicts(_) => None,
+        }
+    }
+}
+
+impl Error for ReplaceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplaceError::Pattern(e) => Some(e),
+        }
+    }
+}
+
+impl Error for GitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GitError::Read { path: _, source } | GitError::Revision { revision: _, source } => {
+                Some(source.as_ref())
+            }
+        }
+    }
+}
+
+impl Error for PermissionError {}
+
+impl From<ReplaceError> for FsRsError {
+    fn from(value: ReplaceError) -> Self {
+        FsRsError::Replace(value)
+    }
+}
+
+impl From<GitError> for FsRsError {
+    fn from(value: GitError) -> Self {
+        FsRsError::Git(value)
+    }
+}
+
+impl From<RenameError> for FsRsError {
+    fn from(value: RenameError) -> Self {
+        FsRsError::Rename(value)
+    }
+}
+
 impl From<walkdir::Error> for SearchError {
     fn from(value: walkdir::Error) -> Self {
         SearchError::WalkDir(value)
@@ -25,20 +357,20 @@ impl From<std::io::Error> for SearchError {
     }
 }
 
-impl From<std::io::Error> for FsRsError {
-    fn from(value: std::io::Error) -> Self {
-        FsRsError::Crossterm(value)
-    }
-}
-
 impl From<SearchError> for FsRsError {
     fn from(value: SearchError) -> Self {
-        FsRsError::SearchError(value)
+        FsRsError::Search(value)
     }
 }
 
 impl From<InquireError> for FsRsError {
     fn from(value: InquireError) -> Self {
-        FsRsError::Inquire(value)
+        FsRsError::Prompt(value)
+    }
+}
+
+impl From<notify::Error> for FsRsError {
+    fn from(value: notify::Error) -> Self {
+        FsRsError::Watch(value)
     }
 }
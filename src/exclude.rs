@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use globset::{Glob, GlobBuilder, GlobMatcher};
+
+/// Entries left out of a search together with everything below them, given
+/// with `--exclude` and `--exclude-dir`.
+///
+/// Patterns containing a `/` match the whole path or the path relative to the
+/// search root, e.g. `/home/*/.cache` or `target/debug`, the others match the
+/// name, e.g. `*.log`. Directory patterns only match the names of directories,
+/// e.g. `node_modules`. The search roots themselves are never excluded.
+#[derive(Clone, Debug, Default)]
+pub struct Exclusions {
+    patterns: Vec<Pattern>,
+    directories: Vec<Pattern>,
+}
+
+#[derive(Clone, Debug)]
+struct Pattern {
+    glob: Glob,
+    matcher: GlobMatcher,
+    whole_path: bool,
+}
+
+impl PartialEq for Exclusions {
+    fn eq(&self, other: &Self) -> bool {
+        let globs = |x: &[Pattern]| x.iter().map(|p| p.glob.clone()).collect::<Vec<_>>();
+
+        globs(&self.patterns) == globs(&other.patterns)
+            && globs(&self.directories) == globs(&other.directories)
+    }
+}
+
+impl Eq for Exclusions {}
+
+impl Pattern {
+    fn new(glob: Glob) -> Self {
+        let whole_path = glob.glob().contains('/');
+
+        // `*` must not cross a `/` of a path, `target/*` is not `target/a/b`
+        let matcher = GlobBuilder::new(glob.glob())
+            .literal_separator(whole_path)
+            .build()
+            .map_or_else(|_| glob.compile_matcher(), |x| x.compile_matcher());
+
+        Self {
+            glob,
+            matcher,
+            whole_path,
+        }
+    }
+
+    fn matches(&self, path: &Path, relative: &Path) -> bool {
+        if self.whole_path {
+            self.matcher.is_match(path) || self.matcher.is_match(relative)
+        } else {
+            path.file_name()
+                .is_some_and(|x| self.matcher.is_match(x))
+        }
+    }
+}
+
+impl Exclusions {
+    #[must_use]
+    pub fn new(
+        patterns: impl IntoIterator<Item = Glob>,
+        directories: impl IntoIterator<Item = Glob>,
+    ) -> Self {
+        Self {
+            patterns: patterns.into_iter().map(Pattern::new).collect(),
+            directories: directories.into_iter().map(Pattern::new).collect(),
+        }
+    }
+
+    /// Excludes the entries matching `glob`.
+    pub fn add_pattern(&mut self, glob: Glob) {
+        self.patterns.push(Pattern::new(glob));
+    }
+
+    /// Excludes the directories matching `glob`.
+    pub fn add_directory(&mut self, glob: Glob) {
+        self.directories.push(Pattern::new(glob));
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && self.directories.is_empty()
+    }
+
+    pub fn patterns(&self) -> impl Iterator<Item = &Glob> {
+        self.patterns.iter().map(|x| &x.glob)
+    }
+
+    pub fn directories(&self) -> impl Iterator<Item = &Glob> {
+        self.directories.iter().map(|x| &x.glob)
+    }
+
+    /// Whether the entry at `path`, found below `root`, is excluded.
+    #[must_use]
+    pub fn excludes(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        if relative.as_os_str().is_empty() {
+            return false;
+        }
+
+        self.patterns.iter().any(|x| x.matches(path, relative))
+            || (is_dir && self.directories.iter().any(|x| x.matches(path, relative)))
+    }
+
+    /// Whether the entry at `path` or one of its parents below `root` is
+    /// excluded, for entries that were not found by walking `root`.
+    #[must_use]
+    pub fn excludes_below(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        path.ancestors()
+            .take_while(|x| x.starts_with(root))
+            .any(|x| self.excludes(root, x, is_dir || x != path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exclusions(patterns: &[&str], directories: &[&str]) -> Exclusions {
+        let globs = |x: &[&str]| x.iter().map(|x| Glob::new(x).unwrap()).collect::<Vec<_>>();
+        Exclusions::new(globs(patterns), globs(directories))
+    }
+
+    #[test]
+    fn excludes_names() {
+        let exclusions = exclusions(&["*.log"], &["node_modules"]);
+        let root = Path::new("/project");
+
+        assert!(exclusions.excludes(root, Path::new("/project/build.log"), false));
+        assert!(exclusions.excludes(root, Path::new("/project/a/b/build.log"), false));
+        assert!(!exclusions.excludes(root, Path::new("/project/build.log.txt"), false));
+        assert!(exclusions.excludes(root, Path::new("/project/web/node_modules"), true));
+        // directory patterns skip files of the same name
+        assert!(!exclusions.excludes(root, Path::new("/project/web/node_modules"), false));
+    }
+
+    #[test]
+    fn excludes_paths() {
+        let exclusions = exclusions(&["target/*", "/home/*/.cache"], &[]);
+        let root = Path::new("/project");
+
+        assert!(exclusions.excludes(root, Path::new("/project/target/debug"), true));
+        assert!(!exclusions.excludes(root, Path::new("/project/target/debug/app"), false));
+        assert!(!exclusions.excludes(root, Path::new("/project/a/target/debug"), true));
+        assert!(exclusions.excludes(root, Path::new("/home/me/.cache"), true));
+        assert!(!exclusions.excludes(root, Path::new("/home/me/work/.cache"), true));
+    }
+
+    #[test]
+    fn excludes_never_the_root() {
+        let exclusions = exclusions(&["*"], &["*"]);
+
+        assert!(!exclusions.excludes(Path::new("/project"), Path::new("/project"), true));
+        assert!(exclusions.excludes(Path::new("/project"), Path::new("/project/a"), true));
+    }
+
+    #[test]
+    fn excludes_below() {
+        let exclusions = exclusions(&[], &["node_modules"]);
+        let root = Path::new("/project");
+
+        assert!(exclusions.excludes_below(root, Path::new("/project/node_modules/a/b.js"), false));
+        assert!(!exclusions.excludes_below(root, Path::new("/project/src/b.js"), false));
+        // the parents of the root are not checked
+        assert!(!exclusions.excludes_below(
+            Path::new("/node_modules/project"),
+            Path::new("/node_modules/project/b.js"),
+            false
+        ));
+    }
+}
@@ -0,0 +1,719 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConfigError;
+
+/// Bytes read from the start of a file to find its magic bytes or shebang.
+pub static HEAD_SIZE: usize = 512;
+
+/// How files of a type are recognized, like the `--type` definitions of ripgrep.
+///
+/// In the config file the definitions extend the built-in ones of the same name:
+///
+/// ```toml
+/// [types.rust]
+/// file-names = ["Cargo.lock"]
+///
+/// [types.web]
+/// include = ["html", "css", "js"]
+///
+/// [types.blend]
+/// mime = "application/x-blender"
+/// extensions = ["blend"]
+/// magic = [[0x42, 0x4c, 0x45, 0x4e, 0x44, 0x45, 0x52]]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FileTypeDefinition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    /// Extensions without the leading dot, e.g. `rs` or `tar.gz`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// Whole file names, e.g. `Makefile`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_names: Vec<String>,
+    /// Interpreters of the shebang line, e.g. `python` for `#!/usr/bin/env python3`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shebangs: Vec<String>,
+    /// Bytes the files start with.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub magic: Vec<Vec<u8>>,
+    /// Other types whose files are files of this type, too.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+}
+
+/// The known file types, built-in and from the config file.
+#[derive(Clone, Debug)]
+pub struct FileTypes {
+    definitions: BTreeMap<String, FileTypeDefinition>,
+}
+
+/// A file type with the definitions of its included types resolved.
+#[derive(Clone, Debug)]
+pub struct FileType {
+    name: String,
+    definitions: Vec<FileTypeDefinition>,
+}
+
+struct BuiltinType {
+    name: &'static str,
+    mime: Option<&'static str>,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    shebangs: &'static [&'static str],
+    magic: &'static [&'static [u8]],
+    include: &'static [&'static str],
+}
+
+const NONE: BuiltinType = BuiltinType {
+    name: "",
+    mime: None,
+    extensions: &[],
+    file_names: &[],
+    shebangs: &[],
+    magic: &[],
+    include: &[],
+};
+
+static BUILTIN_TYPES: &[BuiltinType] = &[
+    BuiltinType {
+        name: "7z",
+        mime: Some("application/x-7z-compressed"),
+        extensions: &["7z"],
+        magic: &[b"7z\xbc\xaf\x27\x1c"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "archive",
+        include: &["zip", "tar", "gzip", "zstd", "xz", "bzip2", "7z"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "audio",
+        include: &["mp3", "flac", "ogg", "wav"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "bmp",
+        mime: Some("image/bmp"),
+        extensions: &["bmp"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "bzip2",
+        mime: Some("application/x-bzip2"),
+        extensions: &["bz2"],
+        magic: &[b"BZh"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "c",
+        mime: Some("text/x-c"),
+        extensions: &["c", "h"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "config",
+        include: &["json", "toml", "yaml", "ini", "xml"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "cpp",
+        mime: Some("text/x-c++"),
+        extensions: &["cpp", "cc", "cxx", "hpp", "hh", "hxx"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "csharp",
+        mime: Some("text/x-csharp"),
+        extensions: &["cs"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "css",
+        mime: Some("text/css"),
+        extensions: &["css", "scss", "sass", "less"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "csv",
+        mime: Some("text/csv"),
+        extensions: &["csv", "tsv"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "docker",
+        mime: Some("text/x-dockerfile"),
+        extensions: &["dockerfile"],
+        file_names: &["Dockerfile", "Containerfile"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "elf",
+        mime: Some("application/x-executable"),
+        extensions: &["so", "o"],
+        magic: &[b"\x7fELF"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "flac",
+        mime: Some("audio/flac"),
+        extensions: &["flac"],
+        magic: &[b"fLaC"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "gif",
+        mime: Some("image/gif"),
+        extensions: &["gif"],
+        magic: &[b"GIF87a", b"GIF89a"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "go",
+        mime: Some("text/x-go"),
+        extensions: &["go"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "gzip",
+        mime: Some("application/gzip"),
+        extensions: &["gz", "tgz"],
+        magic: &[b"\x1f\x8b"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "html",
+        mime: Some("text/html"),
+        extensions: &["html", "htm", "xhtml"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "ico",
+        mime: Some("image/vnd.microsoft.icon"),
+        extensions: &["ico"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "image",
+        include: &["png", "jpeg", "gif", "webp", "svg", "ico", "tiff", "bmp"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "ini",
+        mime: Some("text/x-ini"),
+        extensions: &["ini", "cfg", "conf"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "java",
+        mime: Some("text/x-java"),
+        extensions: &["java"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "jpeg",
+        mime: Some("image/jpeg"),
+        extensions: &["jpg", "jpeg"],
+        magic: &[b"\xff\xd8\xff"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "js",
+        mime: Some("text/javascript"),
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        shebangs: &["node"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "json",
+        mime: Some("application/json"),
+        extensions: &["json", "jsonl", "ndjson"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "kotlin",
+        mime: Some("text/x-kotlin"),
+        extensions: &["kt", "kts"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "lua",
+        mime: Some("text/x-lua"),
+        extensions: &["lua"],
+        shebangs: &["lua"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "make",
+        mime: Some("text/x-makefile"),
+        extensions: &["mk", "mak"],
+        file_names: &["Makefile", "makefile", "GNUmakefile"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "markdown",
+        mime: Some("text/markdown"),
+        extensions: &["md", "markdown", "mdx"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "mkv",
+        mime: Some("video/x-matroska"),
+        extensions: &["mkv"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "mp3",
+        mime: Some("audio/mpeg"),
+        extensions: &["mp3"],
+        magic: &[b"ID3"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "mp4",
+        mime: Some("video/mp4"),
+        extensions: &["mp4", "m4v", "m4a"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "ogg",
+        mime: Some("audio/ogg"),
+        extensions: &["ogg", "oga", "opus"],
+        magic: &[b"OggS"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "pdf",
+        mime: Some("application/pdf"),
+        extensions: &["pdf"],
+        magic: &[b"%PDF-"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "perl",
+        mime: Some("text/x-perl"),
+        extensions: &["pl", "pm"],
+        shebangs: &["perl"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "php",
+        mime: Some("text/x-php"),
+        extensions: &["php"],
+        shebangs: &["php"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "png",
+        mime: Some("image/png"),
+        extensions: &["png"],
+        magic: &[b"\x89PNG\r\n\x1a\n"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "python",
+        mime: Some("text/x-python"),
+        extensions: &["py", "pyi", "pyw"],
+        shebangs: &["python"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "ruby",
+        mime: Some("text/x-ruby"),
+        extensions: &["rb"],
+        file_names: &["Gemfile", "Rakefile"],
+        shebangs: &["ruby"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "rust",
+        mime: Some("text/x-rust"),
+        extensions: &["rs"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "sh",
+        mime: Some("text/x-shellscript"),
+        extensions: &["sh", "bash", "zsh"],
+        shebangs: &["sh", "bash", "zsh", "dash", "ksh"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "sql",
+        mime: Some("application/sql"),
+        extensions: &["sql"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "sqlite",
+        mime: Some("application/vnd.sqlite3"),
+        extensions: &["sqlite", "sqlite3", "db"],
+        magic: &[b"SQLite format 3\0"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "svg",
+        mime: Some("image/svg+xml"),
+        extensions: &["svg"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "tar",
+        mime: Some("application/x-tar"),
+        extensions: &["tar"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "tiff",
+        mime: Some("image/tiff"),
+        extensions: &["tif", "tiff"],
+        magic: &[b"II*\0", b"MM\0*"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "toml",
+        mime: Some("application/toml"),
+        extensions: &["toml"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "ts",
+        mime: Some("text/x-typescript"),
+        extensions: &["ts", "tsx", "mts", "cts"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "txt",
+        mime: Some("text/plain"),
+        extensions: &["txt", "text", "log"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "video",
+        include: &["mp4", "webm", "mkv"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "wav",
+        mime: Some("audio/wav"),
+        extensions: &["wav"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "webm",
+        mime: Some("video/webm"),
+        extensions: &["webm"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "webp",
+        mime: Some("image/webp"),
+        extensions: &["webp"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "xml",
+        mime: Some("application/xml"),
+        extensions: &["xml", "xsd", "xsl"],
+        magic: &[b"<?xml"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "xz",
+        mime: Some("application/x-xz"),
+        extensions: &["xz", "txz"],
+        magic: &[b"\xfd7zXZ\0"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "yaml",
+        mime: Some("application/yaml"),
+        extensions: &["yml", "yaml"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "zip",
+        mime: Some("application/zip"),
+        extensions: &["zip", "jar"],
+        magic: &[b"PK\x03\x04"],
+        ..NONE
+    },
+    BuiltinType {
+        name: "zstd",
+        mime: Some("application/zstd"),
+        extensions: &["zst", "tzst"],
+        magic: &[b"\x28\xb5\x2f\xfd"],
+        ..NONE
+    },
+];
+
+impl FileTypeDefinition {
+    /// Whether the definition needs the start of a file to recognize it.
+    #[must_use]
+    pub fn needs_content(&self) -> bool {
+        !self.magic.is_empty() || !self.shebangs.is_empty()
+    }
+
+    /// Checks the name of a file against the extensions and file names.
+    #[must_use]
+    pub fn matches_name(&self, name: &str) -> bool {
+        let lowercase = name.to_lowercase();
+
+        self.file_names.iter().any(|x| x == name)
+            || self.extensions.iter().any(|x| {
+                lowercase.len() > x.len() + 1
+                    && lowercase.ends_with(&x.to_lowercase())
+                    && lowercase[..lowercase.len() - x.len()].ends_with('.')
+            })
+    }
+
+    /// Checks the start of a file against the magic bytes and shebangs.
+    #[must_use]
+    pub fn matches_content(&self, head: &[u8]) -> bool {
+        self.magic.iter().any(|x| head.starts_with(x))
+            || shebang_interpreter(head).is_some_and(|interpreter| {
+                self.shebangs.iter().any(|x| {
+                    interpreter
+                        .strip_prefix(x.as_str())
+                        .is_some_and(|v| v.chars().all(|c| c.is_ascii_digit() || c == '.'))
+                })
+            })
+    }
+
+    fn extend(&mut self, other: &FileTypeDefinition) {
+        if other.mime.is_some() {
+            self.mime.clone_from(&other.mime);
+        }
+        self.extensions.extend(other.extensions.iter().cloned());
+        self.file_names.extend(other.file_names.iter().cloned());
+        self.shebangs.extend(other.shebangs.iter().cloned());
+        self.magic.extend(other.magic.iter().cloned());
+        self.include.extend(other.include.iter().cloned());
+    }
+}
+
+impl From<&BuiltinType> for FileTypeDefinition {
+    fn from(value: &BuiltinType) -> Self {
+        let strings = |x: &[&str]| x.iter().map(ToString::to_string).collect();
+
+        Self {
+            mime: value.mime.map(ToString::to_string),
+            extensions: strings(value.extensions),
+            file_names: strings(value.file_names),
+            shebangs: strings(value.shebangs),
+            magic: value.magic.iter().map(|x| x.to_vec()).collect(),
+            include: strings(value.include),
+        }
+    }
+}
+
+impl Default for FileTypes {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl FileTypes {
+    #[must_use]
+    pub fn builtin() -> Self {
+        Self {
+            definitions: BUILTIN_TYPES
+                .iter()
+                .map(|x| (x.name.to_string(), FileTypeDefinition::from(x)))
+                .collect(),
+        }
+    }
+
+    /// Adds the definitions, those with the name of a known type extend it.
+    #[must_use]
+    pub fn with_definitions(mut self, definitions: &BTreeMap<String, FileTypeDefinition>) -> Self {
+        for (name, definition) in definitions {
+            self.definitions
+                .entry(name.clone())
+                .or_default()
+                .extend(definition);
+        }
+
+        self
+    }
+
+    /// The definitions by name, sorted by name.
+    #[must_use]
+    pub fn definitions(&self) -> &BTreeMap<String, FileTypeDefinition> {
+        &self.definitions
+    }
+
+    /// Looks up a type by name together with the types it includes.
+    pub fn get(&self, name: &str) -> Result<FileType, ConfigError> {
+        let mut definitions = Vec::new();
+        let mut pending = vec![name];
+        let mut seen = HashSet::new();
+
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name) {
+                continue;
+            }
+
+            let definition = self
+                .definitions
+                .get(name)
+                .ok_or_else(|| ConfigError::UnknownFileType(name.to_string()))?;
+            pending.extend(definition.include.iter().map(String::as_str));
+            definitions.push(definition.clone());
+        }
+
+        Ok(FileType {
+            name: name.to_string(),
+            definitions,
+        })
+    }
+
+    /// The MIME type of a file by its name, `None` if the name is not enough.
+    #[must_use]
+    pub fn mime_by_name(&self, name: &str) -> Option<&str> {
+        self.definitions
+            .values()
+            .find(|x| x.mime.is_some() && x.matches_name(name))
+            .and_then(|x| x.mime.as_deref())
+    }
+
+    /// The MIME type of a file by the start of its content, files without
+    /// magic bytes or shebang are `text/plain` if they look like text.
+    #[must_use]
+    pub fn mime_by_content(&self, head: &[u8]) -> &str {
+        let found = self
+            .definitions
+            .values()
+            .find(|x| x.mime.is_some() && x.matches_content(head))
+            .and_then(|x| x.mime.as_deref());
+
+        match found {
+            Some(mime) => mime,
+            None if is_text(head) => "text/plain",
+            None => "application/octet-stream",
+        }
+    }
+}
+
+impl FileType {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn needs_content(&self) -> bool {
+        self.definitions
+            .iter()
+            .any(FileTypeDefinition::needs_content)
+    }
+
+    #[must_use]
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.definitions.iter().any(|x| x.matches_name(name))
+    }
+
+    #[must_use]
+    pub fn matches_content(&self, head: &[u8]) -> bool {
+        self.definitions.iter().any(|x| x.matches_content(head))
+    }
+}
+
+/// Reads up to [`HEAD_SIZE`] bytes from the start of the file.
+pub fn read_head(path: &Path) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(HEAD_SIZE);
+    File::open(path)?
+        .take(HEAD_SIZE as u64)
+        .read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// The program of a `#!` line, e.g. `python3` for `#!/usr/bin/env -S python3 -u`.
+fn shebang_interpreter(head: &[u8]) -> Option<&str> {
+    let line = head.strip_prefix(b"#!")?.split(|x| *x == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut words = line.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+
+    if program == "env" {
+        words.find(|x| !x.starts_with('-'))
+    } else {
+        Some(program)
+    }
+}
+
+/// Text has no NUL bytes and is valid UTF-8, apart from a character cut off at the end.
+fn is_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(why) => why.error_len().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(extensions: &[&str], file_names: &[&str]) -> FileTypeDefinition {
+        FileTypeDefinition {
+            extensions: extensions.iter().map(ToString::to_string).collect(),
+            file_names: file_names.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_name_extensions() {
+        let archives = definition(&["tar.gz", "zip"], &[]);
+
+        assert!(archives.matches_name("bundle.tar.gz"));
+        assert!(archives.matches_name("BUNDLE.TAR.GZ"));
+        assert!(archives.matches_name("photos.Zip"));
+        assert!(!archives.matches_name("bundle.gz"));
+        assert!(!archives.matches_name("bundletar.gz"));
+        assert!(!archives.matches_name("zip"));
+        assert!(!archives.matches_name(".zip"));
+        assert!(!archives.matches_name("unzip"));
+    }
+
+    #[test]
+    fn matches_name_file_names() {
+        let make = definition(&[], &["Makefile"]);
+
+        assert!(make.matches_name("Makefile"));
+        assert!(!make.matches_name("makefile"));
+        assert!(!make.matches_name("Makefile.bak"));
+    }
+
+    #[test]
+    fn shebang_interpreter_program() {
+        assert_eq!(shebang_interpreter(b"#!/bin/sh\necho"), Some("sh"));
+        assert_eq!(shebang_interpreter(b"#! /usr/bin/python3 -u\n"), Some("python3"));
+        assert_eq!(shebang_interpreter(b"#!/usr/bin/env node\n"), Some("node"));
+        assert_eq!(shebang_interpreter(b"#!/usr/bin/env -S python3 -u\n"), Some("python3"));
+        assert_eq!(shebang_interpreter(b"#!bash"), Some("bash"));
+    }
+
+    #[test]
+    fn shebang_interpreter_missing() {
+        assert_eq!(shebang_interpreter(b""), None);
+        assert_eq!(shebang_interpreter(b"#!\n/bin/sh"), None);
+        assert_eq!(shebang_interpreter(b"#!/usr/bin/env\n"), None);
+        assert_eq!(shebang_interpreter(b"# /bin/sh\n"), None);
+        assert_eq!(shebang_interpreter(b"\n#!/bin/sh\n"), None);
+        assert_eq!(shebang_interpreter(b"#!/bin/\xff\n"), None);
+    }
+}
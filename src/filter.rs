@@ -1,131 +1,272 @@
-use std::fs;
+use std::{
+    ffi::OsStr,
+    fs::{self, Metadata},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
 
-use walkdir::DirEntry;
+use globset::{Glob, GlobMatcher};
+use memchr::memmem::Finder;
 
-use crate::cli::{MatchOption, ResultFilter};
+use crate::{
+    archive::ArchiveEntry,
+    cli::{MatchOption, ResultFilter},
+    error::SearchError,
+    filetype::{self, FileType, FileTypes, HEAD_SIZE},
+    git::{GitState, GitStatus},
+    index::IndexEntry,
+    links::{self, FileId, LinkCount},
+    lint::{LintCheck, LintProblems},
+    search::{EntryKind, FileEntry, SearchWarning},
+    xattrs::{self, XattrPattern},
+};
 
-pub trait SearchFilter {
-    fn check_filter(&self, dir_entry: &DirEntry) -> bool;
+/// A condition that search results have to fulfill, shared between threads by
+/// the searches of the library.
+pub trait SearchFilter: Send + Sync {
+    /// Checks an entry on the filesystem, found by walking a directory or read from a list.
+    fn check_filter(&self, entry: &FileEntry) -> bool;
+    fn check_archive_entry(&self, archive_entry: &ArchiveEntry) -> bool;
+    /// Checks an entry of the file index, `None` if the filter needs the filesystem.
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool>;
+    /// Whether the filter needs the content of the archive member, it is only
+    /// read into memory if a filter needs it and all other filters match.
+    fn needs_archive_content(&self, _archive_entry: &ArchiveEntry) -> bool {
+        false
+    }
+    /// Bytes of file contents read by the filter so far, reported as search progress.
+    fn bytes_read(&self) -> u64 {
+        0
+    }
+    /// Entries the filter could not check since the last call, reported as search warnings.
+    fn take_warnings(&self) -> Vec<SearchWarning> {
+        Vec::new()
+    }
 }
 
 pub struct FilenameFilter {
-    file_names: Vec<String>,
-    match_option: MatchOption,
-    case_sensitiv: bool,
+    matcher: WordMatcher,
 }
 
 pub struct FileContentFilter {
-    words: Vec<String>,
-    match_option: MatchOption,
-    case_sensitiv: bool,
+    matcher: WordMatcher,
+    bytes_read: AtomicU64,
+    warnings: Mutex<Vec<SearchWarning>>,
 }
 
 pub struct EntryTypeFilter {
     result_type: ResultFilter,
 }
 
-impl FilenameFilter {
-    pub fn new(file_names: &[&str], match_option: MatchOption, case_sensitiv: bool) -> Self {
-        let file_names = file_names.iter().map(ToString::to_string).collect();
+/// Matches files of any of the types, by name or by the start of their content.
+pub struct FileTypeFilter {
+    file_types: Vec<FileType>,
+    bytes_read: AtomicU64,
+}
+
+/// Matches files by extension, ignoring case.
+pub struct ExtensionFilter {
+    extensions: Vec<String>,
+}
+
+/// Matches files whose MIME type matches any of the patterns, e.g. `text/*`.
+pub struct MimeFilter {
+    patterns: Vec<String>,
+    file_types: FileTypes,
+    bytes_read: AtomicU64,
+}
+
+/// Matches entries failing any of the hygiene checks of `fs-rs lint`.
+pub struct LintFilter {
+    checks: Vec<LintCheck>,
+    problems: LintProblems,
+}
+
+/// Matches the hard links to a file, like `find -samefile`.
+pub struct SameFileFilter {
+    file_id: Option<FileId>,
+}
+
+/// Matches entries by their number of hard links, like `find -links`.
+pub struct LinkCountFilter {
+    count: LinkCount,
+}
+
+/// Matches entries with an extended attribute, optionally with a given value.
+pub struct XattrFilter {
+    pattern: XattrPattern,
+}
+
+/// Matches entries carrying a POSIX ACL.
+pub struct AclFilter;
+
+/// Matches entries whose SELinux context matches a glob, e.g. `*:httpd_sys_content_t:*`.
+pub struct SecurityContextFilter {
+    matcher: GlobMatcher,
+}
+
+/// Matches entries with any of the statuses in their git repository.
+pub struct GitStatusFilter {
+    state: Arc<GitState>,
+    statuses: Vec<GitStatus>,
+}
+
+/// Matches entries changed since a git revision, like `git diff --name-only REV`.
+pub struct ChangedSinceFilter {
+    state: Arc<GitState>,
+}
+
+pub struct ModifiedFilter {
+    older_than: Option<Duration>,
+    newer_than: Option<Duration>,
+}
+
+pub struct SizeFilter {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+/// Matches words against raw bytes, so names and contents that are not valid
+/// UTF-8 can still be searched.
+struct WordMatcher {
+    words: Vec<Finder<'static>>,
+    match_option: MatchOption,
+    case_sensitiv: bool,
+}
+
+impl WordMatcher {
+    fn new<S: AsRef<OsStr>>(words: &[S], match_option: MatchOption, case_sensitiv: bool) -> Self {
+        let words = words
+            .iter()
+            .map(|x| {
+                let word = x.as_ref().as_encoded_bytes();
+
+                if case_sensitiv {
+                    Finder::new(word).into_owned()
+                } else {
+                    Finder::new(&to_lowercase(word)).into_owned()
+                }
+            })
+            .collect();
 
         Self {
-            file_names,
+            words,
             match_option,
             case_sensitiv,
         }
     }
-}
 
-impl SearchFilter for FilenameFilter {
-    fn check_filter(&self, dir_entry: &DirEntry) -> bool {
-        let name = dir_entry.file_name().to_str();
-
-        match name {
-            Some(n) => {
-                if self.case_sensitiv {
-                    match self.match_option {
-                        MatchOption::All => self.file_names.iter().all(|x| n.contains(x)),
-                        MatchOption::Any => self.file_names.iter().any(|x| n.contains(x)),
-                        MatchOption::None => !self.file_names.iter().any(|x| n.contains(x)),
-                    }
-                } else {
-                    match self.match_option {
-                        MatchOption::All => self.file_names.iter().all(|x| {
-                            n.to_lowercase()
-                                .as_str()
-                                .contains(x.to_lowercase().as_str())
-                        }),
-                        MatchOption::Any => self.file_names.iter().any(|x| {
-                            n.to_lowercase()
-                                .as_str()
-                                .contains(x.to_lowercase().as_str())
-                        }),
-                        MatchOption::None => !self.file_names.iter().any(|x| {
-                            n.to_lowercase()
-                                .as_str()
-                                .contains(x.to_lowercase().as_str())
-                        }),
-                    }
-                }
-            }
-            None => false,
+    fn matches(&self, haystack: &[u8]) -> bool {
+        let lowercase;
+        let haystack = if self.case_sensitiv {
+            haystack
+        } else {
+            lowercase = to_lowercase(haystack);
+            &lowercase
+        };
+
+        match self.match_option {
+            MatchOption::All => self.words.iter().all(|x| x.find(haystack).is_some()),
+            MatchOption::Any => self.words.iter().any(|x| x.find(haystack).is_some()),
+            MatchOption::None => !self.words.iter().any(|x| x.find(haystack).is_some()),
         }
     }
 }
 
-impl FileContentFilter {
-    pub fn new(words: &[&str], match_option: MatchOption, case_sensitiv: bool) -> Self {
-        let words = words.iter().map(ToString::to_string).collect();
+/// Unicode lowercase for UTF-8, ASCII lowercase for everything else.
+fn to_lowercase(bytes: &[u8]) -> Vec<u8> {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_lowercase().into_bytes(),
+        Err(_) => bytes.to_ascii_lowercase(),
+    }
+}
 
+impl FilenameFilter {
+    pub fn new<S: AsRef<OsStr>>(
+        file_names: &[S],
+        match_option: MatchOption,
+        case_sensitiv: bool,
+    ) -> Self {
         Self {
-            words,
-            match_option,
-            case_sensitiv,
+            matcher: WordMatcher::new(file_names, match_option, case_sensitiv),
         }
     }
 
-    fn check_content(&self, file_path: &str) -> bool {
-        if self.case_sensitiv {
-            match fs::read_to_string(file_path) {
-                Ok(c) => match self.match_option {
-                    MatchOption::All => self.words.iter().all(|x| c.contains(x)),
-                    MatchOption::Any => self.words.iter().any(|x| c.contains(x)),
-                    MatchOption::None => !self.words.iter().any(|x| c.contains(x)),
-                },
-                Err(_) => false,
-            }
-        } else {
-            match fs::read_to_string(file_path) {
-                Ok(c) => match self.match_option {
-                    MatchOption::All => self.words.iter().all(|x| {
-                        c.to_lowercase()
-                            .as_str()
-                            .contains(x.to_lowercase().as_str())
-                    }),
-                    MatchOption::Any => self.words.iter().any(|x| {
-                        c.to_lowercase()
-                            .as_str()
-                            .contains(x.to_lowercase().as_str())
-                    }),
-                    MatchOption::None => !self.words.iter().any(|x| {
-                        c.to_lowercase()
-                            .as_str()
-                            .contains(x.to_lowercase().as_str())
-                    }),
-                },
-                Err(_) => false,
-            }
+    fn check_name(&self, name: &OsStr) -> bool {
+        self.matcher.matches(name.as_encoded_bytes())
+    }
+}
+
+impl SearchFilter for FilenameFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        self.check_name(entry.file_name())
+    }
+
+    fn check_archive_entry(&self, archive_entry: &ArchiveEntry) -> bool {
+        self.check_name(OsStr::new(&archive_entry.name))
+    }
+
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool> {
+        Some(self.check_name(&index_entry.name()))
+    }
+}
+
+impl FileContentFilter {
+    pub fn new<S: AsRef<OsStr>>(words: &[S], match_option: MatchOption, case_sensitiv: bool) -> Self {
+        Self {
+            matcher: WordMatcher::new(words, match_option, case_sensitiv),
+            bytes_read: AtomicU64::new(0),
+            warnings: Mutex::new(Vec::new()),
         }
     }
 }
 
 impl SearchFilter for FileContentFilter {
-    fn check_filter(&self, dir_entry: &DirEntry) -> bool {
-        match dir_entry.path().to_str() {
-            Some(f) => self.check_content(f),
-            None => false,
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        match fs::read(entry.path()) {
+            Ok(c) => {
+                self.bytes_read
+                    .fetch_add(c.len() as u64, Ordering::Relaxed);
+                self.matcher.matches(&c)
+            }
+            Err(why) => {
+                // directories and special files have no content to search
+                if entry.file_type().is_file() {
+                    self.warnings
+                        .lock()
+                        .expect("warnings are not poisoned")
+                        .push(SearchWarning {
+                            path: Some(entry.path().to_path_buf()),
+                            error: SearchError::IO(why),
+                        });
+                }
+                false
+            }
         }
     }
+
+    fn check_archive_entry(&self, archive_entry: &ArchiveEntry) -> bool {
+        archive_entry.kind == EntryKind::File && self.matcher.matches(&archive_entry.content)
+    }
+
+    fn check_index_entry(&self, _: &IndexEntry) -> Option<bool> {
+        None
+    }
+
+    fn needs_archive_content(&self, _: &ArchiveEntry) -> bool {
+        true
+    }
+
+    fn take_warnings(&self) -> Vec<SearchWarning> {
+        std::mem::take(&mut *self.warnings.lock().expect("warnings are not poisoned"))
+    }
+
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
 }
 
 impl EntryTypeFilter {
@@ -138,12 +279,531 @@ impl EntryTypeFilter {
 }
 
 impl SearchFilter for EntryTypeFilter {
-    fn check_filter(&self, dir_entry: &DirEntry) -> bool {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        match self.result_type {
+            ResultFilter::All => true,
+            ResultFilter::Directory => entry.file_type().is_dir(),
+            ResultFilter::File => entry.file_type().is_file(),
+            ResultFilter::SymLink => entry.file_type().is_symlink(),
+        }
+    }
+
+    fn check_archive_entry(&self, archive_entry: &ArchiveEntry) -> bool {
         match self.result_type {
             ResultFilter::All => true,
-            ResultFilter::Directory => dir_entry.file_type().is_dir(),
-            ResultFilter::File => dir_entry.file_type().is_file(),
-            ResultFilter::SymLink => dir_entry.file_type().is_symlink(),
+            ResultFilter::Directory => archive_entry.kind == EntryKind::Directory,
+            ResultFilter::File => archive_entry.kind == EntryKind::File,
+            ResultFilter::SymLink => archive_entry.kind == EntryKind::SymLink,
+        }
+    }
+
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool> {
+        Some(match self.result_type {
+            ResultFilter::All => true,
+            ResultFilter::Directory => index_entry.kind == EntryKind::Directory,
+            ResultFilter::File => index_entry.kind == EntryKind::File,
+            ResultFilter::SymLink => index_entry.kind == EntryKind::SymLink,
+        })
+    }
+}
+
+impl FileTypeFilter {
+    #[must_use]
+    pub fn new(file_types: Vec<FileType>) -> Self {
+        Self {
+            file_types,
+            bytes_read: AtomicU64::new(0),
+        }
+    }
+
+    fn check_name(&self, name: &str) -> bool {
+        self.file_types.iter().any(|x| x.matches_name(name))
+    }
+
+    fn check_content(&self, head: &[u8]) -> bool {
+        self.file_types.iter().any(|x| x.matches_content(head))
+    }
+
+    fn needs_content(&self) -> bool {
+        self.file_types.iter().any(FileType::needs_content)
+    }
+}
+
+impl SearchFilter for FileTypeFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        if !entry.file_type().is_file() {
+            return false;
+        }
+
+        if self.check_name(&entry.file_name().to_string_lossy()) {
+            return true;
+        }
+
+        if !self.needs_content() {
+            return false;
+        }
+
+        match filetype::read_head(entry.path()) {
+            Ok(head) => {
+                self.bytes_read
+                    .fetch_add(head.len() as u64, Ordering::Relaxed);
+                self.check_content(&head)
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn check_archive_entry(&self, archive_entry: &ArchiveEntry) -> bool {
+        archive_entry.kind == EntryKind::File
+            && (self.check_name(&archive_entry.name)
+                || self.check_content(head(&archive_entry.content)))
+    }
+
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool> {
+        if index_entry.kind != EntryKind::File {
+            Some(false)
+        } else if self.check_name(&index_entry.name().to_string_lossy()) {
+            Some(true)
+        } else if self.needs_content() {
+            None
+        } else {
+            Some(false)
+        }
+    }
+
+    fn needs_archive_content(&self, archive_entry: &ArchiveEntry) -> bool {
+        self.needs_content() && !self.check_name(&archive_entry.name)
+    }
+
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+}
+
+impl ExtensionFilter {
+    /// Takes the extensions with or without the leading dot, e.g. `md` or `.tar.gz`.
+    pub fn new<S: AsRef<str>>(extensions: &[S]) -> Self {
+        Self {
+            extensions: extensions
+                .iter()
+                .map(|x| format!(".{}", x.as_ref().trim_start_matches('.').to_lowercase()))
+                .collect(),
+        }
+    }
+
+    fn check_name(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+
+        self.extensions
+            .iter()
+            .any(|x| name.len() > x.len() && name.ends_with(x.as_str()))
+    }
+}
+
+impl SearchFilter for ExtensionFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        !entry.file_type().is_dir() && self.check_name(&entry.file_name().to_string_lossy())
+    }
+
+    fn check_archive_entry(&self, archive_entry: &ArchiveEntry) -> bool {
+        archive_entry.kind != EntryKind::Directory && self.check_name(&archive_entry.name)
+    }
+
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool> {
+        Some(
+            index_entry.kind != EntryKind::Directory
+                && self.check_name(&index_entry.name().to_string_lossy()),
+        )
+    }
+}
+
+impl MimeFilter {
+    /// Takes patterns like `image/png`, `text/*` or `*/*`, the MIME types are
+    /// detected with the definitions of `file_types`.
+    pub fn new<S: AsRef<str>>(patterns: &[S], file_types: FileTypes) -> Self {
+        Self {
+            patterns: patterns
+                .iter()
+                .map(|x| x.as_ref().to_lowercase())
+                .collect(),
+            file_types,
+            bytes_read: AtomicU64::new(0),
+        }
+    }
+
+    fn check_mime(&self, mime: &str) -> bool {
+        let mime = mime.to_lowercase();
+
+        self.patterns.iter().any(|x| match x.strip_suffix('*') {
+            Some(prefix) => mime.starts_with(prefix),
+            None => *x == mime,
+        })
+    }
+}
+
+impl SearchFilter for MimeFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        if !entry.file_type().is_file() {
+            return false;
+        }
+
+        if let Some(mime) = self
+            .file_types
+            .mime_by_name(&entry.file_name().to_string_lossy())
+        {
+            return self.check_mime(mime);
         }
+
+        match filetype::read_head(entry.path()) {
+            Ok(head) => {
+                self.bytes_read
+                    .fetch_add(head.len() as u64, Ordering::Relaxed);
+                self.check_mime(self.file_types.mime_by_content(&head))
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn check_archive_entry(&self, archive_entry: &ArchiveEntry) -> bool {
+        if archive_entry.kind != EntryKind::File {
+            return false;
+        }
+
+        match self.file_types.mime_by_name(&archive_entry.name) {
+            Some(mime) => self.check_mime(mime),
+            None => self.check_mime(
+                self.file_types
+                    .mime_by_content(head(&archive_entry.content)),
+            ),
+        }
+    }
+
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool> {
+        if index_entry.kind != EntryKind::File {
+            return Some(false);
+        }
+
+        self.file_types
+            .mime_by_name(&index_entry.name().to_string_lossy())
+            .map(|x| self.check_mime(x))
+    }
+
+    fn needs_archive_content(&self, archive_entry: &ArchiveEntry) -> bool {
+        self.file_types.mime_by_name(&archive_entry.name).is_none()
+    }
+
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+}
+
+impl LintFilter {
+    #[must_use]
+    pub fn new(checks: Vec<LintCheck>) -> Self {
+        Self {
+            checks,
+            problems: LintProblems::default(),
+        }
+    }
+
+    /// The failed checks of the matched entries, filled in while searching.
+    #[must_use]
+    pub fn problems(&self) -> LintProblems {
+        self.problems.clone()
+    }
+}
+
+impl SearchFilter for LintFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        let Some(metadata) = entry.metadata() else {
+            return false;
+        };
+
+        let problems: Vec<LintCheck> = self
+            .checks
+            .iter()
+            .copied()
+            .filter(|x| x.check(entry.path(), metadata, entry.root()))
+            .collect();
+
+        if problems.is_empty() {
+            return false;
+        }
+
+        self.problems.insert(entry.path(), problems);
+        true
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        false
+    }
+
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool> {
+        match self.checks.as_slice() {
+            [LintCheck::EmptyFile] => {
+                let is_empty = index_entry.kind == EntryKind::File && index_entry.size == 0;
+                if is_empty {
+                    self.problems.insert(&index_entry.path, vec![LintCheck::EmptyFile]);
+                }
+                Some(is_empty)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl SameFileFilter {
+    /// Matches the entries sharing the device and inode of `metadata`, none on
+    /// platforms without inodes.
+    #[must_use]
+    pub fn new(metadata: &Metadata) -> Self {
+        Self {
+            file_id: FileId::from_metadata(metadata),
+        }
+    }
+}
+
+impl SearchFilter for SameFileFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        self.file_id.is_some()
+            && entry.metadata().and_then(FileId::from_metadata) == self.file_id
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        false
+    }
+
+    fn check_index_entry(&self, _: &IndexEntry) -> Option<bool> {
+        None
+    }
+}
+
+impl LinkCountFilter {
+    #[must_use]
+    pub fn new(count: LinkCount) -> Self {
+        Self { count }
+    }
+}
+
+impl SearchFilter for LinkCountFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        entry
+            .metadata()
+            .is_some_and(|x| self.count.matches(links::link_count(x)))
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        false
+    }
+
+    fn check_index_entry(&self, _: &IndexEntry) -> Option<bool> {
+        None
+    }
+}
+
+impl XattrFilter {
+    #[must_use]
+    pub fn new(pattern: XattrPattern) -> Self {
+        Self { pattern }
+    }
+}
+
+impl SearchFilter for XattrFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        self.pattern.matches(entry.path())
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        false
+    }
+
+    fn check_index_entry(&self, _: &IndexEntry) -> Option<bool> {
+        None
+    }
+}
+
+impl SearchFilter for AclFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        xattrs::has_acl(entry.path())
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        false
+    }
+
+    fn check_index_entry(&self, _: &IndexEntry) -> Option<bool> {
+        None
+    }
+}
+
+impl SecurityContextFilter {
+    #[must_use]
+    pub fn new(context: &Glob) -> Self {
+        Self {
+            matcher: context.compile_matcher(),
+        }
+    }
+}
+
+impl SearchFilter for SecurityContextFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        xattrs::security_context(entry.path()).is_some_and(|x| self.matcher.is_match(x))
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        false
+    }
+
+    fn check_index_entry(&self, _: &IndexEntry) -> Option<bool> {
+        None
+    }
+}
+
+impl GitStatusFilter {
+    #[must_use]
+    pub fn new(state: Arc<GitState>, statuses: Vec<GitStatus>) -> Self {
+        Self { state, statuses }
+    }
+}
+
+impl SearchFilter for GitStatusFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        self.statuses
+            .iter()
+            .any(|x| self.state.has_status(entry.path(), *x))
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        false
+    }
+
+    fn check_index_entry(&self, _: &IndexEntry) -> Option<bool> {
+        None
+    }
+}
+
+impl ChangedSinceFilter {
+    #[must_use]
+    pub fn new(state: Arc<GitState>) -> Self {
+        Self { state }
+    }
+}
+
+impl SearchFilter for ChangedSinceFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        self.state.is_changed(entry.path())
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        false
+    }
+
+    fn check_index_entry(&self, _: &IndexEntry) -> Option<bool> {
+        None
+    }
+}
+
+fn head(content: &[u8]) -> &[u8] {
+    &content[..content.len().min(HEAD_SIZE)]
+}
+
+impl ModifiedFilter {
+    #[must_use]
+    pub fn new(older_than: Option<Duration>, newer_than: Option<Duration>) -> Self {
+        Self {
+            older_than,
+            newer_than,
+        }
+    }
+
+    fn check_modified(&self, modified: SystemTime) -> bool {
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+
+        self.older_than.is_none_or(|x| age >= x) && self.newer_than.is_none_or(|x| age <= x)
+    }
+}
+
+impl SearchFilter for ModifiedFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        match entry.metadata().and_then(|x| x.modified().ok()) {
+            Some(m) => self.check_modified(m),
+            None => false,
+        }
+    }
+
+    fn check_archive_entry(&self, _: &ArchiveEntry) -> bool {
+        self.older_than.is_none() && self.newer_than.is_none()
+    }
+
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool> {
+        Some(self.check_modified(index_entry.modified))
+    }
+}
+
+impl SizeFilter {
+    #[must_use]
+    pub fn new(min_size: Option<u64>, max_size: Option<u64>) -> Self {
+        Self { min_size, max_size }
+    }
+
+    fn check_size(&self, size: u64) -> bool {
+        self.min_size.is_none_or(|x| size >= x) && self.max_size.is_none_or(|x| size <= x)
+    }
+}
+
+impl SearchFilter for SizeFilter {
+    fn check_filter(&self, entry: &FileEntry) -> bool {
+        match entry.metadata() {
+            Some(m) => m.is_file() && self.check_size(m.len()),
+            None => false,
+        }
+    }
+
+    fn check_archive_entry(&self, archive_entry: &ArchiveEntry) -> bool {
+        archive_entry.kind == EntryKind::File
+            && archive_entry.size.is_some_and(|x| self.check_size(x))
+    }
+
+    fn check_index_entry(&self, index_entry: &IndexEntry) -> Option<bool> {
+        Some(index_entry.kind == EntryKind::File && self.check_size(index_entry.size))
+    }
+
+    /// The size of a compressed file is only known once it is decompressed.
+    fn needs_archive_content(&self, archive_entry: &ArchiveEntry) -> bool {
+        archive_entry.kind == EntryKind::File && archive_entry.size.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_matcher_options() {
+        let matcher = |option| WordMatcher::new(&["foo", "bar"], option, true);
+        let haystack = b"a foo in the haystack";
+
+        assert!(matcher(MatchOption::Any).matches(haystack));
+        assert!(!matcher(MatchOption::All).matches(haystack));
+        assert!(!matcher(MatchOption::None).matches(haystack));
+        assert!(matcher(MatchOption::All).matches(b"foobar"));
+        assert!(matcher(MatchOption::None).matches(b"baz"));
+    }
+
+    #[test]
+    fn word_matcher_case() {
+        let insensitive = WordMatcher::new(&["Straße"], MatchOption::Any, false);
+        let sensitive = WordMatcher::new(&["Straße"], MatchOption::Any, true);
+
+        assert!(insensitive.matches("DIE STRAßE".as_bytes()));
+        assert!(!sensitive.matches("die straße".as_bytes()));
+        assert!(insensitive.matches(b"\xff not utf-8 STRA\xc3\x9fE"));
+    }
+
+    #[test]
+    fn word_matcher_empty_word() {
+        assert!(WordMatcher::new(&[""], MatchOption::Any, true).matches(b""));
     }
 }
@@ -0,0 +1,43 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A free path next to `path` for a temporary entry, a hidden name like
+/// `.notes.txt.fs-rs-replace` that ends in `-1`, `-2`, … if it is taken.
+pub(crate) fn temporary_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".fs-rs-");
+    name.push(suffix);
+
+    let mut temp_path = path.with_file_name(&name);
+    let mut counter = 1;
+    while fs::symlink_metadata(&temp_path).is_ok() {
+        let mut numbered = name.clone();
+        numbered.push(format!("-{counter}"));
+        temp_path = path.with_file_name(numbered);
+        counter += 1;
+    }
+
+    temp_path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temporary_path_is_free() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-fsutil-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = temporary_path(&dir.join("notes.txt"), "replace");
+        fs::write(&first, "").unwrap();
+        let second = temporary_path(&dir.join("notes.txt"), "replace");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, dir.join(".notes.txt.fs-rs-replace"));
+        assert_eq!(second, dir.join(".notes.txt.fs-rs-replace-1"));
+    }
+}
@@ -0,0 +1,377 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use gix::{
+    bstr::BStr,
+    dir::{entry::Status, walk::EmissionMode},
+    status::{index_worktree, UntrackedFiles},
+    Repository,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::GitError;
+
+/// Number of commits [`CommitLookup::last_commit`] walks back before it gives up.
+const MAX_COMMIT_DEPTH: usize = 10_000;
+
+/// The state of an entry in its git repository, for `--git-status`.
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum GitStatus {
+    /// Files in the index, and directories containing them.
+    Tracked,
+    /// Tracked files with staged or unstaged changes, and directories containing them.
+    Modified,
+    /// Files and directories that are neither tracked nor ignored.
+    Untracked,
+    /// Files and directories excluded by `.gitignore` and other exclude files.
+    Ignored,
+}
+
+/// The git repositories of the search paths, read once before the search so the
+/// filters only look up paths.
+#[derive(Debug, Default)]
+pub struct GitState {
+    repositories: Vec<RepositoryState>,
+}
+
+/// The status of a work tree, all paths are relative to `workdir`.
+#[derive(Debug)]
+struct RepositoryState {
+    workdir: PathBuf,
+    tracked: HashSet<PathBuf>,
+    modified: HashSet<PathBuf>,
+    /// Untracked and ignored directories are listed once, not with their contents.
+    untracked: HashSet<PathBuf>,
+    ignored: HashSet<PathBuf>,
+    /// Paths changed between the revision of `--changed-since` and the work tree.
+    changed: HashSet<PathBuf>,
+}
+
+/// Finds the last commits of entries for the details view, the repositories are
+/// discovered once per directory and shared by the entries in them.
+#[derive(Debug, Default)]
+pub struct CommitLookup {
+    repositories: Vec<(PathBuf, Repository)>,
+    directories: HashMap<PathBuf, Option<usize>>,
+}
+
+/// The last commit that changed an entry, shown in the details view.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastCommit {
+    pub id: String,
+    pub author: String,
+    pub time: DateTime<Local>,
+    pub summary: String,
+}
+
+impl GitState {
+    /// Reads the repositories containing `paths`, with the changes since `since`
+    /// if it is given. Paths that are not in a git work tree have no status.
+    pub fn read(paths: &[PathBuf], since: Option<&str>) -> Result<Self, GitError> {
+        let mut state = GitState::default();
+
+        for path in paths {
+            let Ok(repository) = gix::discover(path) else {
+                continue;
+            };
+            let Some(workdir) = workdir(&repository) else {
+                continue;
+            };
+
+            if state.repositories.iter().any(|x| x.workdir == workdir) {
+                continue;
+            }
+
+            state
+                .repositories
+                .push(RepositoryState::read(&repository, workdir, since)?);
+        }
+
+        Ok(state)
+    }
+
+    /// Whether the entry at `path` has the status in its repository.
+    #[must_use]
+    pub fn has_status(&self, path: &Path, status: GitStatus) -> bool {
+        self.find(path)
+            .is_some_and(|(repository, path)| match status {
+                GitStatus::Tracked => repository.tracked.contains(&path),
+                GitStatus::Modified => repository.modified.contains(&path),
+                GitStatus::Untracked => contains_ancestor(&repository.untracked, &path),
+                GitStatus::Ignored => contains_ancestor(&repository.ignored, &path),
+            })
+    }
+
+    /// Whether the entry at `path` changed since the revision of `--changed-since`.
+    #[must_use]
+    pub fn is_changed(&self, path: &Path) -> bool {
+        self.find(path)
+            .is_some_and(|(repository, path)| repository.changed.contains(&path))
+    }
+
+    fn find(&self, path: &Path) -> Option<(&RepositoryState, PathBuf)> {
+        let path = resolve(path)?;
+
+        self.repositories
+            .iter()
+            .filter_map(|x| Some((x, path.strip_prefix(&x.workdir).ok()?.to_path_buf())))
+            .filter(|(_, path)| !path.starts_with(".git"))
+            // the innermost repository of nested ones
+            .max_by_key(|(x, _)| x.workdir.components().count())
+    }
+}
+
+impl RepositoryState {
+    fn read(
+        repository: &Repository,
+        workdir: PathBuf,
+        since: Option<&str>,
+    ) -> Result<Self, GitError> {
+        let read_error = |e: Box<dyn std::error::Error + Send + Sync>| GitError::Read {
+            path: workdir.clone(),
+            source: e,
+        };
+
+        let mut state = RepositoryState {
+            workdir: workdir.clone(),
+            tracked: HashSet::new(),
+            modified: HashSet::new(),
+            untracked: HashSet::new(),
+            ignored: HashSet::new(),
+            changed: HashSet::new(),
+        };
+
+        let index = repository
+            .index_or_empty()
+            .map_err(|e| read_error(Box::new(e)))?;
+        for entry in index.entries() {
+            insert_with_ancestors(&mut state.tracked, entry.path(&index));
+        }
+
+        let status = repository
+            .status(gix::progress::Discard)
+            .map_err(|e| read_error(Box::new(e)))?
+            .untracked_files(UntrackedFiles::Collapsed)
+            .dirwalk_options(|x| x.emit_ignored(Some(EmissionMode::CollapseDirectory)))
+            .into_iter(None)
+            .map_err(|e| read_error(Box::new(e)))?;
+
+        for item in status {
+            match item.map_err(|e| read_error(Box::new(e)))? {
+                gix::status::Item::TreeIndex(change) => {
+                    insert_with_ancestors(&mut state.modified, change.location());
+                }
+                gix::status::Item::IndexWorktree(item) => match item {
+                    index_worktree::Item::Modification { .. } if item.summary().is_some() => {
+                        insert_with_ancestors(&mut state.modified, item.rela_path());
+                    }
+                    index_worktree::Item::DirectoryContents { entry, .. } => match entry.status {
+                        Status::Untracked => {
+                            state.untracked.insert(to_path(entry.rela_path.as_ref()));
+                        }
+                        Status::Ignored(_) => {
+                            state.ignored.insert(to_path(entry.rela_path.as_ref()));
+                        }
+                        Status::Pruned | Status::Tracked => {}
+                    },
+                    index_worktree::Item::Rewrite { dirwalk_entry, .. } => {
+                        state
+                            .untracked
+                            .insert(to_path(dirwalk_entry.rela_path.as_ref()));
+                    }
+                    index_worktree::Item::Modification { .. } => {}
+                },
+            }
+        }
+
+        if let Some(revision) = since {
+            state.changed.clone_from(&state.modified);
+
+            let revision_error = |e: Box<dyn std::error::Error + Send + Sync>| GitError::Revision {
+                revision: revision.to_string(),
+                source: e,
+            };
+            let old = repository
+                .rev_parse_single(revision)
+                .map_err(|e| revision_error(Box::new(e)))?
+                .object()
+                .map_err(|e| revision_error(Box::new(e)))?
+                .peel_to_tree()
+                .map_err(|e| revision_error(Box::new(e)))?;
+            let head = repository
+                .head_tree_id_or_empty()
+                .map_err(|e| read_error(Box::new(e)))?;
+            let head = repository
+                .find_tree(head)
+                .map_err(|e| read_error(Box::new(e)))?;
+            let changes = repository
+                .diff_tree_to_tree(&old, &head, None)
+                .map_err(|e| read_error(Box::new(e)))?;
+
+            for change in changes {
+                insert_with_ancestors(&mut state.changed, change.location());
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+impl CommitLookup {
+    /// The last commit on the first-parent history of `HEAD` that changed the
+    /// entry at `path`, like `git log --first-parent -1 -- PATH`. `None` outside
+    /// of a repository, for entries without commits and for entries that did
+    /// not change in the last [`MAX_COMMIT_DEPTH`] commits.
+    pub fn last_commit(&mut self, path: &Path) -> Option<LastCommit> {
+        let path = resolve(path)?;
+        let index = self.find(path.parent()?)?;
+        let (workdir, repository) = &self.repositories[index];
+        let relative = path.strip_prefix(workdir).ok()?;
+
+        let entry_id = |commit: &gix::Commit<'_>| {
+            commit
+                .tree()
+                .ok()?
+                .lookup_entry_by_path(relative)
+                .ok()?
+                .map(|x| x.object_id())
+        };
+
+        let mut commit = repository.head_commit().ok()?;
+        let id = entry_id(&commit)?;
+        let mut parents = commit.ancestors().first_parent_only().all().ok()?.skip(1);
+
+        for _ in 0..MAX_COMMIT_DEPTH {
+            // the entry was added in the first commit
+            let Some(parent) = parents.next() else {
+                return LastCommit::new(&commit);
+            };
+            let parent = parent.ok()?.object().ok()?;
+
+            if entry_id(&parent) != Some(id) {
+                return LastCommit::new(&commit);
+            }
+
+            commit = parent;
+        }
+
+        None
+    }
+
+    /// The index of the repository containing `directory`, it is discovered on
+    /// the first lookup in the directory.
+    fn find(&mut self, directory: &Path) -> Option<usize> {
+        if let Some(index) = self.directories.get(directory) {
+            return *index;
+        }
+
+        let index = gix::discover(directory).ok().and_then(|repository| {
+            let workdir = workdir(&repository)?;
+
+            match self.repositories.iter().position(|(x, _)| *x == workdir) {
+                Some(index) => Some(index),
+                None => {
+                    self.repositories.push((workdir, repository));
+                    Some(self.repositories.len() - 1)
+                }
+            }
+        });
+        self.directories.insert(directory.to_path_buf(), index);
+
+        index
+    }
+}
+
+impl LastCommit {
+    fn new(commit: &gix::Commit<'_>) -> Option<Self> {
+        let author = commit.author().ok()?;
+        let time = DateTime::from_timestamp(author.time().ok()?.seconds, 0)?;
+
+        Some(Self {
+            id: commit.short_id().ok()?.to_string(),
+            author: format!("{} <{}>", author.name, author.email),
+            time: time.with_timezone(&Local),
+            summary: commit.message().ok()?.summary().to_string(),
+        })
+    }
+}
+
+/// The work tree of the repository, canonicalized like the paths of the entries.
+fn workdir(repository: &Repository) -> Option<PathBuf> {
+    fs::canonicalize(repository.workdir()?).ok()
+}
+
+/// The absolute path of an entry with its parent canonicalized, a symlink
+/// itself is not resolved as git tracks symlinks, not their targets.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if parent.as_os_str().is_empty() => {
+            Some(fs::canonicalize(".").ok()?.join(name))
+        }
+        (Some(parent), Some(name)) => Some(fs::canonicalize(parent).ok()?.join(name)),
+        _ => fs::canonicalize(path).ok(),
+    }
+}
+
+fn to_path(path: &BStr) -> PathBuf {
+    gix::path::from_bstr(path).into_owned()
+}
+
+fn insert_with_ancestors(paths: &mut HashSet<PathBuf>, path: &BStr) {
+    for ancestor in to_path(path).ancestors() {
+        if ancestor.as_os_str().is_empty() || !paths.insert(ancestor.to_path_buf()) {
+            break;
+        }
+    }
+}
+
+fn contains_ancestor(paths: &HashSet<PathBuf>, path: &Path) -> bool {
+    path.ancestors().any(|x| paths.contains(x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_with_ancestors_and_contains_ancestor() {
+        let mut paths = HashSet::new();
+        insert_with_ancestors(&mut paths, "a/b/c".into());
+        insert_with_ancestors(&mut paths, "a/d".into());
+
+        let mut sorted: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+        sorted.sort();
+        assert_eq!(
+            sorted,
+            [Path::new("a"), Path::new("a/b"), Path::new("a/b/c"), Path::new("a/d")]
+        );
+        assert!(contains_ancestor(&paths, Path::new("a/d/e")));
+        assert!(!contains_ancestor(&paths, Path::new("b/c")));
+    }
+
+    #[test]
+    fn read_skips_paths_outside_of_repositories() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-git-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+
+        // the temporary directory itself may be inside of a repository
+        let outside = gix::discover(&dir).is_err();
+        let state = GitState::read(std::slice::from_ref(&dir), None);
+        let commit = CommitLookup::default().last_commit(&dir.join("file"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        if outside {
+            let state = state.unwrap();
+            assert!(state.repositories.is_empty());
+            assert!(!state.has_status(&dir.join("file"), GitStatus::Untracked));
+            assert_eq!(commit, None);
+        }
+    }
+}
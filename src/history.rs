@@ -0,0 +1,408 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    config::Profile,
+    error::{FsOperation, FsRsError},
+    index::{bytes_to_path, path_to_bytes},
+    search::{ArchiveOrigin, EntryKind, SearchResult},
+};
+
+/// Number of searches kept in the history, older ones are removed.
+pub static MAX_HISTORY_ENTRIES: usize = 100;
+
+static HISTORY_FILE: &str = "history.jsonl";
+
+/// The arguments and results of a search, stored in the history or a result file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResultSet {
+    /// Command line arguments of the search, including the program name.
+    pub arguments: Vec<StoredOsString>,
+    /// Seconds since the unix epoch.
+    pub created: u64,
+    /// Directory the search ran in, relative paths of the arguments are resolved in it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<StoredOsString>,
+    /// The filters of the search that are not in the arguments, like those of a
+    /// search built in the dialogue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Profile>,
+    pub results: Vec<StoredResult>,
+}
+
+/// A search result without its metadata, which is read again when it is restored.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoredResult {
+    pub path: StoredOsString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<StoredOsString>,
+    pub kind: EntryKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<StoredArchiveOrigin>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoredArchiveOrigin {
+    pub archive: StoredOsString,
+    pub member: String,
+    pub size: Option<u64>,
+}
+
+/// An `OsString` stored as text if it is valid UTF-8 and as bytes otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredOsString(pub OsString);
+
+/// A search in the history, without its results.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub arguments: Vec<StoredOsString>,
+    pub created: u64,
+    pub result_count: usize,
+}
+
+/// Searches that were run before, stored in `$XDG_DATA_HOME/fs-rs/history`.
+///
+/// The entries are listed in `history.jsonl`, the results of each entry are
+/// stored in `ID.json` next to it.
+#[derive(Clone, Debug)]
+pub struct History {
+    dir: PathBuf,
+}
+
+impl ResultSet {
+    #[must_use]
+    pub fn new(arguments: Vec<OsString>, results: &[SearchResult]) -> Self {
+        Self {
+            arguments: arguments.into_iter().map(StoredOsString).collect(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs()),
+            working_dir: env::current_dir()
+                .ok()
+                .map(|x| StoredOsString(x.into_os_string())),
+            profile: None,
+            results: results.iter().map(StoredResult::from).collect(),
+        }
+    }
+
+    /// Stores the profile of the search, it is applied again when the search is run
+    /// from the history.
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<&Profile>) -> Self {
+        self.profile = profile.cloned();
+        self
+    }
+
+    /// Restores the results, their metadata is read again from the filesystem.
+    #[must_use]
+    pub fn search_results(&self) -> Vec<SearchResult> {
+        self.results.iter().map(StoredResult::restore).collect()
+    }
+
+    #[must_use]
+    pub fn arguments(&self) -> Vec<OsString> {
+        self.arguments.iter().map(|x| x.0.clone()).collect()
+    }
+
+    #[must_use]
+    pub fn working_dir(&self) -> Option<PathBuf> {
+        self.working_dir.as_ref().map(|x| PathBuf::from(&x.0))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, FsRsError> {
+        let file = File::open(path).map_err(|e| FsRsError::filesystem(FsOperation::Read, path, e))?;
+
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| FsRsError::filesystem(FsOperation::Read, path, io::Error::from(e)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), FsRsError> {
+        if let Some(parent) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| FsRsError::filesystem(FsOperation::CreateDirectory, parent, e))?;
+        }
+
+        File::create(path)
+            .and_then(|x| self.write_to(x))
+            .map_err(|e| FsRsError::filesystem(FsOperation::Write, path, e))
+    }
+
+    fn write_to(&self, file: File) -> io::Result<()> {
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+}
+
+impl From<&SearchResult> for StoredResult {
+    fn from(value: &SearchResult) -> Self {
+        Self {
+            path: StoredOsString(value.path().as_os_str().to_os_string()),
+            root: value
+                .root()
+                .map(|x| StoredOsString(x.as_os_str().to_os_string())),
+            kind: value.kind(),
+            archive: value.archive().map(|x| StoredArchiveOrigin {
+                archive: StoredOsString(x.archive.as_os_str().to_os_string()),
+                member: x.member.clone(),
+                size: x.size,
+            }),
+        }
+    }
+}
+
+impl StoredResult {
+    #[must_use]
+    pub fn restore(&self) -> SearchResult {
+        let archive = self.archive.as_ref().map(|x| ArchiveOrigin {
+            archive: PathBuf::from(&x.archive.0),
+            member: x.member.clone(),
+            size: x.size,
+        });
+
+        SearchResult::restore(
+            PathBuf::from(&self.path.0),
+            self.root.as_ref().map(|x| PathBuf::from(&x.0)),
+            self.kind,
+            archive,
+        )
+    }
+}
+
+impl History {
+    #[must_use]
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Default location of the history, `$XDG_DATA_HOME/fs-rs/history`.
+    #[must_use]
+    pub fn default_dir() -> PathBuf {
+        let data_dir = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|x| PathBuf::from(x).join(".local").join("share"))
+            })
+            .unwrap_or_else(env::temp_dir);
+
+        data_dir.join("fs-rs").join("history")
+    }
+
+    /// The searches in the history, oldest first.
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>, FsRsError> {
+        let path = self.dir.join(HISTORY_FILE);
+
+        let file = match File::open(&path) {
+            Ok(f) => f,
+            Err(why) if why.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(why) => return Err(FsRsError::filesystem(FsOperation::Read, &path, why)),
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| FsRsError::filesystem(FsOperation::Read, &path, e))?;
+
+            // A line that was not written completely is skipped.
+            if let Ok(entry) = serde_json::from_str(&line) {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Stores `result_set` as a new entry and returns its id.
+    ///
+    /// The id follows the last one in the history, a result file that appears
+    /// in the meantime is never overwritten.
+    pub fn add(&self, result_set: &ResultSet) -> Result<u64, FsRsError> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| FsRsError::filesystem(FsOperation::CreateDirectory, &self.dir, e))?;
+
+        let mut entries = self.entries()?;
+        let mut id = entries.last().map_or(1, |x| x.id + 1);
+        let (path, file) = loop {
+            let path = self.result_set_path(id);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(why) if why.kind() == ErrorKind::AlreadyExists => id += 1,
+                Err(why) => return Err(FsRsError::filesystem(FsOperation::Write, &path, why)),
+            }
+        };
+
+        result_set
+            .write_to(file)
+            .map_err(|e| FsRsError::filesystem(FsOperation::Write, &path, e))?;
+
+        let entry = HistoryEntry {
+            id,
+            arguments: result_set.arguments.clone(),
+            created: result_set.created,
+            result_count: result_set.results.len(),
+        };
+
+        if entries.len() < MAX_HISTORY_ENTRIES {
+            self.append_entry(&entry)?;
+            return Ok(id);
+        }
+
+        entries.push(entry);
+        let removed = entries.len() - MAX_HISTORY_ENTRIES;
+        for old in entries.drain(..removed) {
+            let _ = fs::remove_file(self.result_set_path(old.id));
+        }
+        self.write_entries(&entries)?;
+
+        Ok(id)
+    }
+
+    pub fn load(&self, id: u64) -> Result<ResultSet, FsRsError> {
+        ResultSet::load(&self.result_set_path(id))
+    }
+
+    pub fn clear(&self) -> Result<(), FsRsError> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(why) if why.kind() != ErrorKind::NotFound => {
+                Err(FsRsError::filesystem(FsOperation::Delete, &self.dir, why))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn result_set_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    fn append_entry(&self, entry: &HistoryEntry) -> Result<(), FsRsError> {
+        let path = self.dir.join(HISTORY_FILE);
+
+        let append = || -> io::Result<()> {
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            writeln!(file, "{}", serde_json::to_string(entry)?)
+        };
+
+        append().map_err(|e| FsRsError::filesystem(FsOperation::Write, &path, e))
+    }
+
+    fn write_entries(&self, entries: &[HistoryEntry]) -> Result<(), FsRsError> {
+        let path = self.dir.join(HISTORY_FILE);
+        let mut temp_path = path.as_os_str().to_os_string();
+        temp_path.push(".tmp");
+
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(File::create(&temp_path)?);
+            for entry in entries {
+                writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+            }
+            writer.into_inner()?.sync_all()?;
+            fs::rename(&temp_path, &path)
+        };
+
+        write().map_err(|e| FsRsError::filesystem(FsOperation::Write, &path, e))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum EncodedOsString {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Serialize for StoredOsString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let encoded = match self.0.to_str() {
+            Some(s) => EncodedOsString::Text(s.to_string()),
+            None => EncodedOsString::Bytes(path_to_bytes(Path::new(&self.0))),
+        };
+
+        encoded.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StoredOsString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = match EncodedOsString::deserialize(deserializer)? {
+            EncodedOsString::Text(s) => OsString::from(s),
+            EncodedOsString::Bytes(b) => bytes_to_path(b).into_os_string(),
+        };
+
+        Ok(Self(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("fs-rs-history-{name}-{}", std::process::id()))
+    }
+
+    fn result_set() -> ResultSet {
+        ResultSet::new(vec![OsString::from("fs-rs")], &[])
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stored_os_string_round_trip() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let text = StoredOsString(OsString::from("caf\u{e9}"));
+        let bytes = StoredOsString(OsString::from_vec(vec![b'a', 0xff, b'b']));
+
+        let text_json = serde_json::to_string(&text).unwrap();
+        let bytes_json = serde_json::to_string(&bytes).unwrap();
+
+        assert_eq!(text_json, "\"caf\u{e9}\"");
+        assert_eq!(bytes_json, "[97,255,98]");
+        assert_eq!(serde_json::from_str::<StoredOsString>(&text_json).unwrap(), text);
+        assert_eq!(serde_json::from_str::<StoredOsString>(&bytes_json).unwrap(), bytes);
+    }
+
+    #[test]
+    fn add_assigns_ids_after_the_last_entry() {
+        let history = History::new(test_dir("ids"));
+        let first = history.add(&result_set()).unwrap();
+        let second = history.add(&result_set()).unwrap();
+        // a result file that is not listed yet, e.g. one of a search running at the same time
+        fs::write(history.result_set_path(3), "").unwrap();
+        let third = history.add(&result_set()).unwrap();
+        let ids: Vec<u64> = history.entries().unwrap().iter().map(|x| x.id).collect();
+        let stray = fs::read_to_string(history.result_set_path(3)).unwrap();
+        let loaded = history.load(third).unwrap();
+        history.clear().unwrap();
+
+        assert_eq!((first, second, third), (1, 2, 4));
+        assert_eq!(ids, [1, 2, 4]);
+        assert_eq!(stray, "");
+        assert_eq!(loaded.arguments(), [OsString::from("fs-rs")]);
+    }
+
+    #[test]
+    fn add_trims_to_the_maximum_number_of_entries() {
+        let history = History::new(test_dir("trim"));
+        for _ in 0..MAX_HISTORY_ENTRIES + 2 {
+            history.add(&result_set()).unwrap();
+        }
+        let entries = history.entries().unwrap();
+        let removed = history.result_set_path(2).exists();
+        let kept = history.result_set_path(3).exists();
+        history.clear().unwrap();
+
+        assert_eq!(entries.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(entries[0].id, 3);
+        assert_eq!(entries.last().unwrap().id, MAX_HISTORY_ENTRIES as u64 + 2);
+        assert!(!removed);
+        assert!(kept);
+    }
+}
@@ -0,0 +1,533 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    fs::{self, File, Metadata},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::{FsOperation, FsRsError},
+    search::EntryKind,
+};
+
+static MAGIC: &[u8; 8] = b"FSRSIDX2";
+
+/// Longest path read from an index, a corrupt length must not allocate more.
+const MAX_PATH_LENGTH: u64 = 64 * 1024;
+
+/// A filesystem entry as it was stored in the index.
+#[derive(Clone, Debug)]
+pub struct IndexEntry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// On-disk index of the entries below a set of root directories.
+#[derive(Clone, Debug, Default)]
+pub struct FileIndex {
+    roots: Vec<PathBuf>,
+    entries: Vec<IndexEntry>,
+}
+
+impl IndexEntry {
+    fn from_metadata(path: PathBuf, metadata: &Metadata) -> Self {
+        let kind = if metadata.is_dir() {
+            EntryKind::Directory
+        } else if metadata.is_symlink() {
+            EntryKind::SymLink
+        } else {
+            EntryKind::File
+        };
+
+        Self {
+            path,
+            kind,
+            size: metadata.len(),
+            modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> OsString {
+        self.path
+            .file_name()
+            .map_or_else(|| self.path.as_os_str().to_os_string(), ToOwned::to_owned)
+    }
+}
+
+impl FileIndex {
+    /// Default location of the index, `$XDG_CACHE_HOME/fs-rs/index.db`.
+    #[must_use]
+    pub fn default_path() -> PathBuf {
+        let cache_dir = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|x| PathBuf::from(x).join(".cache")))
+            .unwrap_or_else(env::temp_dir);
+
+        cache_dir.join("fs-rs").join("index.db")
+    }
+
+    #[must_use]
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub fn covers(&self, path: &Path) -> bool {
+        self.roots.iter().any(|x| path.starts_with(x))
+    }
+
+    /// Indexes `paths` from scratch, replacing previously indexed entries below them.
+    pub fn build(&mut self, paths: &[PathBuf]) -> Result<(), FsRsError> {
+        self.update_roots(paths, &HashMap::new())
+    }
+
+    /// Re-indexes `paths`, or all roots if `paths` is empty.
+    ///
+    /// Only directories whose modification time changed since the last run are
+    /// read again, the children of unchanged directories are taken from the index.
+    pub fn update(&mut self, paths: &[PathBuf]) -> Result<(), FsRsError> {
+        let paths = if paths.is_empty() {
+            self.roots.clone()
+        } else {
+            paths.to_vec()
+        };
+
+        let mut children: HashMap<PathBuf, Vec<IndexEntry>> = HashMap::new();
+        for entry in &self.entries {
+            if let Some(parent) = entry.path.parent() {
+                children
+                    .entry(parent.to_path_buf())
+                    .or_default()
+                    .push(entry.clone());
+            }
+        }
+
+        self.update_roots(&paths, &children)
+    }
+
+    fn update_roots(
+        &mut self,
+        paths: &[PathBuf],
+        children: &HashMap<PathBuf, Vec<IndexEntry>>,
+    ) -> Result<(), FsRsError> {
+        for path in paths {
+            let root = fs::canonicalize(path)
+                .map_err(|e| FsRsError::filesystem(FsOperation::Resolve, path, e))?;
+            let previous: HashMap<PathBuf, IndexEntry> = self
+                .entries
+                .iter()
+                .filter(|x| x.path.starts_with(&root))
+                .map(|x| (x.path.clone(), x.clone()))
+                .collect();
+
+            let mut entries = Vec::new();
+            let metadata = fs::symlink_metadata(&root)
+                .map_err(|e| FsRsError::filesystem(FsOperation::Read, &root, e))?;
+            index_entry(
+                IndexEntry::from_metadata(root.clone(), &metadata),
+                &previous,
+                children,
+                &mut entries,
+            );
+
+            self.entries.retain(|x| !x.path.starts_with(&root));
+            self.entries.extend(entries);
+            self.roots.retain(|x| !x.starts_with(&root));
+            if !self.covers(&root) {
+                self.roots.push(root);
+            }
+        }
+
+        self.entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, FsRsError> {
+        Self::read_from(path).map_err(|e| FsRsError::filesystem(FsOperation::Read, path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), FsRsError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| FsRsError::filesystem(FsOperation::CreateDirectory, parent, e))?;
+        }
+
+        self.write_to(path)
+            .map_err(|e| FsRsError::filesystem(FsOperation::Write, path, e))
+    }
+
+    fn read_from(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an fs-rs index or one of an older version, build it again",
+            ));
+        }
+
+        let root_count = read_u64(&mut reader)?;
+        let mut roots = Vec::new();
+        for _ in 0..root_count {
+            roots.push(read_path(&mut reader)?);
+        }
+
+        let entry_count = read_u64(&mut reader)?;
+        let mut entries = Vec::new();
+        for _ in 0..entry_count {
+            let mut kind = [0; 1];
+            reader.read_exact(&mut kind)?;
+            let kind = match kind[0] {
+                b'd' => EntryKind::Directory,
+                b's' => EntryKind::SymLink,
+                b'f' => EntryKind::File,
+                _ => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid entry kind"))
+                }
+            };
+            let size = read_u64(&mut reader)?;
+            let modified = read_time(&mut reader)?;
+            let path = read_path(&mut reader)?;
+
+            entries.push(IndexEntry {
+                path,
+                kind,
+                size,
+                modified,
+            });
+        }
+
+        Ok(Self { roots, entries })
+    }
+
+    /// Writes the index to a temporary file next to `path` first, which is
+    /// removed again if writing fails.
+    fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut temp_path = path.as_os_str().to_os_string();
+        temp_path.push(".tmp");
+
+        let written = self
+            .write_entries(File::create(&temp_path)?)
+            .and_then(|()| fs::rename(&temp_path, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+
+        written
+    }
+
+    fn write_entries(&self, file: File) -> io::Result<()> {
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+
+        writer.write_all(&(self.roots.len() as u64).to_le_bytes())?;
+        for root in &self.roots {
+            write_path(&mut writer, root)?;
+        }
+
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for entry in &self.entries {
+            let kind = match entry.kind {
+                EntryKind::Directory => b'd',
+                EntryKind::File => b'f',
+                EntryKind::SymLink => b's',
+            };
+            writer.write_all(&[kind])?;
+            writer.write_all(&entry.size.to_le_bytes())?;
+            write_time(&mut writer, entry.modified)?;
+            write_path(&mut writer, &entry.path)?;
+        }
+
+        writer.into_inner()?.sync_all()
+    }
+}
+
+fn index_entry(
+    entry: IndexEntry,
+    previous: &HashMap<PathBuf, IndexEntry>,
+    children: &HashMap<PathBuf, Vec<IndexEntry>>,
+    entries: &mut Vec<IndexEntry>,
+) {
+    if entry.kind != EntryKind::Directory {
+        entries.push(entry);
+        return;
+    }
+
+    let unchanged = previous
+        .get(&entry.path)
+        .is_some_and(|x| x.kind == EntryKind::Directory && x.modified == entry.modified);
+
+    let dir_entries: Vec<IndexEntry> = match children.get(&entry.path) {
+        Some(c) if unchanged => c
+            .iter()
+            .map(|x| match x.kind {
+                EntryKind::Directory => fs::symlink_metadata(&x.path)
+                    .map_or_else(|_| x.clone(), |m| IndexEntry::from_metadata(x.path.clone(), &m)),
+                _ => x.clone(),
+            })
+            .collect(),
+        _ => read_dir_entries(&entry.path),
+    };
+
+    entries.push(entry);
+
+    for dir_entry in dir_entries {
+        index_entry(dir_entry, previous, children, entries);
+    }
+}
+
+fn read_dir_entries(path: &Path) -> Vec<IndexEntry> {
+    let Ok(read_dir) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    read_dir
+        .filter_map(Result::ok)
+        .filter_map(|x| {
+            let metadata = fs::symlink_metadata(x.path()).ok()?;
+            Some(IndexEntry::from_metadata(x.path(), &metadata))
+        })
+        .collect()
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+/// Modification times are stored to the nanosecond, a directory changed in
+/// the same second as the index was built is not taken as unchanged.
+fn read_time(reader: &mut impl Read) -> io::Result<SystemTime> {
+    let secs = read_u64(reader)?;
+    let mut nanos = [0; 4];
+    reader.read_exact(&mut nanos)?;
+    let nanos = u32::from_le_bytes(nanos);
+
+    if nanos >= 1_000_000_000 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid time"));
+    }
+
+    UNIX_EPOCH
+        .checked_add(Duration::new(secs, nanos))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid time"))
+}
+
+/// Times before the epoch are stored as the epoch.
+fn write_time(writer: &mut impl Write, time: SystemTime) -> io::Result<()> {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    writer.write_all(&since_epoch.as_secs().to_le_bytes())?;
+    writer.write_all(&since_epoch.subsec_nanos().to_le_bytes())
+}
+
+fn read_path(reader: &mut impl Read) -> io::Result<PathBuf> {
+    let length = read_u64(reader)?;
+    if length > MAX_PATH_LENGTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "path too long"));
+    }
+
+    let mut buffer = vec![0; usize::try_from(length).unwrap_or_default()];
+    reader.read_exact(&mut buffer)?;
+    Ok(bytes_to_path(buffer))
+}
+
+fn write_path(writer: &mut impl Write, path: &Path) -> io::Result<()> {
+    let bytes = path_to_bytes(path);
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(&bytes)
+}
+
+#[cfg(unix)]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+pub(crate) fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::SearchBuilder,
+        search::{SearchMode, SearchRoot, TraversalOptions},
+    };
+    use std::ops::ControlFlow;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("fs-rs-index-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    fn paths(index: &FileIndex, root: &Path) -> Vec<PathBuf> {
+        index
+            .entries()
+            .iter()
+            .map(|x| x.path.strip_prefix(root).unwrap().to_path_buf())
+            .collect()
+    }
+
+    #[test]
+    fn build_and_update() {
+        let dir = test_dir("update");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/a"), "content").unwrap();
+
+        let mut index = FileIndex::default();
+        index.build(std::slice::from_ref(&dir)).unwrap();
+        let built = paths(&index, &dir);
+        // the new file changes the modification time of `sub`, even within the same second
+        fs::write(dir.join("sub/b"), "").unwrap();
+        index.update(&[]).unwrap();
+        let updated = paths(&index, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(index.roots(), std::slice::from_ref(&dir));
+        assert_eq!(built, ["", "sub", "sub/a"].map(PathBuf::from));
+        assert_eq!(updated, ["", "sub", "sub/a", "sub/b"].map(PathBuf::from));
+        assert_eq!(index.entries()[2].size, 7);
+        assert_eq!(index.entries()[2].kind, EntryKind::File);
+    }
+
+    #[test]
+    fn search_uses_the_options_of_each_root() {
+        let dir = test_dir("roots");
+        fs::create_dir_all(dir.join("indexed/sub")).unwrap();
+        fs::create_dir_all(dir.join("on-disk/sub")).unwrap();
+        fs::write(dir.join("indexed/sub/a"), "").unwrap();
+        fs::write(dir.join("on-disk/sub/a"), "").unwrap();
+
+        let mut index = FileIndex::default();
+        index.build(&[dir.join("indexed")]).unwrap();
+        let top_level = TraversalOptions {
+            mode: SearchMode::TopLevelOnly,
+            ..TraversalOptions::default()
+        };
+        let search = SearchBuilder::new()
+            .search_root(SearchRoot::new(dir.join("indexed"), top_level.clone()))
+            .search_root(SearchRoot::new(dir.join("on-disk"), top_level))
+            .build();
+        let all = search.run_with_index(&index);
+        let mut first = Vec::new();
+        search.run_index_with(&index, |x| {
+            first.push(x);
+            ControlFlow::Break(())
+        });
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut found: Vec<_> = all
+            .results
+            .iter()
+            .map(|x| x.path().strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            ["indexed", "indexed/sub", "on-disk", "on-disk/sub"].map(PathBuf::from)
+        );
+        assert_eq!(first.len(), 1);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = test_dir("save");
+        fs::write(dir.join("a"), "content").unwrap();
+
+        let mut index = FileIndex::default();
+        index.build(std::slice::from_ref(&dir)).unwrap();
+        index.save(&dir.join("cache/index.db")).unwrap();
+        let loaded = FileIndex::load(&dir.join("cache/index.db")).unwrap();
+        let leftovers = fs::read_dir(dir.join("cache")).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.roots(), index.roots());
+        assert_eq!(loaded.entries().len(), index.entries().len());
+        for (loaded, entry) in loaded.entries().iter().zip(index.entries()) {
+            assert_eq!(loaded.path, entry.path);
+            assert_eq!(loaded.kind, entry.kind);
+            assert_eq!(loaded.size, entry.size);
+            assert_eq!(loaded.modified, entry.modified);
+        }
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn load_rejects_corrupt_files() {
+        let dir = test_dir("corrupt");
+        let mut index = FileIndex::default();
+        index.build(std::slice::from_ref(&dir)).unwrap();
+        index.save(&dir.join("index.db")).unwrap();
+        let valid = fs::read(dir.join("index.db")).unwrap();
+
+        let mut huge_path = MAGIC.to_vec();
+        huge_path.extend(1_u64.to_le_bytes());
+        huge_path.extend(u64::MAX.to_le_bytes());
+
+        let invalid = [
+            b"FSRSIDX1".to_vec(),
+            valid[..valid.len() - 1].to_vec(),
+            huge_path,
+        ];
+        let errors: Vec<io::ErrorKind> = invalid
+            .iter()
+            .map(|x| {
+                fs::write(dir.join("index.db"), x).unwrap();
+                FileIndex::read_from(&dir.join("index.db")).unwrap_err().kind()
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            errors,
+            [
+                io::ErrorKind::InvalidData,
+                io::ErrorKind::UnexpectedEof,
+                io::ErrorKind::InvalidData
+            ]
+        );
+    }
+
+    #[test]
+    fn save_removes_the_temporary_file_on_failure() {
+        let dir = test_dir("failure");
+        // a directory in the way of the rename
+        fs::create_dir_all(dir.join("index.db/child")).unwrap();
+
+        let saved = FileIndex::default().save(&dir.join("index.db"));
+        let temporary = dir.join("index.db.tmp").exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(saved.is_err());
+        assert!(!temporary);
+    }
+}
@@ -1,6 +1,26 @@
+pub mod archive;
+pub mod builder;
 pub mod cli;
+pub mod config;
 pub mod dialogue;
 pub mod displaying;
+pub mod duplicates;
 pub mod error;
+pub mod exclude;
+pub mod filetype;
 pub mod filter;
+mod fsutil;
+pub mod git;
+pub mod history;
+pub mod index;
+pub mod links;
+pub mod lint;
+pub mod permissions;
+pub mod rename;
+pub mod replace;
 pub mod search;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod usage;
+pub mod watch;
+pub mod xattrs;
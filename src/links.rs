@@ -0,0 +1,141 @@
+use std::{fmt::Display, fs::Metadata, path::PathBuf};
+
+use crate::search::SearchResult;
+
+/// Identifies a file independent of its paths, hard links to it share the id.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FileId {
+    pub device: u64,
+    pub inode: u64,
+}
+
+/// A number of hard links like `find -links`, `N` exactly, `+N` more and `-N` less.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LinkCount {
+    Exactly(u64),
+    MoreThan(u64),
+    LessThan(u64),
+}
+
+impl FileId {
+    /// The id of the entry, `None` on platforms without inodes.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        Some(Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    #[must_use]
+    pub fn from_metadata(_: &Metadata) -> Option<Self> {
+        None
+    }
+}
+
+impl LinkCount {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let parse = |x: &str| {
+            x.parse::<u64>()
+                .map_err(|why| format!("invalid link count '{value}': {why}"))
+        };
+
+        if let Some(count) = value.strip_prefix('+') {
+            Ok(LinkCount::MoreThan(parse(count)?))
+        } else if let Some(count) = value.strip_prefix('-') {
+            Ok(LinkCount::LessThan(parse(count)?))
+        } else {
+            Ok(LinkCount::Exactly(parse(value)?))
+        }
+    }
+
+    #[must_use]
+    pub fn matches(self, links: u64) -> bool {
+        match self {
+            LinkCount::Exactly(x) => links == x,
+            LinkCount::MoreThan(x) => links > x,
+            LinkCount::LessThan(x) => links < x,
+        }
+    }
+}
+
+impl Display for LinkCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkCount::Exactly(x) => write!(f, "{x}"),
+            LinkCount::MoreThan(x) => write!(f, "+{x}"),
+            LinkCount::LessThan(x) => write!(f, "-{x}"),
+        }
+    }
+}
+
+/// Number of hard links to the entry, 1 on platforms without them.
+#[cfg(unix)]
+#[must_use]
+pub fn link_count(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+#[must_use]
+pub fn link_count(_: &Metadata) -> u64 {
+    1
+}
+
+/// Paths of the other results that are hard links to the same file as `result`.
+#[must_use]
+pub fn linked_paths(result: &SearchResult, results: &[SearchResult]) -> Vec<PathBuf> {
+    let Some(id) = result.file_id() else {
+        return Vec::new();
+    };
+
+    results
+        .iter()
+        .filter(|x| x.path() != result.path() && x.file_id() == Some(id))
+        .map(|x| x.path().to_path_buf())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_count_parse() {
+        assert_eq!(LinkCount::parse("2"), Ok(LinkCount::Exactly(2)));
+        assert_eq!(LinkCount::parse("+1"), Ok(LinkCount::MoreThan(1)));
+        assert_eq!(LinkCount::parse("-3"), Ok(LinkCount::LessThan(3)));
+        assert_eq!(LinkCount::parse(" +0 "), Ok(LinkCount::MoreThan(0)));
+    }
+
+    #[test]
+    fn link_count_parse_invalid() {
+        for value in ["", "+", "-", "two", "+-1", "--1", "1.5"] {
+            assert!(LinkCount::parse(value).is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn link_count_display_round_trips() {
+        for count in [LinkCount::Exactly(1), LinkCount::MoreThan(2), LinkCount::LessThan(3)] {
+            assert_eq!(LinkCount::parse(&count.to_string()), Ok(count));
+        }
+    }
+
+    #[test]
+    fn link_count_matches() {
+        assert!(LinkCount::Exactly(2).matches(2));
+        assert!(!LinkCount::Exactly(2).matches(3));
+        assert!(LinkCount::MoreThan(1).matches(2));
+        assert!(!LinkCount::MoreThan(1).matches(1));
+        assert!(LinkCount::LessThan(2).matches(1));
+        assert!(!LinkCount::LessThan(2).matches(2));
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{self, Metadata},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::search::SearchResult;
+
+/// A hygiene problem of a filesystem entry, found with `fs-rs lint`.
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintCheck {
+    /// Symlinks whose target does not exist.
+    Dangling,
+    /// Symlinks whose target is outside of the search root they were found in.
+    External,
+    /// Symlinks that lead back to themselves.
+    Cycle,
+    EmptyFile,
+    EmptyDirectory,
+    /// Entries without any permission bits, mode `000`.
+    NoPermissions,
+}
+
+/// A search result together with the checks it failed.
+#[derive(Clone, Debug)]
+pub struct LintFinding {
+    pub result: SearchResult,
+    pub problems: Vec<LintCheck>,
+}
+
+/// The failed checks of the entries the [`LintFilter`](crate::filter::LintFilter)
+/// matched, by path, shared with the caller of the search.
+#[derive(Clone, Debug, Default)]
+pub struct LintProblems(Arc<Mutex<HashMap<PathBuf, Vec<LintCheck>>>>);
+
+impl LintCheck {
+    pub const ALL: [LintCheck; 6] = [
+        LintCheck::Dangling,
+        LintCheck::External,
+        LintCheck::Cycle,
+        LintCheck::EmptyFile,
+        LintCheck::EmptyDirectory,
+        LintCheck::NoPermissions,
+    ];
+
+    /// Whether the entry at `path`, found below `root`, has the problem.
+    /// Symlinks are checked without following them, entries without a root
+    /// are never [`LintCheck::External`].
+    #[must_use]
+    pub fn check(self, path: &Path, metadata: &Metadata, root: Option<&Path>) -> bool {
+        match self {
+            LintCheck::Dangling => {
+                metadata.is_symlink() && fs::metadata(path).is_err_and(|x| !is_link_loop(&x))
+            }
+            LintCheck::External => {
+                metadata.is_symlink()
+                    && root.is_some_and(|root| {
+                        match (fs::canonicalize(path), fs::canonicalize(root)) {
+                            (Ok(target), Ok(root)) => !target.starts_with(root),
+                            _ => false,
+                        }
+                    })
+            }
+            LintCheck::Cycle => {
+                metadata.is_symlink() && fs::metadata(path).is_err_and(|x| is_link_loop(&x))
+            }
+            LintCheck::EmptyFile => metadata.is_file() && metadata.len() == 0,
+            LintCheck::EmptyDirectory => {
+                metadata.is_dir() && fs::read_dir(path).is_ok_and(|mut x| x.next().is_none())
+            }
+            LintCheck::NoPermissions => !metadata.is_symlink() && has_no_permissions(metadata),
+        }
+    }
+}
+
+impl Display for LintCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintCheck::Dangling => write!(f, "dangling symlink"),
+            LintCheck::External => write!(f, "symlink out of the root"),
+            LintCheck::Cycle => write!(f, "symlink cycle"),
+            LintCheck::EmptyFile => write!(f, "empty file"),
+            LintCheck::EmptyDirectory => write!(f, "empty directory"),
+            LintCheck::NoPermissions => write!(f, "no permissions"),
+        }
+    }
+}
+
+impl LintProblems {
+    pub(crate) fn insert(&self, path: &Path, problems: Vec<LintCheck>) {
+        if let Ok(mut x) = self.0.lock() {
+            x.insert(path.to_path_buf(), problems);
+        }
+    }
+
+    /// The results together with the checks they failed during the search.
+    #[must_use]
+    pub fn findings(&self, results: &[SearchResult]) -> Vec<LintFinding> {
+        let Ok(problems) = self.0.lock() else {
+            return Vec::new();
+        };
+
+        results
+            .iter()
+            .filter(|x| x.archive().is_none())
+            .filter_map(|x| {
+                Some(LintFinding {
+                    result: x.clone(),
+                    problems: problems.get(x.path())?.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Resolving a symlink failed because it leads back to itself.
+#[cfg(unix)]
+fn is_link_loop(error: &io::Error) -> bool {
+    error.raw_os_error() == Some(nix::errno::Errno::ELOOP as i32)
+}
+
+#[cfg(not(unix))]
+fn is_link_loop(_: &io::Error) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn has_no_permissions(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.mode() & 0o777 == 0
+}
+
+#[cfg(not(unix))]
+fn has_no_permissions(_: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn check_symlinks() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-lint-{}", std::process::id()));
+        let root = dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(dir.join("outside"), "").unwrap();
+        std::os::unix::fs::symlink("../outside", root.join("external")).unwrap();
+        std::os::unix::fs::symlink("missing", root.join("dangling")).unwrap();
+        std::os::unix::fs::symlink("cycle", root.join("cycle")).unwrap();
+
+        let check = |check: LintCheck, name: &str, root: Option<&Path>| {
+            let path = dir.join("root").join(name);
+            check.check(&path, &fs::symlink_metadata(&path).unwrap(), root)
+        };
+        let checks = [
+            check(LintCheck::External, "external", Some(&root)),
+            check(LintCheck::External, "external", None),
+            check(LintCheck::External, "external", Some(&dir)),
+            check(LintCheck::Dangling, "dangling", Some(&root)),
+            check(LintCheck::Dangling, "cycle", Some(&root)),
+            check(LintCheck::Cycle, "cycle", Some(&root)),
+        ];
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(checks, [true, false, false, true, false, true]);
+    }
+}
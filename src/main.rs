@@ -1,45 +1,648 @@
-use std::time::Instant;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use globset::Glob;
 
 use fs_rs::{
-    cli::{Cli, MatchOption, ResultFilter, Search},
+    archive::{archive_inputs, create_archive, ArchiveKind},
+    builder::SearchBuilder,
+    cli::{
+        days, Cli, DupesArgs, FilterArgs, HistoryAction, HistoryArgs, IndexAction, IndexArgs,
+        LintArgs, MatchOption, OutputFormat, PermissionsAction, PermissionsArgs, ReplaceArgs,
+        ResultFilter, ResultsAction, ResultsArgs, Search, UsageArgs, WatchArgs,
+    },
+    config::{Config, Profile, Settings, WordFilter},
     dialogue::{
-        CopyEntriesDialogue, DeleteEntriesDialogue, MoveEntriesDialogue, OpenEntriesDialogue,
+        ArchiveEntriesDialogue, ChangePermissionsDialogue, CopyEntriesDialogue,
+        DeleteEntriesDialogue, HardLinkDuplicatesDialogue, MoveEntriesDialogue,
+        OpenEntriesDialogue, RenameEntriesDialogue, ReplaceContentDialogue,
         RevealEntriesDialogue, ShowEntriesDialogue,
     },
-    displaying::{print_error, print_message, print_warning},
-    error::FsRsError,
-    filter::{EntryTypeFilter, FileContentFilter, FilenameFilter, SearchFilter},
-    search::{FileSearcher, SearchResult},
+    displaying::{
+        clear_progress, format_arguments, format_size, print_archive_inputs,
+        print_archive_progress, print_duplicate_group, print_error, print_fatal_error,
+        print_file_edit, print_file_types, print_history, print_lint_findings, print_lint_output,
+        print_message, print_not_reverted, print_permission_plan, print_permission_records,
+        print_progress, print_rename_plan, print_search_output, print_search_warnings,
+        print_usage_report, print_warning, print_watch_event, PROGRESS_INTERVAL,
+    },
+    duplicates::{find_duplicates, DuplicateGroup},
+    error::{FsOperation, FsRsError, RenameError},
+    filetype::FileTypes,
+    filter::LintFilter,
+    git::GitState,
+    history::{History, ResultSet},
+    index::FileIndex,
+    lint::LintCheck,
+    permissions::{PermissionJournal, PermissionPlan, PermissionRule},
+    rename::{RenamePlan, RenameRule},
+    replace::Replacer,
+    search::{CancellationToken, SearchOutput, SearchResult},
+    usage::analyze_usage,
+    watch::SearchWatcher,
 };
-use inquire::{Confirm, CustomType, Select, Text};
+use inquire::{validator::ValueRequiredValidator, Confirm, CustomType, Select, Text};
 
 static OPEN_ENTRY: &str = "Open entries";
 static SHOW_DETAILS: &str = "Show details";
 static COPY_ENTRIES: &str = "Copy entries";
 static REVEAL_ENTRIES: &str = "Reveal entries";
 static MOVE_ENTRIES: &str = "Move entries";
+static RENAME_ENTRIES: &str = "Rename entries";
+static REPLACE_CONTENT: &str = "Replace text in files";
+static ARCHIVE_ENTRIES: &str = "Archive entries";
+static CHANGE_PERMISSIONS: &str = "Change permissions";
 static DELETE_ENTRIES: &str = "Delete entries";
+static DELETE_DUPLICATES: &str = "Delete duplicates";
+static MOVE_DUPLICATES: &str = "Move duplicates";
+static LINK_DUPLICATES: &str = "Replace duplicates with hard links";
+
+/// The token of the running search, the Ctrl-C handler cancels it.
+static RUNNING_SEARCH: Mutex<Option<CancellationToken>> = Mutex::new(None);
+
+fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|why| why.exit());
+
+    if let Err(why) = set_interrupt_handler() {
+        let _ = print_warning(&format!(
+            "Ctrl-C ends the process instead of stopping the search: {why}"
+        ));
+    }
+
+    match run(cli, &matches, &env::args_os().collect::<Vec<_>>(), None) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(why) => {
+            print_fatal_error(&why);
+            ExitCode::from(why.exit_code())
+        }
+    }
+}
+
+/// Runs the command line, `arguments` are stored in the history to run the search again.
+/// `saved` is the profile of a search from the history, used if no profile is given.
+fn run(
+    mut cli: Cli,
+    matches: &ArgMatches,
+    arguments: &[OsString],
+    saved: Option<&Profile>,
+) -> Result<(), FsRsError> {
+    let config_path = config_path(&cli);
+    let config = Config::load(&config_path)?;
+    config.defaults.apply(&mut cli, matches);
+
+    let profile = match &cli.profile {
+        Some(name) => Some(config.profile(name)?),
+        None => saved,
+    };
+    if let Some(profile) = profile {
+        profile.apply(&mut cli, matches);
+    }
+
+    let cli = &cli;
+
+    if let Some(path) = &cli.load_results {
+        return show_result_set(cli, &ResultSet::load(path)?);
+    }
+
+    if let Some(Search::History(args)) = &cli.search {
+        return run_history(args);
+    }
+
+    if let Some(Search::Results(args)) = &cli.search {
+        return run_results(cli, args);
+    }
+
+    if let Some(Search::Permissions(args)) = &cli.search {
+        return run_permission_journal(args);
+    }
+
+    let file_types = config.file_types();
+
+    if let Some(Search::Types) = &cli.search {
+        return print_file_types(&file_types);
+    }
+
+    if let Some(Search::Dupes(args)) = &cli.search {
+        return run_dupes(cli, args);
+    }
+
+    if let Some(Search::Usage(args)) = &cli.search {
+        return run_usage(cli, args, &file_types);
+    }
+
+    if let Some(Search::Lint(args)) = &cli.search {
+        return run_lint(cli, args);
+    }
+
+    if let Some(Search::Index(args)) = &cli.search {
+        return run_index(cli, args);
+    }
+
+    if let Some(Search::Replace(args)) = &cli.search {
+        return run_replace(cli, args);
+    }
+
+    if let Some(Search::Watch(args)) = &cli.search {
+        return run_watch(cli, args);
+    }
+
+    if cli.search_paths.is_empty() && cli.root_options.is_empty() && !cli.reads_file_list() {
+        return run_dialogue(cli, &config_path, arguments);
+    }
+
+    run_cli(cli, profile, &file_types, arguments)
+}
+
+fn run_cli(
+    cli: &Cli,
+    profile: Option<&Profile>,
+    file_types: &FileTypes,
+    arguments: &[OsString],
+) -> Result<(), FsRsError> {
+    // Checked before searching, so a typo does not waste a long search.
+    let rename_rule = match &cli.rename {
+        Some(template) => Some(RenameRule::new(
+            cli.rename_pattern.as_deref(),
+            template,
+            cli.rename_case,
+        )?),
+        None => None,
+    };
+
+    let mut search = create_search_from_cli(cli, file_types)?;
+    if let Some(profile) = profile {
+        search = profile.apply_filters(search);
+    }
+    let index = load_index(cli)?;
+    let quiet = cli.format != OutputFormat::Text;
+    let (output, duration) = run_search(search, index.as_ref(), quiet)?;
+    save_results(cli, arguments, profile, &output.results, quiet)?;
+
+    if let Some(rule) = rename_rule {
+        print_search_warnings(&output.warnings, cli.show_errors)?;
+        return run_rename(cli, &rule, &output.results);
+    }
+
+    if let Some(path) = &cli.archive {
+        print_search_warnings(&output.warnings, cli.show_errors)?;
+        return run_archive(cli, path, &output.results);
+    }
+
+    let permission_rule = cli.permission_rule();
+    if !permission_rule.is_empty() {
+        print_search_warnings(&output.warnings, cli.show_errors)?;
+        return run_permissions(cli, &permission_rule, &output.results);
+    }
+
+    if quiet {
+        return print_search_output(&output, cli.format);
+    }
+
+    print_search_warnings(&output.warnings, cli.show_errors)?;
+    display_results(&output.results, duration, cli.max_results)
+}
+
+/// Renames the results, only shows the renames for `--dry-run`.
+fn run_rename(cli: &Cli, rule: &RenameRule, results: &[SearchResult]) -> Result<(), FsRsError> {
+    let plan = RenamePlan::new(rule, results);
+    print_rename_plan(&plan)?;
+
+    if !plan.conflicts().is_empty() {
+        return Err(RenameError::Conflicts(plan.conflicts().len()).into());
+    }
+
+    if cli.dry_run || plan.renames().is_empty() {
+        return Ok(());
+    }
+
+    let confirmation = cli.yes
+        || Confirm::new(&format!(
+            "Do you want to rename '{}' entries?",
+            plan.renames().len()
+        ))
+        .with_default(false)
+        .prompt()?;
+
+    if confirmation {
+        if let Err(why) = plan.apply() {
+            if let FsRsError::Rename(RenameError::NotReverted { entries, source: _ }) = &why {
+                print_not_reverted(entries)?;
+            }
+            return Err(why);
+        }
+        print_message(&format!("Renamed '{}' entries.", plan.renames().len()))?;
+    }
+
+    Ok(())
+}
+
+/// Packs the results into the `--archive` file, only lists the members for `--dry-run`.
+fn run_archive(cli: &Cli, path: &Path, results: &[SearchResult]) -> Result<(), FsRsError> {
+    let (inputs, warnings) = archive_inputs(results, path);
+    print_search_warnings(&warnings, cli.show_errors)?;
+
+    if cli.dry_run {
+        return print_archive_inputs(&inputs);
+    }
+
+    if inputs.is_empty() {
+        return print_warning("There are no entries to archive.");
+    }
+
+    let confirmation = cli.yes
+        || fs::symlink_metadata(path).is_err()
+        || Confirm::new(&format!("{path:?} already exists, overwrite it?"))
+            .with_default(false)
+            .prompt()?;
+
+    if !confirmation {
+        return Ok(());
+    }
+
+    // The value parser only accepts paths of a kind that can be written.
+    let kind = ArchiveKind::detect(path).unwrap_or(ArchiveKind::Tar);
+    let mut last_update = Instant::now();
+    let result = create_archive(path, kind, &inputs, |progress| {
+        if last_update.elapsed() >= PROGRESS_INTERVAL {
+            last_update = Instant::now();
+            let _ = print_archive_progress(progress);
+        }
+    });
+    clear_progress()?;
+    result?;
+
+    print_message(&format!(
+        "Archived '{}' entries into {path:?}.",
+        inputs.len()
+    ))
+}
+
+/// Changes the mode and owner of the results, only shows the changes for `--dry-run`.
+fn run_permissions(
+    cli: &Cli,
+    rule: &PermissionRule,
+    results: &[SearchResult],
+) -> Result<(), FsRsError> {
+    let (plan, warnings) = PermissionPlan::collect(rule, results, cli.recursive);
+    print_search_warnings(&warnings, cli.show_errors)?;
+    print_permission_plan(&plan)?;
+
+    if cli.dry_run || plan.changes().is_empty() {
+        return Ok(());
+    }
 
-fn main() -> Result<(), FsRsError> {
-    let cli = Cli::parse();
+    let confirmation = cli.yes
+        || Confirm::new(&format!(
+            "Do you want to change the permissions of '{}' entries?",
+            plan.changes().len()
+        ))
+        .with_default(false)
+        .prompt()?;
 
-    if cli.search_paths.is_empty() {
-        return run_dialogue();
+    if confirmation {
+        let id = plan.apply(&PermissionJournal::new(PermissionJournal::default_dir()))?;
+        print_message(&format!(
+            "Changed the permissions of '{}' entries, undo with `fs-rs permissions undo {id}`.",
+            plan.changes().len()
+        ))?;
     }
 
-    run_cli(&cli)
+    Ok(())
+}
+
+fn run_permission_journal(args: &PermissionsArgs) -> Result<(), FsRsError> {
+    let journal = PermissionJournal::new(PermissionJournal::default_dir());
+
+    match &args.action {
+        None | Some(PermissionsAction::List) => print_permission_records(&journal.records()?),
+        Some(PermissionsAction::Undo { id }) => {
+            let record = match id {
+                Some(id) => journal.load(*id)?,
+                None => match journal.records()?.pop() {
+                    Some(r) => r,
+                    None => return print_warning("There are no permission changes to undo."),
+                },
+            };
+
+            let skipped = record.undo()?;
+            journal.remove(record.id)?;
+
+            for path in &skipped {
+                print_warning(&format!("Skipped {path:?}, its permissions changed since."))?;
+            }
+            print_message(&format!(
+                "Restored the permissions of '{}' entries.",
+                record.changes.len() - skipped.len()
+            ))
+        }
+    }
 }
 
-fn run_cli(cli: &Cli) -> Result<(), FsRsError> {
-    let filters = create_filters_from_cli(cli);
-    let (results, duration) = run_search(filters, &cli.search_paths, cli.depth)?;
+fn run_history(args: &HistoryArgs) -> Result<(), FsRsError> {
+    let history = History::new(History::default_dir());
+
+    match &args.action {
+        None | Some(HistoryAction::List) => print_history(&history.entries()?),
+        Some(HistoryAction::Run { id }) => {
+            let result_set = history.load(*id)?;
+            let arguments = result_set.arguments();
+            let matches = Cli::command()
+                .try_get_matches_from(&arguments)
+                .unwrap_or_else(|why| why.exit());
+            let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|why| why.exit());
+
+            if let Some(dir) = result_set.working_dir() {
+                env::set_current_dir(&dir)
+                    .map_err(|e| FsRsError::filesystem(FsOperation::Resolve, &dir, e))?;
+            }
 
+            run(cli, &matches, &arguments, result_set.profile.as_ref())
+        }
+        Some(HistoryAction::Clear) => {
+            history.clear()?;
+            print_message("Cleared the history.")
+        }
+    }
+}
+
+fn run_results(cli: &Cli, args: &ResultsArgs) -> Result<(), FsRsError> {
+    match &args.action {
+        ResultsAction::Load { id } => {
+            let result_set = History::new(History::default_dir()).load(*id)?;
+            show_result_set(cli, &result_set)
+        }
+    }
+}
+
+fn show_result_set(cli: &Cli, result_set: &ResultSet) -> Result<(), FsRsError> {
+    let output = SearchOutput {
+        results: result_set.search_results(),
+        warnings: Vec::new(),
+    };
+
+    if let Some(path) = &cli.archive {
+        return run_archive(cli, path, &output.results);
+    }
+
+    let permission_rule = cli.permission_rule();
+    if !permission_rule.is_empty() {
+        return run_permissions(cli, &permission_rule, &output.results);
+    }
+
+    if cli.format != OutputFormat::Text {
+        return print_search_output(&output, cli.format);
+    }
+
+    print_message(&format!(
+        "Loaded '{}' results of `{}`.",
+        output.results.len(),
+        format_arguments(&result_set.arguments())
+    ))?;
+    show_entries_menu(&output.results, cli.max_results)
+}
+
+/// Stores the results in the history and the file given with `--save-results`.
+fn save_results(
+    cli: &Cli,
+    arguments: &[OsString],
+    profile: Option<&Profile>,
+    results: &[SearchResult],
+    quiet: bool,
+) -> Result<(), FsRsError> {
+    let result_set = ResultSet::new(arguments.to_vec(), results).with_profile(profile);
+
+    if let Some(path) = &cli.save_results {
+        result_set.save(path)?;
+    }
+
+    if cli.no_history {
+        return Ok(());
+    }
+
+    // The search itself succeeded, so a broken history only warrants a warning.
+    match History::new(History::default_dir()).add(&result_set) {
+        Err(why) if !quiet => {
+            print_warning(&format!("Could not add the search to the history: {why}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn run_dupes(cli: &Cli, args: &DupesArgs) -> Result<(), FsRsError> {
+    let mut search_paths = cli.search_paths.clone();
+    search_paths.extend(args.paths.iter().cloned());
+
+    if search_paths.is_empty() && !cli.reads_file_list() {
+        return print_warning("No paths to search for duplicates given!");
+    }
+
+    let search = create_search_for_dupes(search_builder(cli, &search_paths)?, args);
+    let index = load_index(cli)?;
+    let (output, duration) = run_search(search, index.as_ref(), false)?;
+    print_search_warnings(&output.warnings, cli.show_errors)?;
+    let groups = find_duplicates(&output.results);
+
+    display_duplicates(&groups, duration, cli.max_results)
+}
+
+fn run_usage(cli: &Cli, args: &UsageArgs, file_types: &FileTypes) -> Result<(), FsRsError> {
+    let mut search_paths = cli.search_paths.clone();
+    search_paths.extend(args.paths.iter().cloned());
+
+    if search_paths.is_empty() && !cli.reads_file_list() {
+        return print_warning("No paths to analyze given!");
+    }
+
+    let search = search_builder(cli, &search_paths)?;
+    let search = create_search_for_usage(search, cli, &search_paths, args, file_types)?;
+    let index = load_index(cli)?;
+    let (output, duration) = run_search(search, index.as_ref(), false)?;
+    print_search_warnings(&output.warnings, cli.show_errors)?;
+    let results = output.results;
+    let search_paths: Vec<PathBuf> = cli
+        .search_roots(&search_paths)
+        .into_iter()
+        .map(|x| x.path)
+        .collect();
+    let report = analyze_usage(&results, &search_paths, args.top, args.count_links);
+
+    print_usage_report(&report)?;
+
+    let largest_files: Vec<SearchResult> = report
+        .files
+        .iter()
+        .filter_map(|x| results.iter().find(|y| x.path.as_deref() == Some(y.path())))
+        .cloned()
+        .collect();
+
+    display_results(&largest_files, duration, cli.max_results)
+}
+
+fn run_lint(cli: &Cli, args: &LintArgs) -> Result<(), FsRsError> {
+    let mut search_paths = cli.search_paths.clone();
+    search_paths.extend(args.paths.iter().cloned());
+
+    if search_paths.is_empty() && !cli.reads_file_list() {
+        return print_warning("No paths to check given!");
+    }
+
+    // Symlinks are the subject of the checks, following them would hide dangling ones.
+    let cli = &Cli {
+        follow: false,
+        ..cli.clone()
+    };
+    let filter = LintFilter::new(lint_checks(args));
+    let problems = filter.problems();
+    let search = search_builder(cli, &search_paths)?.filter(filter);
+    let index = load_index(cli)?;
+    let quiet = cli.format != OutputFormat::Text;
+    let (output, duration) = run_search(search, index.as_ref(), quiet)?;
+    let findings = problems.findings(&output.results);
+
+    if quiet {
+        return print_lint_output(&findings, &output.warnings, cli.format);
+    }
+
+    print_search_warnings(&output.warnings, cli.show_errors)?;
+    print_lint_findings(&findings)?;
+
+    let results: Vec<SearchResult> = findings.into_iter().map(|x| x.result).collect();
     display_results(&results, duration, cli.max_results)
 }
 
-fn run_dialogue() -> Result<(), FsRsError> {
+fn run_replace(cli: &Cli, args: &ReplaceArgs) -> Result<(), FsRsError> {
+    let mut search_paths = cli.search_paths.clone();
+    search_paths.extend(args.paths.iter().cloned());
+
+    if search_paths.is_empty() && !cli.reads_file_list() {
+        return print_warning("No paths to replace text in given!");
+    }
+
+    let replacer = Replacer::new(&args.find, &args.replacement, args.regex, !args.ignore_case)?;
+    let search = create_search_for_replace(search_builder(cli, &search_paths)?, args);
+    let index = load_index(cli)?;
+    let (output, _) = run_search(search, index.as_ref(), false)?;
+    print_search_warnings(&output.warnings, cli.show_errors)?;
+
+    let (edits, warnings) = replacer.plan_results(&output.results);
+    print_search_warnings(&warnings, cli.show_errors)?;
+
+    if edits.is_empty() {
+        return print_message(&format!("None of the files contain '{}'.", args.find));
+    }
+
+    if cli.dry_run {
+        for edit in &edits {
+            print_file_edit(edit)?;
+        }
+
+        let count: usize = edits.iter().map(|x| x.replacement_count()).sum();
+        return print_message(&format!(
+            "Would replace '{count}' matches in '{}' files.",
+            edits.len()
+        ));
+    }
+
+    let (replaced, files) = if cli.yes {
+        for edit in &edits {
+            print_file_edit(edit)?;
+            edit.apply(&vec![true; edit.hunks().len()], args.backup.as_deref())?;
+        }
+
+        (edits.iter().map(|x| x.replacement_count()).sum(), edits.len())
+    } else {
+        ReplaceContentDialogue::review(&edits, args.backup.as_deref())?
+    };
+
+    print_message(&format!("Replaced '{replaced}' matches in '{files}' files."))
+}
+
+fn run_index(cli: &Cli, args: &IndexArgs) -> Result<(), FsRsError> {
+    let index_path = index_path(cli);
+    let mut index = if index_path.exists() {
+        FileIndex::load(&index_path)?
+    } else {
+        FileIndex::default()
+    };
+
+    let start = Instant::now();
+
+    match &args.action {
+        IndexAction::Build { paths } => {
+            if paths.is_empty() {
+                return print_warning("No paths to index given!");
+            }
+
+            print_message("Building index...")?;
+            index.build(paths)?;
+        }
+        IndexAction::Update { paths } => {
+            print_message("Updating index...")?;
+            index.update(paths)?;
+        }
+    }
+
+    index.save(&index_path)?;
+
+    print_message(&format!(
+        "Needed {}s for indexing '{}' entries into {:?}.",
+        start.elapsed().as_secs(),
+        index.entries().len(),
+        index_path
+    ))
+}
+
+fn run_watch(cli: &Cli, args: &WatchArgs) -> Result<(), FsRsError> {
+    let mut search_paths = cli.search_paths.clone();
+    search_paths.extend(args.paths.iter().cloned());
+
+    if search_paths.is_empty() {
+        return print_warning("No paths to watch given!");
+    }
+
+    let (searcher, roots) =
+        create_search_for_watch(search_builder(cli, &search_paths)?, args)
+            .build()
+            .into_parts();
+    let mut watcher = SearchWatcher::new(searcher, roots)?;
+
+    watcher.watch(|event| print_watch_event(event, args.format))
+}
+
+fn config_path(cli: &Cli) -> PathBuf {
+    cli.config.clone().unwrap_or_else(Config::default_path)
+}
+
+fn index_path(cli: &Cli) -> PathBuf {
+    cli.index_file.clone().unwrap_or_else(FileIndex::default_path)
+}
+
+fn load_index(cli: &Cli) -> Result<Option<FileIndex>, FsRsError> {
+    if !cli.use_index {
+        return Ok(None);
+    }
+
+    match FileIndex::load(&index_path(cli)) {
+        Ok(index) => Ok(Some(index)),
+        Err(why) => {
+            print_warning(&format!(
+                "Could not load the file index, searching the filesystem instead: {why}"
+            ))?;
+            Ok(None)
+        }
+    }
+}
+
+fn run_dialogue(cli: &Cli, config_path: &Path, arguments: &[OsString]) -> Result<(), FsRsError> {
     let help = "Skip to continue.";
     let search_paths =
         show_multiple_inputs_dialogue("Which paths do you want to search in?", help)?;
@@ -48,49 +651,142 @@ fn run_dialogue() -> Result<(), FsRsError> {
         return print_warning("Search aborted!");
     }
 
-    let (filenames, filename_match_option, filename_case_sensitiv) =
+    // absolute paths, so the saved profile runs from any directory
+    let search_paths = search_paths
+        .iter()
+        .map(|x| {
+            fs::canonicalize(x).map_err(|e| FsRsError::filesystem(FsOperation::Resolve, x, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let names =
         show_filter_creation_dialogue("Which filenames do you search for?", "How do you want to match the filenames?", help)?;
-    let (filecontents, filecontent_match_option, filecontent_case_sensitiv) =
+    let contents =
         show_filter_creation_dialogue("Which filecontents do you search for?", "How do you want to match the filecontents?", help)?;
 
     let results_filter = show_results_filter_dialogue()?;
 
-    let filters = create_filters_for_dialogue(
-        filenames,
-        filename_match_option,
-        filename_case_sensitiv,
-        filecontents,
-        filecontent_match_option,
-        filecontent_case_sensitiv,
-        results_filter,
-    );
-
     let max_depths = CustomType::<usize>::new("How deep do you want to search?")
         .with_default(1000)
         .prompt()?;
     let max_results = CustomType::<usize>::new("How many results do you want to see?")
         .with_default(10)
         .prompt()?;
+    let search_archives = Confirm::new("Do you want to search inside of archives?")
+        .with_default(false)
+        .prompt()?;
+    let exclude_dir = show_glob_inputs_dialogue(
+        "Which directories do you want to skip?",
+        "Names like `node_modules` or `.git`, skip to continue.",
+    )?;
+    let exclude = show_glob_inputs_dialogue(
+        "Which other entries do you want to skip?",
+        "Names like `*.log` or paths like `target/debug`, skip to continue.",
+    )?;
+
+    let profile = Profile {
+        paths: search_paths,
+        kind: Some(results_filter),
+        names,
+        contents,
+        settings: Settings {
+            depth: Some(max_depths),
+            max_results: Some(max_results),
+            search_archives: Some(search_archives),
+            exclude: Some(exclude.clone()).filter(|x| !x.is_empty()),
+            exclude_dir: Some(exclude_dir.clone()).filter(|x| !x.is_empty()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let search = SearchBuilder::new()
+        .roots(profile.paths.clone())
+        .max_depth(max_depths)
+        .search_archives(search_archives)
+        .exclude(exclude)
+        .exclude_dir(exclude_dir);
+    let search = profile.apply_filters(search);
+
+    show_save_profile_dialogue(config_path, &profile)?;
 
-    let (results, duration) = run_search(filters, &search_paths, max_depths)?;
+    let (output, duration) = run_search(search, None, false)?;
+    save_results(cli, arguments, Some(&profile), &output.results, false)?;
 
-    display_results(&results, duration, max_results)
+    print_search_warnings(&output.warnings, false)?;
+    display_results(&output.results, duration, max_results)
+}
+
+fn show_save_profile_dialogue(config_path: &Path, profile: &Profile) -> Result<(), FsRsError> {
+    let save = Confirm::new("Do you want to save this search as a profile?")
+        .with_default(false)
+        .prompt()?;
+
+    if !save {
+        return Ok(());
+    }
+
+    let name = Text::new("How do you want to name the profile?")
+        .with_validator(ValueRequiredValidator::default())
+        .prompt()?;
+
+    let config = Config::load(config_path)?;
+
+    if config.profiles.contains_key(&name) {
+        let overwrite = Confirm::new(&format!("Do you want to overwrite the profile '{name}'?"))
+            .with_default(false)
+            .prompt()?;
+
+        if !overwrite {
+            return Ok(());
+        }
+    }
+
+    Config::save_profile(config_path, &name, profile)?;
+
+    print_message(&format!(
+        "Saved the profile '{name}' to {config_path:?}, run it with `fs-rs --profile {name}`."
+    ))
 }
 
 fn show_filter_creation_dialogue(
     message: &str,
     option_message: &str,
     help: &str,
-) -> Result<(Vec<String>, Option<MatchOption>, Option<bool>), FsRsError> {
-    let search_words = show_multiple_inputs_dialogue(message, help)?;
+) -> Result<Option<WordFilter>, FsRsError> {
+    let words = show_multiple_inputs_dialogue(message, help)?;
 
-    if search_words.is_empty() {
-        return Ok((search_words, None, None));
+    if words.is_empty() {
+        return Ok(None);
     }
 
     let match_option = show_match_option_dialogue(option_message)?;
     let case_sensitiv = show_case_sensitiv_dialogue()?;
-    Ok((search_words, Some(match_option), Some(case_sensitiv)))
+    Ok(Some(WordFilter {
+        words,
+        match_option,
+        case_sensitiv,
+    }))
+}
+
+/// Asks for globs until the prompt is skipped, invalid ones are asked for again.
+fn show_glob_inputs_dialogue(message: &str, help: &str) -> Result<Vec<Glob>, FsRsError> {
+    let mut globs = Vec::new();
+
+    loop {
+        let input = Text::new(message)
+            .with_help_message(help)
+            .prompt_skippable()?;
+
+        let Some(input) = input else {
+            return Ok(globs);
+        };
+
+        match Glob::new(&input) {
+            Ok(glob) => globs.push(glob),
+            Err(why) => print_warning(&format!("Invalid pattern: {why}."))?,
+        }
+    }
 }
 
 fn show_multiple_inputs_dialogue(message: &str, help: &str) -> Result<Vec<String>, FsRsError> {
@@ -139,96 +835,283 @@ fn show_match_option_dialogue(message: &str) -> Result<MatchOption, FsRsError> {
     } 
 }
 
+fn set_running_search(token: Option<CancellationToken>) {
+    if let Ok(mut running) = RUNNING_SEARCH.lock() {
+        *running = token;
+    }
+}
+
+/// The first Ctrl-C during a search stops it, the second one or one outside of
+/// a search ends the process.
+fn set_interrupt_handler() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        let running = RUNNING_SEARCH.lock().ok().and_then(|x| x.clone());
+
+        match running {
+            Some(token) if !token.is_canceled() => token.cancel(),
+            _ => process::exit(130),
+        }
+    })
+}
+
+/// Runs the search with a live status line, the first Ctrl-C stops the search
+/// and offers the results found so far, the second one ends the process. A
+/// quiet search has no one to ask and fails when it is stopped.
 fn run_search(
-    filters: Vec<Box<dyn SearchFilter>>,
-    search_paths: &[String],
-    depth: usize,
-) -> Result<(Vec<SearchResult>, std::time::Duration), FsRsError> {
-    let searcher = FileSearcher::new(filters, depth);
+    search: SearchBuilder,
+    index: Option<&FileIndex>,
+    quiet: bool,
+) -> Result<(SearchOutput, Duration), FsRsError> {
+    let cancellation = CancellationToken::new();
+    let mut search = search.cancellation(cancellation.clone());
 
-    print_message("Searching...")?;
+    if !quiet {
+        print_message("Searching...")?;
 
-    let paths: Vec<&str> = search_paths
-        .iter()
-        .map(std::string::String::as_str)
-        .collect();
+        let last_update = Mutex::new(Instant::now());
+        search = search.progress(move |progress| {
+            let Ok(mut last_update) = last_update.lock() else {
+                return;
+            };
 
+            if last_update.elapsed() >= PROGRESS_INTERVAL {
+                *last_update = Instant::now();
+                let _ = print_progress(progress);
+            }
+        });
+    }
+
+    let search = search.build();
     let start = Instant::now();
-    let results = searcher.search_paths(&paths);
+    set_running_search(Some(cancellation.clone()));
+    let output = match index {
+        Some(i) => search.run_with_index(i),
+        None => search.run(),
+    };
+    set_running_search(None);
     let duration = start.elapsed();
 
-    print_message("Finished searching...")?;
+    if quiet {
+        if cancellation.is_canceled() {
+            return Err(FsRsError::Interrupted);
+        }
+
+        return Ok((output, duration));
+    }
+
+    clear_progress()?;
+
+    if cancellation.is_canceled() {
+        print_warning(&format!(
+            "Search interrupted after finding '{}' results.",
+            output.results.len()
+        ))?;
+
+        let keep = Confirm::new("Do you want to continue with the results found so far?")
+            .with_default(true)
+            .prompt()?;
+
+        if !keep {
+            return Err(FsRsError::Interrupted);
+        }
+    } else {
+        print_message("Finished searching...")?;
+    }
+
+    Ok((output, duration))
+}
 
-    Ok((results, duration))
+fn search_builder(cli: &Cli, paths: &[PathBuf]) -> Result<SearchBuilder, FsRsError> {
+    Ok(cli
+        .search_roots(paths)
+        .into_iter()
+        .fold(
+            SearchBuilder::new()
+                .traversal(cli.traversal_options())
+                .search_archives(cli.search_archives),
+            SearchBuilder::search_root,
+        )
+        .files(cli.read_file_list()?))
 }
 
-fn create_filters_from_cli(cli: &Cli) -> Vec<Box<dyn SearchFilter>> {
-    let mut filters: Vec<Box<dyn SearchFilter>> = Vec::new();
+fn create_search_from_cli(cli: &Cli, file_types: &FileTypes) -> Result<SearchBuilder, FsRsError> {
+    let search = search_builder(cli, &cli.search_paths)?;
 
-    if let Some(search_v) = &cli.search {
-        let search_filters: (Box<dyn SearchFilter>, Box<dyn SearchFilter>) = match search_v {
-            Search::Name(args) => {
-                let names: Vec<&str> = args.names.iter().map(std::string::String::as_str).collect();
-                let name_filter =
-                    FilenameFilter::new(&names, args.match_option, args.case_sensisitiv);
-                let result_type_filter = EntryTypeFilter::new(args.result_filter);
+    Ok(match &cli.search {
+        Some(Search::Name(args)) => {
+            with_metadata_filters(search, cli, &cli.search_paths, &args.filters, file_types)?
+                .names(&args.names, args.match_option)
+                .case_sensitiv(args.case_sensisitiv)
+                .kind(args.result_filter)
+        }
+        Some(Search::Content(args)) => {
+            with_metadata_filters(search, cli, &cli.search_paths, &args.filters, file_types)?
+                .contents(&args.names, args.match_option)
+                .case_sensitiv(args.case_sensisitiv)
+                .kind(args.result_filter)
+        }
+        Some(Search::Dupes(args)) => create_search_for_dupes(search, args),
+        Some(Search::Usage(args)) => {
+            create_search_for_usage(search, cli, &cli.search_paths, args, file_types)?
+        }
+        Some(Search::Lint(args)) => create_search_for_lint(search, args),
+        Some(Search::Watch(args)) => create_search_for_watch(search, args),
+        Some(Search::Replace(args)) => create_search_for_replace(search, args),
+        Some(
+            Search::Index(_)
+            | Search::History(_)
+            | Search::Results(_)
+            | Search::Permissions(_)
+            | Search::Types,
+        )
+        | None => search,
+    })
+}
 
-                (Box::new(name_filter), Box::new(result_type_filter))
-            }
-            Search::Content(args) => {
-                let words: Vec<&str> = args.names.iter().map(std::string::String::as_str).collect();
-                let file_content_filter =
-                    FileContentFilter::new(&words, args.match_option, args.case_sensisitiv);
-                let result_type_filter = EntryTypeFilter::new(args.result_filter);
+fn with_metadata_filters(
+    mut search: SearchBuilder,
+    cli: &Cli,
+    search_paths: &[PathBuf],
+    args: &FilterArgs,
+    file_types: &FileTypes,
+) -> Result<SearchBuilder, FsRsError> {
+    let types = args
+        .types
+        .iter()
+        .map(|x| file_types.get(x))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(FsRsError::Config)?;
 
-                (Box::new(file_content_filter), Box::new(result_type_filter))
-            }
-        };
+    search = search
+        .file_types(types)
+        .extensions(args.extensions.iter().cloned())
+        .mime_types(args.mime.iter().cloned())
+        .type_definitions(file_types.clone());
 
-        filters.push(search_filters.0);
-        filters.push(search_filters.1);
+    if let Some(path) = &args.same_file {
+        let metadata =
+            fs::metadata(path).map_err(|e| FsRsError::filesystem(FsOperation::Read, path, e))?;
+        search = search.same_file(metadata);
     }
 
-    filters
+    if let Some(links) = args.links {
+        search = search.links(links);
+    }
+
+    for pattern in &args.xattr {
+        search = search.xattr(pattern.clone());
+    }
+
+    if let Some(context) = &args.selinux_context {
+        search = search.security_context(context.clone());
+    }
+
+    search = search.acl(args.acl);
+
+    if !args.git_status.is_empty() || args.changed_since.is_some() {
+        let paths = git_paths(cli, search_paths);
+        let state = Arc::new(GitState::read(&paths, args.changed_since.as_deref())?);
+
+        if args.changed_since.is_some() {
+            search = search.changed_since(Arc::clone(&state));
+        }
+
+        search = search.git_status(state, args.git_status.clone());
+    }
+
+    if let Some(min_size) = args.min_size {
+        search = search.min_size(min_size);
+    }
+
+    if let Some(max_size) = args.max_size {
+        search = search.max_size(max_size);
+    }
+
+    Ok(with_modified_filters(search, args.older_than, args.newer_than))
 }
 
-fn create_filters_for_dialogue(
-    filenames: Vec<String>,
-    filename_match_option: Option<MatchOption>,
-    filename_case_sensitiv: Option<bool>,
-    filecontents: Vec<String>,
-    filecontent_match_option: Option<MatchOption>,
-    filecontent_case_sensitiv: Option<bool>,
-    results_filter: ResultFilter,
-) -> Vec<Box<dyn SearchFilter>> {
-    let mut filters: Vec<Box<dyn SearchFilter>> = Vec::new();
+/// The paths whose git repositories are read, the working directory when
+/// only a file list is searched.
+fn git_paths(cli: &Cli, search_paths: &[PathBuf]) -> Vec<PathBuf> {
+    let paths: Vec<PathBuf> = cli
+        .search_roots(search_paths)
+        .into_iter()
+        .map(|x| x.path)
+        .collect();
 
-    if !filenames.is_empty() {
-        let names: Vec<&str> = filenames.iter().map(std::string::String::as_str).collect();
-        let name_filter =
-            FilenameFilter::new(&names, filename_match_option.unwrap(), filename_case_sensitiv.unwrap());
-        filters.push(Box::new(name_filter));
+    if paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        paths
+    }
+}
+
+fn with_modified_filters(
+    mut search: SearchBuilder,
+    older_than: Option<u64>,
+    newer_than: Option<u64>,
+) -> SearchBuilder {
+    if let Some(older_than) = older_than {
+        search = search.older_than(days(older_than));
     }
 
-    if !filecontents.is_empty() {
-        let words: Vec<&str> = filecontents
-            .iter()
-            .map(std::string::String::as_str)
-            .collect();
-        let file_content_filter =
-            FileContentFilter::new(&words, filecontent_match_option.unwrap(), filecontent_case_sensitiv.unwrap());
-        filters.push(Box::new(file_content_filter));
+    if let Some(newer_than) = newer_than {
+        search = search.newer_than(days(newer_than));
     }
 
-    let result_type_filter = EntryTypeFilter::new(results_filter);
-    filters.push(Box::new(result_type_filter));
+    search
+}
+
+fn create_search_for_dupes(search: SearchBuilder, args: &DupesArgs) -> SearchBuilder {
+    search
+        .names(&args.names, args.match_option)
+        .case_sensitiv(args.case_sensisitiv)
+        .kind(ResultFilter::File)
+}
+
+fn create_search_for_usage(
+    search: SearchBuilder,
+    cli: &Cli,
+    search_paths: &[PathBuf],
+    args: &UsageArgs,
+    file_types: &FileTypes,
+) -> Result<SearchBuilder, FsRsError> {
+    Ok(with_metadata_filters(search, cli, search_paths, &args.filters, file_types)?
+        .names(&args.names, args.match_option)
+        .case_sensitiv(args.case_sensisitiv)
+        .kind(ResultFilter::File))
+}
+
+fn lint_checks(args: &LintArgs) -> Vec<LintCheck> {
+    if args.checks.is_empty() {
+        LintCheck::ALL.to_vec()
+    } else {
+        args.checks.clone()
+    }
+}
+
+fn create_search_for_lint(search: SearchBuilder, args: &LintArgs) -> SearchBuilder {
+    search.filter(LintFilter::new(lint_checks(args)))
+}
+
+fn create_search_for_watch(search: SearchBuilder, args: &WatchArgs) -> SearchBuilder {
+    search
+        .names(&args.names, args.match_option)
+        .contents(&args.words, args.match_option)
+        .case_sensitiv(args.case_sensisitiv)
+        .kind(args.result_filter)
+}
 
-    filters
+fn create_search_for_replace(search: SearchBuilder, args: &ReplaceArgs) -> SearchBuilder {
+    search
+        .names(&args.names, args.match_option)
+        .case_sensitiv(!args.ignore_case)
+        .kind(ResultFilter::File)
 }
 
 fn display_results(
     results: &[SearchResult],
-    duration: std::time::Duration,
+    duration: Duration,
     max_results: usize,
 ) -> Result<(), FsRsError> {
     print_message(&format!(
@@ -237,12 +1120,20 @@ fn display_results(
         results.len()
     ))?;
 
+    show_entries_menu(results, max_results)
+}
+
+fn show_entries_menu(results: &[SearchResult], max_results: usize) -> Result<(), FsRsError> {
     let options = vec![
         OPEN_ENTRY,
         REVEAL_ENTRIES,
         SHOW_DETAILS,
         COPY_ENTRIES,
         MOVE_ENTRIES,
+        RENAME_ENTRIES,
+        REPLACE_CONTENT,
+        ARCHIVE_ENTRIES,
+        CHANGE_PERMISSIONS,
         DELETE_ENTRIES,
     ];
 
@@ -257,6 +1148,10 @@ fn display_results(
                 "Show details" => ShowEntriesDialogue::show(results, max_results),
                 "Copy entries" => CopyEntriesDialogue::show(results, max_results),
                 "Move entries" => MoveEntriesDialogue::show(results, max_results),
+                "Rename entries" => RenameEntriesDialogue::show(results, max_results),
+                "Replace text in files" => ReplaceContentDialogue::show(results, max_results),
+                "Archive entries" => ArchiveEntriesDialogue::show(results, max_results),
+                "Change permissions" => ChangePermissionsDialogue::show(results, max_results),
                 "Delete entries" => DeleteEntriesDialogue::show(results, max_results),
                 _ => print_error("Invalid option entered!"),
             }?;
@@ -275,3 +1170,61 @@ fn display_results(
         };
     }
 }
+
+fn display_duplicates(
+    groups: &[DuplicateGroup],
+    duration: Duration,
+    max_results: usize,
+) -> Result<(), FsRsError> {
+    for group in groups {
+        print_duplicate_group(group)?;
+    }
+
+    let wasted: u64 = groups.iter().map(DuplicateGroup::wasted_space).sum();
+
+    print_message(&format!(
+        "Needed {}s for finding '{}' duplicate groups, wasting {}.",
+        duration.as_secs(),
+        groups.len(),
+        format_size(wasted)
+    ))?;
+
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    let redundant: Vec<SearchResult> = groups
+        .iter()
+        .flat_map(|x| x.redundant().iter().cloned())
+        .collect();
+
+    let options = vec![DELETE_DUPLICATES, MOVE_DUPLICATES, LINK_DUPLICATES];
+
+    loop {
+        let entry_action =
+            Select::new("What do you want to do?", options.clone()).prompt_skippable()?;
+
+        if let Some(action) = entry_action {
+            match action {
+                "Delete duplicates" => DeleteEntriesDialogue::show(&redundant, max_results),
+                "Move duplicates" => MoveEntriesDialogue::show(&redundant, max_results),
+                "Replace duplicates with hard links" => {
+                    HardLinkDuplicatesDialogue::show(groups, max_results)
+                }
+                _ => print_error("Invalid option entered!"),
+            }?;
+        }
+
+        match Confirm::new("Do you want to do additional things?")
+            .with_default(true)
+            .prompt()
+        {
+            Ok(res) => {
+                if !res {
+                    return Ok(());
+                }
+            }
+            Err(_) => return Ok(()),
+        };
+    }
+}
@@ -0,0 +1,718 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs::{self, File, Metadata, OpenOptions},
+    io::{self, BufReader, BufWriter, ErrorKind},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+    error::{FsOperation, FsRsError, PermissionError, SearchError},
+    history::{History, StoredOsString},
+    search::{EntryKind, SearchResult, SearchWarning},
+};
+
+/// Permission bits of the `u`, `g` and `o` classes, including setuid, setgid and sticky.
+static USER_BITS: u32 = 0o4700;
+static GROUP_BITS: u32 = 0o2070;
+static OTHER_BITS: u32 = 0o1007;
+static ALL_BITS: u32 = 0o7777;
+
+/// A `chmod` mode, octal like `755` or symbolic like `u+x,go-w`.
+///
+/// Symbolic modes without a class, e.g. `+x`, apply to all classes and do
+/// not respect the umask. Like `chmod`, directories keep their setuid and
+/// setgid bits unless they are removed explicitly, e.g. with `g-s`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModeChange {
+    Octal(u32),
+    Symbolic(Vec<ModeClause>),
+}
+
+/// One comma separated part of a symbolic mode, e.g. `go-w`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModeClause {
+    classes: u32,
+    actions: Vec<ModeAction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ModeAction {
+    operator: char,
+    /// Letters of `rwxXst`, or a single class of `ugo` to copy the bits from.
+    permissions: String,
+}
+
+/// A `chown` owner, `user`, `user:group`, `user:` for the login group of the
+/// user or `:group`. Names and numeric ids are accepted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OwnerChange {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+/// The mode and owner of an entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryPermissions {
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// The new mode and owner for the entries, either may be left unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct PermissionRule {
+    pub mode: Option<ModeChange>,
+    pub owner: Option<OwnerChange>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PermissionChange {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    pub old: EntryPermissions,
+    pub new: EntryPermissions,
+}
+
+/// The permission changes for a set of search results.
+#[derive(Clone, Debug)]
+pub struct PermissionPlan {
+    changes: Vec<PermissionChange>,
+}
+
+/// Changes applied together, kept in the [`PermissionJournal`] to undo them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PermissionRecord {
+    pub id: u64,
+    /// Seconds since the unix epoch.
+    pub created: u64,
+    pub changes: Vec<StoredPermissionChange>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoredPermissionChange {
+    pub path: StoredOsString,
+    pub kind: EntryKind,
+    pub old: EntryPermissions,
+    pub new: EntryPermissions,
+}
+
+/// The applied permission changes, one file per [`PermissionRecord`].
+#[derive(Clone, Debug)]
+pub struct PermissionJournal {
+    dir: PathBuf,
+}
+
+impl ModeChange {
+    pub fn parse(value: &str) -> Result<Self, PermissionError> {
+        let invalid = || PermissionError::Mode(value.to_string());
+
+        if !value.is_empty() && value.bytes().all(|x| x.is_ascii_digit()) {
+            if value.len() > 4 {
+                return Err(invalid());
+            }
+
+            return u32::from_str_radix(value, 8)
+                .map(ModeChange::Octal)
+                .map_err(|_| invalid());
+        }
+
+        let mut clauses = Vec::new();
+
+        for clause in value.split(',') {
+            let classes_end = clause
+                .find(|x| !matches!(x, 'u' | 'g' | 'o' | 'a'))
+                .ok_or_else(invalid)?;
+            let classes = match &clause[..classes_end] {
+                "" => ALL_BITS,
+                c => c.chars().fold(0, |bits, x| {
+                    bits | match x {
+                        'u' => USER_BITS,
+                        'g' => GROUP_BITS,
+                        'o' => OTHER_BITS,
+                        _ => ALL_BITS,
+                    }
+                }),
+            };
+
+            let mut actions: Vec<ModeAction> = Vec::new();
+            for x in clause[classes_end..].chars() {
+                match (x, actions.last_mut()) {
+                    ('+' | '-' | '=', _) => actions.push(ModeAction {
+                        operator: x,
+                        permissions: String::new(),
+                    }),
+                    ('r' | 'w' | 'x' | 'X' | 's' | 't' | 'u' | 'g' | 'o', Some(action)) => {
+                        action.permissions.push(x);
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+
+            let copies_class = |x: &ModeAction| x.permissions.contains(['u', 'g', 'o']);
+            if actions
+                .iter()
+                .any(|x| copies_class(x) && x.permissions.len() > 1)
+            {
+                return Err(invalid());
+            }
+
+            clauses.push(ModeClause { classes, actions });
+        }
+
+        Ok(ModeChange::Symbolic(clauses))
+    }
+
+    /// The new permission bits for an entry with the permission bits `mode`.
+    #[must_use]
+    pub fn apply(&self, mode: u32, is_directory: bool) -> u32 {
+        match self {
+            ModeChange::Octal(m) if is_directory => *m | (mode & 0o6000),
+            ModeChange::Octal(m) => *m,
+            ModeChange::Symbolic(clauses) => clauses
+                .iter()
+                .fold(mode & ALL_BITS, |mode, x| x.apply(mode, is_directory)),
+        }
+    }
+}
+
+impl ModeClause {
+    fn apply(&self, mut mode: u32, is_directory: bool) -> u32 {
+        for action in &self.actions {
+            let bits = action.bits(mode, is_directory) & self.classes;
+
+            mode = match action.operator {
+                '+' => mode | bits,
+                '-' => mode & !bits,
+                _ if is_directory && !action.permissions.contains('s') => {
+                    (mode & !(self.classes & !0o6000)) | bits
+                }
+                _ => (mode & !self.classes) | bits,
+            };
+        }
+
+        mode
+    }
+}
+
+impl ModeAction {
+    fn bits(&self, mode: u32, is_directory: bool) -> u32 {
+        let copy = |shift: u32| ((mode >> shift) & 0o7) * 0o111;
+
+        self.permissions.chars().fold(0, |bits, x| {
+            bits | match x {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                'X' if is_directory || mode & 0o111 != 0 => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                'u' => copy(6),
+                'g' => copy(3),
+                'o' => copy(0),
+                _ => 0,
+            }
+        })
+    }
+}
+
+impl OwnerChange {
+    pub fn parse(value: &str) -> Result<Self, PermissionError> {
+        let (user, group) = match value.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (value, None),
+        };
+
+        if user.is_empty() && group.is_none_or(str::is_empty) {
+            return Err(PermissionError::Owner(value.to_string()));
+        }
+
+        let (uid, login_gid) = match user {
+            "" => (None, None),
+            name => {
+                let (uid, gid) = lookup_user(name)
+                    .ok_or_else(|| PermissionError::UnknownUser(name.to_string()))?;
+                (Some(uid), gid)
+            }
+        };
+
+        let gid = match group {
+            None => None,
+            Some("") => login_gid,
+            Some(name) => Some(
+                lookup_group(name)
+                    .ok_or_else(|| PermissionError::UnknownGroup(name.to_string()))?,
+            ),
+        };
+
+        Ok(Self { uid, gid })
+    }
+}
+
+impl EntryPermissions {
+    #[must_use]
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        read_permissions(metadata)
+    }
+}
+
+impl PermissionRule {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.mode.is_none() && self.owner.is_none()
+    }
+
+    /// The new permissions of an entry, the mode of symlinks can not be changed.
+    #[must_use]
+    pub fn new_permissions(&self, old: EntryPermissions, kind: EntryKind) -> EntryPermissions {
+        let mode = match (&self.mode, kind) {
+            (Some(m), EntryKind::File | EntryKind::Directory) => {
+                m.apply(old.mode, kind == EntryKind::Directory)
+            }
+            _ => old.mode,
+        };
+
+        EntryPermissions {
+            mode,
+            uid: self.owner.and_then(|x| x.uid).unwrap_or(old.uid),
+            gid: self.owner.and_then(|x| x.gid).unwrap_or(old.gid),
+        }
+    }
+}
+
+impl PermissionPlan {
+    /// Collects the changes for the results, with `recursive` also for
+    /// everything below the directories. Entries that keep their permissions
+    /// and archive members are left out.
+    #[must_use]
+    pub fn collect(
+        rule: &PermissionRule,
+        results: &[SearchResult],
+        recursive: bool,
+    ) -> (Self, Vec<SearchWarning>) {
+        let mut seen = HashSet::new();
+        let mut changes = Vec::new();
+        let mut warnings = Vec::new();
+
+        let mut push = |path: &Path, kind: EntryKind, metadata: &Metadata| {
+            if !seen.insert(path.to_path_buf()) {
+                return;
+            }
+
+            let old = EntryPermissions::from_metadata(metadata);
+            let new = rule.new_permissions(old, kind);
+            if new != old {
+                changes.push(PermissionChange {
+                    path: path.to_path_buf(),
+                    kind,
+                    old,
+                    new,
+                });
+            }
+        };
+
+        for result in results.iter().filter(|x| x.archive().is_none()) {
+            match result.metadata() {
+                Some(m) => push(result.path(), result.kind(), m),
+                None => match fs::symlink_metadata(result.path()) {
+                    Ok(m) => push(result.path(), result.kind(), &m),
+                    Err(why) => warnings.push(SearchWarning {
+                        path: Some(result.path().to_path_buf()),
+                        error: SearchError::IO(why),
+                    }),
+                },
+            }
+
+            if !recursive || result.kind() != EntryKind::Directory {
+                continue;
+            }
+
+            for entry in WalkDir::new(result.path()).min_depth(1) {
+                let metadata = entry.and_then(|x| {
+                    let metadata = x.metadata()?;
+                    Ok((x, metadata))
+                });
+
+                match metadata {
+                    Ok((entry, metadata)) => {
+                        push(entry.path(), entry_kind(&metadata), &metadata);
+                    }
+                    Err(why) => warnings.push(SearchWarning {
+                        path: why.path().map(Path::to_path_buf),
+                        error: SearchError::WalkDir(why),
+                    }),
+                }
+            }
+        }
+
+        (Self { changes }, warnings)
+    }
+
+    #[must_use]
+    pub fn changes(&self) -> &[PermissionChange] {
+        &self.changes
+    }
+
+    /// Applies the changes and records them in `journal`, returns the id of the record.
+    ///
+    /// Owners are changed before modes, as a new owner clears the setuid and
+    /// setgid bits. If a change fails, the ones done so far are reverted.
+    pub fn apply(&self, journal: &PermissionJournal) -> Result<u64, FsRsError> {
+        let id = journal.add(&self.changes)?;
+
+        for (index, change) in self.changes.iter().enumerate() {
+            if let Err(why) = write_permissions(&change.path, change.kind, change.old, change.new) {
+                for done in self.changes[..index].iter().rev() {
+                    let _ = write_permissions(&done.path, done.kind, done.new, done.old);
+                }
+                let _ = journal.remove(id);
+
+                return Err(FsRsError::filesystem(
+                    FsOperation::ChangePermissions,
+                    &change.path,
+                    why,
+                ));
+            }
+        }
+
+        Ok(id)
+    }
+}
+
+impl PermissionRecord {
+    /// Restores the old permissions, newest change first. Entries whose
+    /// permissions changed again since are skipped and returned.
+    pub fn undo(&self) -> Result<Vec<PathBuf>, FsRsError> {
+        let mut skipped = Vec::new();
+
+        for change in self.changes.iter().rev() {
+            let path = PathBuf::from(&change.path.0);
+            let current = fs::symlink_metadata(&path)
+                .map_err(|e| FsRsError::filesystem(FsOperation::Read, &path, e))?;
+
+            if EntryPermissions::from_metadata(&current) != change.new {
+                skipped.push(path);
+                continue;
+            }
+
+            write_permissions(&path, change.kind, change.new, change.old)
+                .map_err(|e| FsRsError::filesystem(FsOperation::ChangePermissions, &path, e))?;
+        }
+
+        Ok(skipped)
+    }
+}
+
+impl From<&PermissionChange> for StoredPermissionChange {
+    fn from(value: &PermissionChange) -> Self {
+        Self {
+            path: StoredOsString(value.path.as_os_str().to_os_string()),
+            kind: value.kind,
+            old: value.old,
+            new: value.new,
+        }
+    }
+}
+
+impl PermissionJournal {
+    #[must_use]
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Default location of the journal, `$XDG_DATA_HOME/fs-rs/permissions`.
+    #[must_use]
+    pub fn default_dir() -> PathBuf {
+        History::default_dir().with_file_name("permissions")
+    }
+
+    /// The recorded changes, oldest first.
+    pub fn records(&self) -> Result<Vec<PermissionRecord>, FsRsError> {
+        self.ids()?.into_iter().map(|x| self.load(x)).collect()
+    }
+
+    pub fn load(&self, id: u64) -> Result<PermissionRecord, FsRsError> {
+        let path = self.record_path(id);
+        let file =
+            File::open(&path).map_err(|e| FsRsError::filesystem(FsOperation::Read, &path, e))?;
+
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| FsRsError::filesystem(FsOperation::Read, &path, io::Error::from(e)))
+    }
+
+    /// Stores the changes as a new record and returns its id.
+    ///
+    /// The id follows the highest one in the journal, a record file that
+    /// appears in the meantime is never overwritten.
+    pub fn add(&self, changes: &[PermissionChange]) -> Result<u64, FsRsError> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| FsRsError::filesystem(FsOperation::CreateDirectory, &self.dir, e))?;
+
+        let mut id = self.ids()?.last().map_or(1, |x| x + 1);
+        let (path, file) = loop {
+            let path = self.record_path(id);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(why) if why.kind() == ErrorKind::AlreadyExists => id += 1,
+                Err(why) => return Err(FsRsError::filesystem(FsOperation::Write, &path, why)),
+            }
+        };
+
+        let record = PermissionRecord {
+            id,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs()),
+            changes: changes.iter().map(StoredPermissionChange::from).collect(),
+        };
+
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer(&mut writer, &record)?;
+            writer.into_inner()?.sync_all()
+        };
+
+        write().map_err(|e| FsRsError::filesystem(FsOperation::Write, &path, e))?;
+
+        Ok(id)
+    }
+
+    pub fn remove(&self, id: u64) -> Result<(), FsRsError> {
+        let path = self.record_path(id);
+        fs::remove_file(&path).map_err(|e| FsRsError::filesystem(FsOperation::Delete, &path, e))
+    }
+
+    /// The ids of the record files, in ascending order.
+    fn ids(&self) -> Result<Vec<u64>, FsRsError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(e) => e,
+            Err(why) if why.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(why) => return Err(FsRsError::filesystem(FsOperation::Read, &self.dir, why)),
+        };
+
+        let mut ids: Vec<u64> = entries
+            .flatten()
+            .filter_map(|x| x.file_name().to_str()?.strip_suffix(".json")?.parse().ok())
+            .collect();
+        ids.sort_unstable();
+
+        Ok(ids)
+    }
+
+    fn record_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+impl Display for OwnerChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.uid, self.gid) {
+            (Some(uid), Some(gid)) => write!(f, "{}:{}", user_name(uid), group_name(gid)),
+            (Some(uid), None) => write!(f, "{}", user_name(uid)),
+            (None, Some(gid)) => write!(f, ":{}", group_name(gid)),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+fn entry_kind(metadata: &Metadata) -> EntryKind {
+    if metadata.is_dir() {
+        EntryKind::Directory
+    } else if metadata.is_symlink() {
+        EntryKind::SymLink
+    } else {
+        EntryKind::File
+    }
+}
+
+/// Name of the user with the id, the id itself if there is none.
+#[must_use]
+pub fn user_name(uid: u32) -> String {
+    #[cfg(unix)]
+    if let Ok(Some(user)) = nix::unistd::User::from_uid(uid.into()) {
+        return user.name;
+    }
+
+    uid.to_string()
+}
+
+/// Name of the group with the id, the id itself if there is none.
+#[must_use]
+pub fn group_name(gid: u32) -> String {
+    #[cfg(unix)]
+    if let Ok(Some(group)) = nix::unistd::Group::from_gid(gid.into()) {
+        return group.name;
+    }
+
+    gid.to_string()
+}
+
+/// The id and login group of a user name or numeric id.
+#[cfg(unix)]
+fn lookup_user(name: &str) -> Option<(u32, Option<u32>)> {
+    use nix::unistd::{Uid, User};
+
+    if let Ok(Some(user)) = User::from_name(name) {
+        return Some((user.uid.as_raw(), Some(user.gid.as_raw())));
+    }
+
+    let uid = name.parse().ok()?;
+    let login_gid = User::from_uid(Uid::from_raw(uid))
+        .ok()
+        .flatten()
+        .map(|x| x.gid.as_raw());
+
+    Some((uid, login_gid))
+}
+
+#[cfg(not(unix))]
+fn lookup_user(_: &str) -> Option<(u32, Option<u32>)> {
+    None
+}
+
+#[cfg(unix)]
+fn lookup_group(name: &str) -> Option<u32> {
+    use nix::unistd::Group;
+
+    match Group::from_name(name) {
+        Ok(Some(g)) => Some(g.gid.as_raw()),
+        _ => name.parse().ok(),
+    }
+}
+
+#[cfg(not(unix))]
+fn lookup_group(_: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn read_permissions(metadata: &Metadata) -> EntryPermissions {
+    use std::os::unix::fs::MetadataExt;
+
+    EntryPermissions {
+        mode: metadata.mode() & ALL_BITS,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+    }
+}
+
+#[cfg(not(unix))]
+fn read_permissions(metadata: &Metadata) -> EntryPermissions {
+    EntryPermissions {
+        mode: if metadata.permissions().readonly() {
+            0o555
+        } else {
+            0o777
+        },
+        uid: 0,
+        gid: 0,
+    }
+}
+
+#[cfg(unix)]
+fn write_permissions(
+    path: &Path,
+    kind: EntryKind,
+    from: EntryPermissions,
+    to: EntryPermissions,
+) -> io::Result<()> {
+    use std::os::unix::fs::{lchown, PermissionsExt};
+
+    let owner_changed = (from.uid, from.gid) != (to.uid, to.gid);
+    if owner_changed {
+        lchown(
+            path,
+            Some(to.uid).filter(|x| *x != from.uid),
+            Some(to.gid).filter(|x| *x != from.gid),
+        )?;
+    }
+
+    if kind != EntryKind::SymLink && (owner_changed || from.mode != to.mode) {
+        fs::set_permissions(path, fs::Permissions::from_mode(to.mode))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_permissions(
+    _: &Path,
+    _: EntryKind,
+    _: EntryPermissions,
+    _: EntryPermissions,
+) -> io::Result<()> {
+    Err(io::Error::from(ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(mode: &str, old: u32, is_directory: bool) -> u32 {
+        ModeChange::parse(mode).unwrap().apply(old, is_directory)
+    }
+
+    #[test]
+    fn mode_change_parse() {
+        assert_eq!(ModeChange::parse("755").unwrap(), ModeChange::Octal(0o755));
+        assert_eq!(ModeChange::parse("4750").unwrap(), ModeChange::Octal(0o4750));
+        assert!(ModeChange::parse("u+x,go-w").is_ok());
+        assert!(ModeChange::parse("a=rwX").is_ok());
+        assert!(ModeChange::parse("go=u").is_ok());
+
+        assert!(ModeChange::parse("").is_err());
+        assert!(ModeChange::parse("12345").is_err());
+        assert!(ModeChange::parse("789").is_err());
+        assert!(ModeChange::parse("u").is_err());
+        assert!(ModeChange::parse("u+z").is_err());
+        assert!(ModeChange::parse("x+r").is_err());
+        assert!(ModeChange::parse("g=ur").is_err());
+        assert!(ModeChange::parse("u+x,").is_err());
+    }
+
+    #[test]
+    fn mode_change_apply_octal() {
+        assert_eq!(apply("644", 0o4755, false), 0o644);
+        // directories keep their set-id bits, like with chmod
+        assert_eq!(apply("755", 0o2775, true), 0o2755);
+    }
+
+    #[test]
+    fn mode_change_apply_symbolic() {
+        assert_eq!(apply("u+x,go-w", 0o666, false), 0o744);
+        assert_eq!(apply("a=r", 0o777, false), 0o444);
+        assert_eq!(apply("go=u", 0o750, false), 0o777);
+        assert_eq!(apply("o-rwx", 0o100_644, false), 0o640);
+        assert_eq!(apply("u+s", 0o755, false), 0o4755);
+        assert_eq!(apply("+t", 0o777, true), 0o1777);
+        assert_eq!(apply("=rw", 0o2755, true), 0o2666);
+        assert_eq!(apply("=rw", 0o2755, false), 0o666);
+    }
+
+    #[test]
+    fn mode_change_apply_conditional_execute() {
+        assert_eq!(apply("a+X", 0o644, false), 0o644);
+        assert_eq!(apply("a+X", 0o744, false), 0o755);
+        assert_eq!(apply("a+X", 0o644, true), 0o755);
+    }
+
+    #[test]
+    fn journal_ids_follow_the_record_files() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-journal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("3.json"), "").unwrap();
+        fs::write(dir.join("10.json.tmp"), "").unwrap();
+
+        let journal = PermissionJournal::new(dir.clone());
+        let ids = [journal.add(&[]).unwrap(), journal.add(&[]).unwrap()];
+        let record = journal.load(5);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(ids, [4, 5]);
+        assert!(record.unwrap().changes.is_empty());
+    }
+}
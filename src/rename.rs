@@ -0,0 +1,601 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+use regex::bytes::Regex;
+
+use crate::{
+    cli::CaseConversion,
+    error::{FsOperation, FsRsError, RenameError},
+    fsutil::temporary_path,
+    index::{bytes_to_path, path_to_bytes},
+    search::SearchResult,
+};
+
+/// How the names of entries are changed by a bulk rename.
+///
+/// Without a pattern the template becomes the whole new name, with one it
+/// replaces every match in the name and may refer to the captures as `$1` or
+/// `${name}`. The template placeholders are:
+///
+/// | Placeholder        | Value                                                   |
+/// |--------------------|---------------------------------------------------------|
+/// | `{name}`           | The current name                                        |
+/// | `{stem}`           | The name without its extension                          |
+/// | `{ext}`            | The extension including the dot, empty if there is none |
+/// | `{n}`, `{n:03}`    | Position of the entry starting at 1, padded with zeros  |
+/// | `{mtime:%Y-%m-%d}` | Modification time formatted like `strftime`, `{mtime}`  |
+/// |                    | is short for `{mtime:%Y-%m-%d}`                         |
+///
+/// `{{` and `}}` are literal braces.
+#[derive(Clone, Debug)]
+pub struct RenameRule {
+    pattern: Option<Regex>,
+    template: Vec<TemplatePart>,
+    case: Option<CaseConversion>,
+}
+
+/// A single entry that is renamed, both paths are in the same directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// A reason the renames of a plan can not be applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenameConflict {
+    /// Several entries would get the same path.
+    SameTarget { to: PathBuf, from: Vec<PathBuf> },
+    /// The new path belongs to an entry that is not renamed.
+    TargetExists { from: PathBuf, to: PathBuf },
+    /// The new name is empty, `.`, `..` or contains a path separator.
+    InvalidName { from: PathBuf, name: OsString },
+    /// Entries inside of archives can not be renamed.
+    ArchiveMember(PathBuf),
+}
+
+/// The renames for a list of search results, checked for conflicts before
+/// anything is changed.
+#[derive(Clone, Debug, Default)]
+pub struct RenamePlan {
+    renames: Vec<Rename>,
+    conflicts: Vec<RenameConflict>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Name,
+    Stem,
+    Extension,
+    Counter { width: usize },
+    Modified(String),
+}
+
+impl RenameRule {
+    pub fn new(
+        pattern: Option<&str>,
+        template: &str,
+        case: Option<CaseConversion>,
+    ) -> Result<Self, RenameError> {
+        let pattern = pattern
+            .map(Regex::new)
+            .transpose()
+            .map_err(RenameError::Pattern)?;
+
+        Ok(Self {
+            pattern,
+            template: parse_template(template)?,
+            case,
+        })
+    }
+
+    /// The new name of `result`, `counter` is the value of `{n}`.
+    #[must_use]
+    pub fn new_name(&self, result: &SearchResult, counter: usize) -> OsString {
+        let name = path_to_bytes(Path::new(result.name()));
+        let modified = result.metadata().and_then(|x| x.modified().ok());
+
+        let mut expanded = Vec::new();
+        for part in &self.template {
+            let value = match part {
+                TemplatePart::Text(t) => {
+                    expanded.extend_from_slice(t.as_bytes());
+                    continue;
+                }
+                TemplatePart::Name => name.clone(),
+                TemplatePart::Stem => split_extension(&name).0.to_vec(),
+                TemplatePart::Extension => split_extension(&name).1.to_vec(),
+                TemplatePart::Counter { width } => format!("{counter:0width$}").into_bytes(),
+                TemplatePart::Modified(format) => modified
+                    .map(|x| format_time(x, format).into_bytes())
+                    .unwrap_or_default(),
+            };
+
+            // Values must not be read as references to the captures of the pattern.
+            if self.pattern.is_some() {
+                for byte in value {
+                    if byte == b'$' {
+                        expanded.push(b'$');
+                    }
+                    expanded.push(byte);
+                }
+            } else {
+                expanded.extend(value);
+            }
+        }
+
+        let renamed = match &self.pattern {
+            Some(pattern) => pattern.replace_all(&name, expanded.as_slice()).into_owned(),
+            None => expanded,
+        };
+
+        let converted = match self.case {
+            Some(case) => convert_case(&renamed, case),
+            None => renamed,
+        };
+
+        bytes_to_path(converted).into_os_string()
+    }
+}
+
+impl RenamePlan {
+    /// Plans the renames of `results` in their order, entries whose name does
+    /// not change are left out.
+    #[must_use]
+    pub fn new(rule: &RenameRule, results: &[SearchResult]) -> Self {
+        let mut plan = Self::default();
+
+        for (index, result) in results.iter().enumerate() {
+            if result.archive().is_some() {
+                plan.conflicts
+                    .push(RenameConflict::ArchiveMember(result.path().to_path_buf()));
+                continue;
+            }
+
+            let name = rule.new_name(result, index + 1);
+            if name == result.name() {
+                continue;
+            }
+
+            let from = result.path().to_path_buf();
+            if !is_valid_name(&name) {
+                plan.conflicts.push(RenameConflict::InvalidName { from, name });
+                continue;
+            }
+
+            let to = from.with_file_name(&name);
+            plan.renames.push(Rename { from, to });
+        }
+
+        plan.check_conflicts();
+        plan
+    }
+
+    #[must_use]
+    pub fn renames(&self) -> &[Rename] {
+        &self.renames
+    }
+
+    #[must_use]
+    pub fn conflicts(&self) -> &[RenameConflict] {
+        &self.conflicts
+    }
+
+    /// Renames that are part of a cycle like `a -> b, b -> a`, they are
+    /// applied through temporary names.
+    #[must_use]
+    pub fn cycles(&self) -> Vec<&Rename> {
+        let targets: HashMap<&Path, &Path> = self
+            .renames
+            .iter()
+            .map(|x| (x.from.as_path(), x.to.as_path()))
+            .collect();
+
+        self.renames
+            .iter()
+            .filter(|rename| {
+                let mut current = rename.to.as_path();
+                for _ in 0..self.renames.len() {
+                    if current == rename.from {
+                        return true;
+                    }
+                    match targets.get(current) {
+                        Some(next) => current = next,
+                        None => return false,
+                    }
+                }
+                false
+            })
+            .collect()
+    }
+
+    /// Renames all entries or none of them.
+    ///
+    /// Deeper entries are renamed first, so entries inside of renamed
+    /// directories keep their paths until they are done. Each depth is moved
+    /// to temporary names before the final ones, which resolves cycles. If a
+    /// rename fails, the ones already done are reverted, entries that can not
+    /// be renamed back are listed in [`RenameError::NotReverted`].
+    pub fn apply(&self) -> Result<(), FsRsError> {
+        if !self.conflicts.is_empty() {
+            return Err(RenameError::Conflicts(self.conflicts.len()).into());
+        }
+
+        let mut by_depth: Vec<&Rename> = self.renames.iter().collect();
+        by_depth.sort_by_key(|x| std::cmp::Reverse(x.from.components().count()));
+
+        let mut done: Vec<(PathBuf, PathBuf)> = Vec::new();
+        let result = by_depth
+            .chunk_by(|a, b| a.from.components().count() == b.from.components().count())
+            .try_for_each(|renames| Self::apply_level(renames, &mut done));
+
+        match result {
+            Err(why) => match Self::revert(&done) {
+                entries if entries.is_empty() => Err(why),
+                entries => Err(RenameError::NotReverted {
+                    source: Box::new(why),
+                    entries,
+                }
+                .into()),
+            },
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Undoes the `done` renames in reverse, returns the current and original
+    /// path of the entries that could not be renamed back.
+    fn revert(done: &[(PathBuf, PathBuf)]) -> Vec<(PathBuf, PathBuf)> {
+        let mut not_reverted: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+        for (from, to) in done.iter().rev() {
+            // An entry stuck at a later step stays there, only its original path changes.
+            if let Some(entry) = not_reverted.iter_mut().find(|(_, original)| original == to) {
+                entry.1.clone_from(from);
+                continue;
+            }
+
+            if fs::rename(to, from).is_err() {
+                not_reverted.push((to.clone(), from.clone()));
+            }
+        }
+
+        not_reverted
+    }
+
+    fn apply_level(
+        renames: &[&Rename],
+        done: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<(), FsRsError> {
+        let mut temporary = Vec::with_capacity(renames.len());
+
+        for rename in renames {
+            let temp_path = temporary_path(&rename.from, "rename");
+            move_entry(&rename.from, &temp_path, done)?;
+            temporary.push(temp_path);
+        }
+
+        for (rename, temp_path) in renames.iter().zip(temporary) {
+            // Checked again, the entry may have appeared since the plan was made.
+            if fs::symlink_metadata(&rename.to).is_ok() {
+                return Err(FsRsError::filesystem(
+                    FsOperation::Rename,
+                    &rename.from,
+                    std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!("{:?} already exists", rename.to),
+                    ),
+                ));
+            }
+
+            move_entry(&temp_path, &rename.to, done)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_conflicts(&mut self) {
+        let mut targets: HashMap<&Path, Vec<&Path>> = HashMap::new();
+        for rename in &self.renames {
+            targets
+                .entry(rename.to.as_path())
+                .or_default()
+                .push(rename.from.as_path());
+        }
+
+        let sources: HashSet<&Path> = self.renames.iter().map(|x| x.from.as_path()).collect();
+        let mut conflicts = Vec::new();
+
+        for rename in &self.renames {
+            let from = &targets[rename.to.as_path()];
+
+            if from.len() > 1 {
+                if from[0] == rename.from {
+                    conflicts.push(RenameConflict::SameTarget {
+                        to: rename.to.clone(),
+                        from: from.iter().map(|x| x.to_path_buf()).collect(),
+                    });
+                }
+            } else if !sources.contains(rename.to.as_path())
+                && fs::symlink_metadata(&rename.to).is_ok()
+                && !is_same_entry(&rename.from, &rename.to)
+            {
+                conflicts.push(RenameConflict::TargetExists {
+                    from: rename.from.clone(),
+                    to: rename.to.clone(),
+                });
+            }
+        }
+
+        self.conflicts.extend(conflicts);
+    }
+}
+
+fn move_entry(
+    from: &Path,
+    to: &Path,
+    done: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), FsRsError> {
+    fs::rename(from, to).map_err(|e| FsRsError::filesystem(FsOperation::Rename, from, e))?;
+    done.push((from.to_path_buf(), to.to_path_buf()));
+    Ok(())
+}
+
+/// A name that only differs in case on a case insensitive filesystem.
+fn is_same_entry(a: &Path, b: &Path) -> bool {
+    matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+fn is_valid_name(name: &OsStr) -> bool {
+    let bytes = name.as_encoded_bytes();
+
+    !bytes.is_empty()
+        && name != "."
+        && name != ".."
+        && !bytes.contains(&b'/')
+        && !(cfg!(windows) && bytes.contains(&b'\\'))
+}
+
+/// Splits `archive.tar.gz` into `archive.tar` and `.gz`, a leading dot does
+/// not start an extension.
+fn split_extension(name: &[u8]) -> (&[u8], &[u8]) {
+    match name.iter().rposition(|x| *x == b'.') {
+        Some(0) | None => (name, &[]),
+        Some(index) => name.split_at(index),
+    }
+}
+
+fn format_time(time: SystemTime, format: &str) -> String {
+    DateTime::<Local>::from(time).format(format).to_string()
+}
+
+/// Unicode case conversion for UTF-8, ASCII for everything else.
+fn convert_case(name: &[u8], case: CaseConversion) -> Vec<u8> {
+    let Ok(name) = std::str::from_utf8(name) else {
+        return match case {
+            CaseConversion::Lower => name.to_ascii_lowercase(),
+            CaseConversion::Upper | CaseConversion::Title => name.to_ascii_uppercase(),
+        };
+    };
+
+    match case {
+        CaseConversion::Lower => name.to_lowercase(),
+        CaseConversion::Upper => name.to_uppercase(),
+        CaseConversion::Title => {
+            let mut converted = String::with_capacity(name.len());
+            let mut word_start = true;
+
+            for c in name.chars() {
+                if word_start {
+                    converted.extend(c.to_uppercase());
+                } else {
+                    converted.extend(c.to_lowercase());
+                }
+                word_start = !c.is_alphanumeric();
+            }
+
+            converted
+        }
+    }
+    .into_bytes()
+}
+
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, RenameError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest.find('}').ok_or_else(|| {
+                    RenameError::Template(String::from("'{' without closing '}'"))
+                })?;
+
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(parse_placeholder(&rest[..end])?);
+                chars = rest[end + 1..].chars();
+            }
+            '}' => {
+                return Err(RenameError::Template(String::from(
+                    "'}' without opening '{', use '}}' for a literal brace",
+                )))
+            }
+            c => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+
+    Ok(parts)
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<TemplatePart, RenameError> {
+    let (name, argument) = match placeholder.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (placeholder, None),
+    };
+
+    match (name, argument) {
+        ("name", None) => Ok(TemplatePart::Name),
+        ("stem", None) => Ok(TemplatePart::Stem),
+        ("ext", None) => Ok(TemplatePart::Extension),
+        ("n", None) => Ok(TemplatePart::Counter { width: 0 }),
+        ("n", Some(width)) => width
+            .parse()
+            .map(|width| TemplatePart::Counter { width })
+            .map_err(|_| RenameError::Template(format!("invalid width '{width}' in '{{n:{width}}}'"))),
+        ("mtime", Some(format)) => {
+            if StrftimeItems::new(format).any(|x| matches!(x, Item::Error)) {
+                return Err(RenameError::Template(format!("invalid time format '{format}'")));
+            }
+            Ok(TemplatePart::Modified(format.to_string()))
+        }
+        ("mtime", None) => Ok(TemplatePart::Modified(String::from("%Y-%m-%d"))),
+        _ => Err(RenameError::Template(format!("unknown placeholder '{{{placeholder}}}'"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(renames: &[(&str, &str)]) -> RenamePlan {
+        let mut plan = RenamePlan {
+            renames: renames
+                .iter()
+                .map(|(from, to)| Rename {
+                    from: PathBuf::from(from),
+                    to: PathBuf::from(to),
+                })
+                .collect(),
+            conflicts: Vec::new(),
+        };
+        plan.check_conflicts();
+        plan
+    }
+
+    #[test]
+    fn parse_template_parts() {
+        assert_eq!(
+            parse_template("{stem}-{n:03}{ext}").unwrap(),
+            vec![
+                TemplatePart::Stem,
+                TemplatePart::Text(String::from("-")),
+                TemplatePart::Counter { width: 3 },
+                TemplatePart::Extension,
+            ]
+        );
+        assert_eq!(
+            parse_template("{mtime}_{name}").unwrap(),
+            vec![
+                TemplatePart::Modified(String::from("%Y-%m-%d")),
+                TemplatePart::Text(String::from("_")),
+                TemplatePart::Name,
+            ]
+        );
+        assert_eq!(
+            parse_template("{{{n}}}").unwrap(),
+            vec![
+                TemplatePart::Text(String::from("{")),
+                TemplatePart::Counter { width: 0 },
+                TemplatePart::Text(String::from("}")),
+            ]
+        );
+        assert!(parse_template("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_template_invalid() {
+        assert!(parse_template("{name").is_err());
+        assert!(parse_template("name}").is_err());
+        assert!(parse_template("{size}").is_err());
+        assert!(parse_template("{n:x}").is_err());
+        assert!(parse_template("{mtime:%Q}").is_err());
+    }
+
+    #[test]
+    fn check_conflicts_same_target() {
+        let plan = plan(&[("/nowhere/a", "/nowhere/c"), ("/nowhere/b", "/nowhere/c")]);
+
+        assert_eq!(
+            plan.conflicts(),
+            [RenameConflict::SameTarget {
+                to: PathBuf::from("/nowhere/c"),
+                from: vec![PathBuf::from("/nowhere/a"), PathBuf::from("/nowhere/b")],
+            }]
+        );
+    }
+
+    #[test]
+    fn check_conflicts_target_exists() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-rename-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a"), "").unwrap();
+        fs::write(dir.join("b"), "").unwrap();
+        fs::write(dir.join("c"), "").unwrap();
+
+        let a = dir.join("a").to_string_lossy().into_owned();
+        let b = dir.join("b").to_string_lossy().into_owned();
+        let c = dir.join("c").to_string_lossy().into_owned();
+        // `b` is renamed itself, so only `c` is in the way
+        let plan = plan(&[(&a, &b), (&b, &c)]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            plan.conflicts(),
+            [RenameConflict::TargetExists {
+                from: PathBuf::from(&b),
+                to: PathBuf::from(&c),
+            }]
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let plan = plan(&[
+            ("/nowhere/a", "/nowhere/b"),
+            ("/nowhere/b", "/nowhere/a"),
+            ("/nowhere/c", "/nowhere/d"),
+            ("/nowhere/d", "/nowhere/e"),
+        ]);
+
+        assert!(plan.conflicts().is_empty());
+        assert_eq!(plan.cycles(), [&plan.renames()[0], &plan.renames()[1]]);
+    }
+
+    #[test]
+    fn revert_reports_entries_at_their_current_path() {
+        let done = [
+            (PathBuf::from("/nowhere/a"), PathBuf::from("/nowhere/.a.tmp")),
+            (PathBuf::from("/nowhere/.a.tmp"), PathBuf::from("/nowhere/b")),
+        ];
+
+        assert_eq!(
+            RenamePlan::revert(&done),
+            [(PathBuf::from("/nowhere/b"), PathBuf::from("/nowhere/a"))]
+        );
+    }
+}
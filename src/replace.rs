@@ -0,0 +1,585 @@
+use std::{
+    fs::{self, File, Metadata, OpenOptions},
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::{
+    error::{FsOperation, FsRsError, ReplaceError, SearchError},
+    fsutil::temporary_path,
+    links::link_count,
+    search::{EntryKind, SearchResult, SearchWarning},
+};
+
+/// Lines of unchanged content shown around each change.
+pub static CONTEXT_LINES: usize = 3;
+
+/// Files with a NUL byte in this many leading bytes are treated as binary and skipped.
+static BINARY_CHECK_LENGTH: usize = 8192;
+
+/// Replaces text or regex matches in file contents.
+#[derive(Clone, Debug)]
+pub struct Replacer {
+    pattern: Regex,
+    replacement: Vec<u8>,
+    /// `$1` and `${name}` in the replacement refer to captures of a regex.
+    expand: bool,
+}
+
+/// The replacements in a single file, grouped into hunks that can be
+/// accepted one by one.
+#[derive(Clone, Debug)]
+pub struct FileEdit {
+    path: PathBuf,
+    content: Vec<u8>,
+    modified: Option<SystemTime>,
+    replacements: Vec<Replacement>,
+    hunks: Vec<Hunk>,
+}
+
+/// Neighbouring replacements of a file shown as one unified diff hunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    /// First line of the hunk in the original file, starting at 1.
+    pub old_start: usize,
+    pub old_count: usize,
+    /// First line of the hunk if all hunks before it are applied, starting at 1.
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<DiffLine>,
+    replacements: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffLine {
+    Context(Vec<u8>),
+    Removed(Vec<u8>),
+    Added(Vec<u8>),
+}
+
+#[derive(Clone, Debug)]
+struct Replacement {
+    range: Range<usize>,
+    content: Vec<u8>,
+}
+
+impl Replacer {
+    /// Replaces `find` literally, or as a regex whose captures `replacement`
+    /// can refer to as `$1` or `${name}`.
+    pub fn new(
+        find: &str,
+        replacement: &str,
+        regex: bool,
+        case_sensitiv: bool,
+    ) -> Result<Self, ReplaceError> {
+        let pattern = if regex {
+            find.to_string()
+        } else {
+            regex::escape(find)
+        };
+
+        let pattern = RegexBuilder::new(&pattern)
+            .case_insensitive(!case_sensitiv)
+            .multi_line(true)
+            .build()
+            .map_err(ReplaceError::Pattern)?;
+
+        Ok(Self {
+            pattern,
+            replacement: replacement.as_bytes().to_vec(),
+            expand: regex,
+        })
+    }
+
+    /// Plans the replacements in the files of `results`, entries inside of
+    /// archives are skipped. Files that could not be read are returned as warnings.
+    #[must_use]
+    pub fn plan_results(&self, results: &[SearchResult]) -> (Vec<FileEdit>, Vec<SearchWarning>) {
+        let mut edits = Vec::new();
+        let mut warnings = Vec::new();
+
+        for result in results {
+            if result.kind() != EntryKind::File || result.archive().is_some() {
+                continue;
+            }
+
+            match self.plan(result.path()) {
+                Ok(Some(edit)) => edits.push(edit),
+                Ok(None) => {}
+                Err(why) => warnings.push(SearchWarning {
+                    path: Some(result.path().to_path_buf()),
+                    error: SearchError::IO(why),
+                }),
+            }
+        }
+
+        (edits, warnings)
+    }
+
+    /// Reads the file at `path` and plans its replacements, `None` if nothing
+    /// matches or the file is binary.
+    pub fn plan(&self, path: &Path) -> io::Result<Option<FileEdit>> {
+        let modified = fs::metadata(path)?.modified().ok();
+        let content = fs::read(path)?;
+
+        if content[..content.len().min(BINARY_CHECK_LENGTH)].contains(&0) {
+            return Ok(None);
+        }
+
+        let mut replacements = Vec::new();
+        for captures in self.pattern.captures_iter(&content) {
+            let found = captures.get(0).expect("group 0 is always present");
+
+            let mut replaced = Vec::new();
+            if self.expand {
+                captures.expand(&self.replacement, &mut replaced);
+            } else {
+                replaced.clone_from(&self.replacement);
+            }
+
+            if replaced != found.as_bytes() {
+                replacements.push(Replacement {
+                    range: found.range(),
+                    content: replaced,
+                });
+            }
+        }
+
+        if replacements.is_empty() {
+            return Ok(None);
+        }
+
+        let hunks = create_hunks(&content, &replacements);
+
+        Ok(Some(FileEdit {
+            path: path.to_path_buf(),
+            content,
+            modified,
+            replacements,
+            hunks,
+        }))
+    }
+}
+
+impl FileEdit {
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    #[must_use]
+    pub fn replacement_count(&self) -> usize {
+        self.replacements.len()
+    }
+
+    /// Number of replacements in the hunks that are accepted.
+    #[must_use]
+    pub fn accepted_count(&self, accepted: &[bool]) -> usize {
+        self.hunks
+            .iter()
+            .zip(accepted)
+            .filter(|(_, accepted)| **accepted)
+            .map(|(hunk, _)| hunk.replacements.len())
+            .sum()
+    }
+
+    /// Writes the file with the hunks at the indices where `accepted` is true.
+    ///
+    /// Symlinks are followed, the file they point to is written. The new content
+    /// goes to a temporary file with the mode, owner and extended attributes of
+    /// the original, which then replaces it. Files with several hard links, and
+    /// files whose owner or attributes can not be given to a new file, are
+    /// rewritten in place instead. With a `backup` suffix the original is kept
+    /// next to it, e.g. `notes.txt.bak`. Fails without changing anything if the
+    /// file was modified since it was read.
+    pub fn apply(&self, accepted: &[bool], backup: Option<&str>) -> Result<(), FsRsError> {
+        if self.hunks.iter().zip(accepted).all(|(_, x)| !*x) {
+            return Ok(());
+        }
+
+        let mut content = Vec::with_capacity(self.content.len());
+        let mut position = 0;
+
+        for (hunk, _) in self.hunks.iter().zip(accepted).filter(|(_, x)| **x) {
+            for replacement in &self.replacements[hunk.replacements.clone()] {
+                content.extend_from_slice(&self.content[position..replacement.range.start]);
+                content.extend_from_slice(&replacement.content);
+                position = replacement.range.end;
+            }
+        }
+
+        content.extend_from_slice(&self.content[position..]);
+
+        let path = fs::canonicalize(&self.path)
+            .map_err(|e| FsRsError::filesystem(FsOperation::Resolve, &self.path, e))?;
+        let metadata =
+            fs::metadata(&path).map_err(|e| FsRsError::filesystem(FsOperation::Read, &path, e))?;
+        if metadata.modified().ok() != self.modified || metadata.len() != self.content.len() as u64 {
+            return Err(FsRsError::filesystem(
+                FsOperation::Write,
+                &self.path,
+                io::Error::other("the file was modified since it was read"),
+            ));
+        }
+
+        if let Some(suffix) = backup {
+            let mut backup_path = self.path.as_os_str().to_os_string();
+            backup_path.push(suffix);
+
+            fs::copy(&path, &backup_path)
+                .map_err(|e| FsRsError::filesystem(FsOperation::Copy, &backup_path, e))?;
+        }
+
+        let write_error = |e| FsRsError::filesystem(FsOperation::Write, &path, e);
+
+        // A new file would split the hard links, the others would keep the old content.
+        if link_count(&metadata) > 1 {
+            return write_in_place(&path, &content).map_err(write_error);
+        }
+
+        let temp_path = temporary_path(&path, "replace");
+        let file = match create_like(&temp_path, &path, &metadata) {
+            Ok(file) => file,
+            Err(why) => {
+                let _ = fs::remove_file(&temp_path);
+
+                // e.g. the file belongs to another user or the directory is read-only
+                if why.kind() == io::ErrorKind::PermissionDenied {
+                    return write_in_place(&path, &content).map_err(write_error);
+                }
+                return Err(write_error(why));
+            }
+        };
+
+        let write = |mut file: File| -> io::Result<()> {
+            file.write_all(&content)?;
+            file.sync_all()?;
+            fs::rename(&temp_path, &path)
+        };
+
+        write(file).map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            write_error(e)
+        })
+    }
+}
+
+/// Writes `content` into the existing file, which keeps its inode and links.
+fn write_in_place(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// Creates the file at `path` with the mode, owner and extended attributes of
+/// `original`, before anything is written to it.
+#[cfg(unix)]
+fn create_like(path: &Path, original: &Path, metadata: &Metadata) -> io::Result<File> {
+    use std::os::unix::fs::{fchown, MetadataExt, OpenOptionsExt};
+    use xattr::FileExt;
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(metadata.mode() & 0o777)
+        .open(path)?;
+
+    fchown(&file, Some(metadata.uid()), Some(metadata.gid()))?;
+    // again after the owner, which clears the set-id bits, and without the umask
+    file.set_permissions(metadata.permissions())?;
+
+    for name in xattr::list(original).into_iter().flatten() {
+        if let Some(value) = xattr::get(original, &name)? {
+            file.set_xattr(&name, &value)?;
+        }
+    }
+
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn create_like(path: &Path, _: &Path, metadata: &Metadata) -> io::Result<File> {
+    let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.set_permissions(metadata.permissions())?;
+
+    Ok(file)
+}
+
+/// Groups the replacements into hunks, replacements whose context would
+/// overlap end up in the same hunk.
+fn create_hunks(content: &[u8], replacements: &[Replacement]) -> Vec<Hunk> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(
+            content
+                .iter()
+                .enumerate()
+                .filter(|(_, x)| **x == b'\n')
+                .map(|(i, _)| i + 1)
+                .filter(|x| *x < content.len()),
+        )
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|x| *x <= offset) - 1;
+    let affected_lines = |replacement: &Replacement| {
+        let range = &replacement.range;
+        (
+            line_of(range.start),
+            line_of(range.end.saturating_sub(1).max(range.start)),
+        )
+    };
+
+    let mut groups: Vec<(Range<usize>, usize, usize)> = Vec::new();
+    for (index, replacement) in replacements.iter().enumerate() {
+        let (first, last) = affected_lines(replacement);
+
+        match groups.last_mut() {
+            Some((indices, _, group_last)) if first <= *group_last + 2 * CONTEXT_LINES + 1 => {
+                indices.end = index + 1;
+                *group_last = (*group_last).max(last);
+            }
+            _ => groups.push((index..index + 1, first, last)),
+        }
+    }
+
+    let line_end = |line: usize| line_starts.get(line + 1).copied().unwrap_or(content.len());
+    let mut line_delta: isize = 0;
+
+    groups
+        .into_iter()
+        .map(|(indices, first, last)| {
+            let context_start = first.saturating_sub(CONTEXT_LINES);
+            let context_end = (last + CONTEXT_LINES).min(line_starts.len() - 1);
+
+            let changed_start = line_starts[first];
+            let changed_end = line_end(last);
+
+            let mut new_content = Vec::new();
+            let mut position = changed_start;
+            for replacement in &replacements[indices.clone()] {
+                new_content.extend_from_slice(&content[position..replacement.range.start]);
+                new_content.extend_from_slice(&replacement.content);
+                position = replacement.range.end;
+            }
+            new_content.extend_from_slice(&content[position..changed_end]);
+
+            let context = |from: usize, to: usize| {
+                split_lines(&content[line_starts[from]..line_end(to)])
+                    .into_iter()
+                    .map(DiffLine::Context)
+            };
+            let removed = split_lines(&content[changed_start..changed_end]);
+            let added = split_lines(&new_content);
+
+            let before = first - context_start;
+            let after = context_end - last;
+            let old_count = before + removed.len() + after;
+            let new_count = before + added.len() + after;
+
+            let mut lines: Vec<DiffLine> = Vec::with_capacity(old_count + added.len());
+            if before > 0 {
+                lines.extend(context(context_start, first - 1));
+            }
+            lines.extend(diff_lines(removed, added));
+            if after > 0 {
+                lines.extend(context(last + 1, context_end));
+            }
+
+            let old_start = context_start + 1;
+            let new_start = old_start.saturating_add_signed(line_delta);
+            line_delta += new_count as isize - old_count as isize;
+
+            Hunk {
+                old_start,
+                old_count,
+                new_start,
+                new_count,
+                lines,
+                replacements: indices,
+            }
+        })
+        .collect()
+}
+
+/// Lines that did not change stay context if no lines were added or removed.
+fn diff_lines(removed: Vec<Vec<u8>>, added: Vec<Vec<u8>>) -> Vec<DiffLine> {
+    if removed.len() != added.len() {
+        return removed
+            .into_iter()
+            .map(DiffLine::Removed)
+            .chain(added.into_iter().map(DiffLine::Added))
+            .collect();
+    }
+
+    let mut lines = Vec::new();
+    let mut changed: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+
+    for (old, new) in removed.into_iter().zip(added) {
+        if old == new {
+            flush_changes(&mut changed, &mut lines);
+            lines.push(DiffLine::Context(old));
+        } else {
+            changed.push((old, new));
+        }
+    }
+    flush_changes(&mut changed, &mut lines);
+
+    lines
+}
+
+fn flush_changes(changed: &mut Vec<(Vec<u8>, Vec<u8>)>, lines: &mut Vec<DiffLine>) {
+    let (removed, added): (Vec<_>, Vec<_>) = changed.drain(..).unzip();
+    lines.extend(removed.into_iter().map(DiffLine::Removed));
+    lines.extend(added.into_iter().map(DiffLine::Added));
+}
+
+/// Splits into lines without their line breaks.
+fn split_lines(content: &[u8]) -> Vec<Vec<u8>> {
+    if content.is_empty() {
+        return Vec::new();
+    }
+
+    let content = content.strip_suffix(b"\n").unwrap_or(content);
+
+    content
+        .split(|x| *x == b'\n')
+        .map(|x| x.strip_suffix(b"\r").unwrap_or(x).to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacement(content: &[u8], find: &str, replaced: &str) -> Replacement {
+        let start = content
+            .windows(find.len())
+            .position(|x| x == find.as_bytes())
+            .unwrap();
+
+        Replacement {
+            range: start..start + find.len(),
+            content: replaced.as_bytes().to_vec(),
+        }
+    }
+
+    fn lines(text: &str) -> Vec<Vec<u8>> {
+        text.lines().map(|x| x.as_bytes().to_vec()).collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fs-rs-replace-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn split_lines_without_line_breaks() {
+        assert!(split_lines(b"").is_empty());
+        assert_eq!(split_lines(b"\n"), lines("\n"));
+        assert_eq!(split_lines(b"a\nb"), lines("a\nb"));
+        assert_eq!(split_lines(b"a\r\nb\r\n"), lines("a\nb"));
+        assert_eq!(split_lines(b"a\n\nb\n"), lines("a\n\nb"));
+    }
+
+    #[test]
+    fn create_hunks_with_context() {
+        let content = b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n";
+        let replacements = [
+            replacement(content, "2\n", "two\n"),
+            replacement(content, "13", "13\n13.5"),
+        ];
+
+        let hunks = create_hunks(content, &replacements);
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(
+            (hunks[0].old_start, hunks[0].old_count, hunks[0].new_start, hunks[0].new_count),
+            (1, 5, 1, 5)
+        );
+        assert_eq!(
+            hunks[0].lines,
+            [
+                DiffLine::Context(b"1".to_vec()),
+                DiffLine::Removed(b"2".to_vec()),
+                DiffLine::Added(b"two".to_vec()),
+                DiffLine::Context(b"3".to_vec()),
+                DiffLine::Context(b"4".to_vec()),
+                DiffLine::Context(b"5".to_vec()),
+            ]
+        );
+        assert_eq!(
+            (hunks[1].old_start, hunks[1].old_count, hunks[1].new_start, hunks[1].new_count),
+            (10, 6, 10, 7)
+        );
+        assert_eq!(hunks[1].replacements, 1..2);
+    }
+
+    #[test]
+    fn create_hunks_merges_overlapping_context() {
+        let content = b"a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let replacements = [replacement(content, "b", "B"), replacement(content, "h", "H")];
+
+        let hunks = create_hunks(content, &replacements);
+
+        assert_eq!(hunks.len(), 1);
+        assert_eq!((hunks[0].old_start, hunks[0].old_count), (1, 9));
+        assert_eq!(hunks[0].replacements, 0..2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn apply_keeps_symlinks() {
+        let dir = temp_dir("symlink");
+        fs::write(dir.join("file"), "old text\n").unwrap();
+        std::os::unix::fs::symlink("file", dir.join("link")).unwrap();
+
+        let replacer = Replacer::new("old", "new", false, true).unwrap();
+        let edit = replacer.plan(&dir.join("link")).unwrap().unwrap();
+        edit.apply(&[true], None).unwrap();
+
+        let is_symlink = fs::symlink_metadata(dir.join("link")).unwrap().is_symlink();
+        let content = fs::read_to_string(dir.join("file")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(is_symlink);
+        assert_eq!(content, "new text\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn apply_keeps_hard_links_and_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("hardlink");
+        fs::write(dir.join("file"), "old text\n").unwrap();
+        fs::set_permissions(dir.join("file"), fs::Permissions::from_mode(0o640)).unwrap();
+        fs::hard_link(dir.join("file"), dir.join("link")).unwrap();
+        fs::write(dir.join("single"), "old text\n").unwrap();
+        fs::set_permissions(dir.join("single"), fs::Permissions::from_mode(0o604)).unwrap();
+
+        let replacer = Replacer::new("old", "new", false, true).unwrap();
+        for name in ["file", "single"] {
+            let edit = replacer.plan(&dir.join(name)).unwrap().unwrap();
+            edit.apply(&[true], None).unwrap();
+        }
+
+        let linked = fs::read_to_string(dir.join("link")).unwrap();
+        let mode = fs::metadata(dir.join("link")).unwrap().permissions().mode() & 0o777;
+        let single = fs::read_to_string(dir.join("single")).unwrap();
+        let single_mode = fs::metadata(dir.join("single")).unwrap().permissions().mode() & 0o777;
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(linked, "new text\n");
+        assert_eq!(mode, 0o640);
+        assert_eq!(single, "new text\n");
+        assert_eq!(single_mode, 0o604);
+    }
+}
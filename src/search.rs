@@ -1,161 +1,1067 @@
-use std::{ffi::OsString, fmt::Display, fs::Metadata};
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    fs::{self, FileType, Metadata},
+    io::{self, Read},
+    ops::ControlFlow,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+};
 
+use serde::{Deserialize, Serialize};
 use walkdir::{DirEntry, WalkDir};
 
-use crate::filter::SearchFilter;
+use crate::{
+    archive::{self, ArchiveEntry, ArchiveKind},
+    error::SearchError,
+    exclude::Exclusions,
+    filter::SearchFilter,
+    index::{bytes_to_path, FileIndex, IndexEntry},
+    links::{self, FileId},
+};
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SearchMode {
     TopLevelOnly,
     Recursive,
 }
 
+/// How the filesystem below a search root is traversed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraversalOptions {
+    pub mode: SearchMode,
+    pub min_depth: usize,
+    pub max_depth: usize,
+    /// Follows symbolic links, links back to an ancestor are reported as warnings.
+    pub follow_links: bool,
+    /// Does not descend into directories on other filesystems, like `find -xdev`.
+    pub same_file_system: bool,
+    /// Visits the entries of each directory sorted by name.
+    pub sort_by_name: bool,
+    /// Entries that are not searched, excluded directories are not walked into.
+    pub exclude: Exclusions,
+}
+
+/// Options of a [`FileSearcher`] that are independent of its filters.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Traversal options for paths without options of their own.
+    pub traversal: TraversalOptions,
+    /// Treats the members of archives and compressed files as virtual entries.
+    pub search_archives: bool,
+}
+
+/// A path to search together with the options for traversing it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchRoot {
+    pub path: PathBuf,
+    pub options: TraversalOptions,
+}
+
+/// An entry that could not be searched, e.g. a directory without read permission.
+#[derive(Debug)]
+pub struct SearchWarning {
+    pub path: Option<PathBuf>,
+    pub error: SearchError,
+}
+
+/// The results of a search together with the entries that could not be searched.
+#[derive(Debug, Default)]
+pub struct SearchOutput {
+    pub results: Vec<SearchResult>,
+    pub warnings: Vec<SearchWarning>,
+}
+
+/// A result or warning reported while searching, see [`FileSearcher::search_roots_with`].
+#[derive(Debug)]
+pub enum SearchEvent {
+    Result(Box<SearchResult>),
+    Warning(SearchWarning),
+}
+
+/// Stops a running search once canceled, clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    canceled: Arc<AtomicBool>,
+}
+
+/// How far a running search got, reported to the progress callback of a [`FileSearcher`].
+#[derive(Clone, Debug, Default)]
+pub struct SearchProgress {
+    pub directories_visited: u64,
+    pub entries_checked: u64,
+    /// Bytes of file contents read by the filters, e.g. [`crate::filter::FileContentFilter`].
+    pub bytes_read: u64,
+    pub current_path: PathBuf,
+}
+
+/// An entry on the filesystem as the filters see it, found by walking a
+/// directory or read from a list of paths.
+#[derive(Debug)]
+pub struct FileEntry {
+    path: PathBuf,
+    /// The search path the entry was found below, shared by all its entries.
+    root: Option<Arc<Path>>,
+    file_type: FileType,
+    depth: usize,
+    follow_link: bool,
+    metadata: OnceLock<Option<Metadata>>,
+}
+
+pub type ProgressCallback = Box<dyn Fn(&SearchProgress) + Send + Sync>;
+
+/// The progress of a single search, the byte counters of the filters keep
+/// growing over several searches.
+struct ProgressTracker {
+    progress: SearchProgress,
+    initial_bytes_read: u64,
+}
+
 pub struct FileSearcher {
     filters: Vec<Box<dyn SearchFilter>>,
-    max_depth: usize,
+    options: SearchOptions,
+    cancellation: Option<CancellationToken>,
+    progress: Option<ProgressCallback>,
+}
+
+impl Default for TraversalOptions {
+    fn default() -> Self {
+        Self {
+            mode: SearchMode::Recursive,
+            min_depth: 0,
+            max_depth: usize::MAX,
+            follow_links: false,
+            same_file_system: false,
+            sort_by_name: false,
+            exclude: Exclusions::default(),
+        }
+    }
+}
+
+impl TraversalOptions {
+    /// The maximum depth, limited to the direct children of the root for [`SearchMode::TopLevelOnly`].
+    #[must_use]
+    pub fn effective_max_depth(&self) -> usize {
+        match self.mode {
+            SearchMode::TopLevelOnly => self.max_depth.min(1),
+            SearchMode::Recursive => self.max_depth,
+        }
+    }
+
+    #[must_use]
+    pub fn contains_depth(&self, depth: usize) -> bool {
+        depth >= self.min_depth && depth <= self.effective_max_depth()
+    }
+
+    /// Walks `start`, a directory `depth` levels below `root` or the root
+    /// itself, with the depth limits measured from the root. Excluded entries
+    /// are pruned with everything below them.
+    fn walk_dir<'a>(
+        &'a self,
+        root: &'a Path,
+        start: &'a Path,
+        depth: usize,
+    ) -> impl Iterator<Item = walkdir::Result<DirEntry>> + 'a {
+        let walk_dir = WalkDir::new(start)
+            .min_depth(self.min_depth.saturating_sub(depth))
+            .max_depth(self.effective_max_depth().saturating_sub(depth))
+            .follow_links(self.follow_links)
+            .same_file_system(self.same_file_system);
+
+        let walk_dir = if self.sort_by_name {
+            walk_dir.sort_by_file_name()
+        } else {
+            walk_dir
+        };
+
+        walk_dir.into_iter().filter_entry(move |x| {
+            !self
+                .exclude
+                .excludes(root, x.path(), x.file_type().is_dir())
+        })
+    }
+}
+
+impl FileEntry {
+    /// Reads the entry at `path` without following a symbolic link, its depth is 0.
+    pub fn from_path(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let metadata = fs::symlink_metadata(&path)?;
+
+        Ok(Self {
+            path,
+            root: None,
+            file_type: metadata.file_type(),
+            depth: 0,
+            follow_link: false,
+            metadata: OnceLock::from(Some(metadata)),
+        })
+    }
+
+    pub(crate) fn from_dir_entry(dir_entry: &DirEntry, root: &Arc<Path>) -> Self {
+        Self {
+            path: dir_entry.path().to_path_buf(),
+            root: Some(Arc::clone(root)),
+            file_type: dir_entry.file_type(),
+            depth: dir_entry.depth(),
+            // walkdir reports the type of the target for links it followed.
+            follow_link: dir_entry.path_is_symlink() && !dir_entry.file_type().is_symlink(),
+            metadata: OnceLock::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The last component of the path, or the whole path if it has none, e.g. `/`.
+    #[must_use]
+    pub fn file_name(&self) -> &OsStr {
+        self.path
+            .file_name()
+            .unwrap_or_else(|| self.path.as_os_str())
+    }
+
+    #[must_use]
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> EntryKind {
+        if self.file_type.is_file() {
+            EntryKind::File
+        } else if self.file_type.is_dir() {
+            EntryKind::Directory
+        } else {
+            EntryKind::SymLink
+        }
+    }
+
+    /// Depth below the search path it was found in, 0 for the path itself.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The search path the entry was found below, `None` for entries that are
+    /// checked on their own, like those of `--files-from`.
+    #[must_use]
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Metadata of the entry, read once on first use, `None` if it could not be read.
+    #[must_use]
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata
+            .get_or_init(|| {
+                if self.follow_link {
+                    fs::metadata(&self.path).ok()
+                } else {
+                    fs::symlink_metadata(&self.path).ok()
+                }
+            })
+            .as_ref()
+    }
+}
+
+impl SearchOutput {
+    pub(crate) fn push(&mut self, event: SearchEvent) -> ControlFlow<()> {
+        match event {
+            SearchEvent::Result(r) => self.results.push(*r),
+            SearchEvent::Warning(w) => self.warnings.push(w),
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+impl CancellationToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.canceled.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_canceled(&self) -> bool {
+        self.canceled.load(Ordering::Relaxed)
+    }
+}
+
+impl SearchRoot {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, options: TraversalOptions) -> Self {
+        Self {
+            path: path.into(),
+            options,
+        }
+    }
 }
 
 impl FileSearcher {
     #[must_use]
     pub fn new(filters: Vec<Box<dyn SearchFilter>>, max_depth: usize) -> Self {
-        Self { filters, max_depth }
+        Self::with_options(
+            filters,
+            SearchOptions {
+                traversal: TraversalOptions {
+                    max_depth,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
     }
 
     #[must_use]
-    pub fn search_paths(&self, paths: &[&str]) -> Vec<SearchResult> {
-        paths.iter().flat_map(|x| self.search_path(x)).collect()
+    pub fn with_options(filters: Vec<Box<dyn SearchFilter>>, options: SearchOptions) -> Self {
+        Self {
+            filters,
+            options,
+            cancellation: None,
+            progress: None,
+        }
     }
 
-    fn search_path(&self, path: &str) -> Vec<SearchResult> {
-        WalkDir::new(path)
-            .max_depth(self.max_depth)
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|e| self.check_filters(e))
-            .map(|x| map_filetype(&x))
-            .collect()
+    /// Stops the search once `token` is canceled, the results found so far are kept.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// The cancellation token of the searcher, a new one is set if it has none.
+    #[cfg(feature = "async")]
+    pub(crate) fn cancellation_token(&mut self) -> CancellationToken {
+        self.cancellation
+            .get_or_insert_with(CancellationToken::new)
+            .clone()
+    }
+
+    /// Calls `progress` for every checked entry, it should return quickly.
+    #[must_use]
+    pub fn with_progress(mut self, progress: impl Fn(&SearchProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Whether the cancellation token of the searcher was canceled.
+    #[must_use]
+    pub fn is_canceled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_canceled)
+    }
+
+    /// Sets the traversal options used for paths without options of their own.
+    #[must_use]
+    pub fn with_traversal(mut self, options: TraversalOptions) -> Self {
+        self.options.traversal = options;
+        self
+    }
+
+    /// Treats the members of archives and compressed files as virtual entries.
+    #[must_use]
+    pub fn with_archives(mut self, search_archives: bool) -> Self {
+        self.options.search_archives = search_archives;
+        self
     }
 
-    fn check_filters(&self, dir_entry: &DirEntry) -> bool {
-        if self.filters.is_empty() {
-            return true;
+    #[must_use]
+    pub fn search_paths<P: AsRef<Path>>(&self, paths: &[P]) -> SearchOutput {
+        let mut output = SearchOutput::default();
+
+        let mut progress = self.start_progress();
+
+        for path in paths {
+            let flow = self.search_path(
+                path.as_ref(),
+                path.as_ref(),
+                &self.options.traversal,
+                &mut progress,
+                &mut |x| output.push(x),
+            );
+
+            if flow.is_break() {
+                break;
+            }
         }
 
-        let mut filter_result = true;
+        output
+    }
 
-        for filter in &self.filters {
-            if !filter.check_filter(dir_entry) {
-                filter_result = false;
+    /// Searches every root with its own traversal options.
+    #[must_use]
+    pub fn search_roots(&self, roots: &[SearchRoot]) -> SearchOutput {
+        let mut output = SearchOutput::default();
+        self.search_roots_with(roots, |x| output.push(x));
+        output
+    }
+
+    /// Searches every root and hands each result and warning to `callback` as
+    /// soon as it is found, the search stops once the callback breaks.
+    pub fn search_roots_with(
+        &self,
+        roots: &[SearchRoot],
+        mut callback: impl FnMut(SearchEvent) -> ControlFlow<()>,
+    ) {
+        let mut progress = self.start_progress();
+
+        for root in roots {
+            if self
+                .search_path(&root.path, &root.path, &root.options, &mut progress, &mut callback)
+                .is_break()
+            {
+                return;
             }
         }
+    }
 
-        filter_result
+    /// Searches `path`, a directory below the root, like the root would be
+    /// searched, e.g. a directory created after the root was searched.
+    #[must_use]
+    pub fn search_below(&self, root: &SearchRoot, path: &Path) -> SearchOutput {
+        let mut output = SearchOutput::default();
+        let mut progress = self.start_progress();
+        // the output holds what was found until a cancellation either way
+        let _ = self.search_path(
+            &root.path,
+            path,
+            &root.options,
+            &mut progress,
+            &mut |x| output.push(x),
+        );
+        output
     }
-}
 
-fn map_filetype(dir_entry: &DirEntry) -> SearchResult {
-    if dir_entry.file_type().is_file() {
-        return SearchResult::File {
-            path: dir_entry.path().as_os_str().to_os_string(),
-            name: dir_entry.file_name().to_os_string(),
-            metadata: dir_entry.metadata().ok(),
+    /// Checks the entries at `paths` without walking into directories, e.g. a
+    /// list read with [`read_file_list`].
+    #[must_use]
+    pub fn search_files<P: AsRef<Path>>(&self, paths: &[P]) -> SearchOutput {
+        let mut output = SearchOutput::default();
+        self.search_files_with(paths, |x| output.push(x));
+        output
+    }
+
+    /// Checks the entries at `paths` and hands each result and warning to
+    /// `callback`, see [`FileSearcher::search_roots_with`].
+    pub fn search_files_with<P: AsRef<Path>>(
+        &self,
+        paths: &[P],
+        mut callback: impl FnMut(SearchEvent) -> ControlFlow<()>,
+    ) {
+        let mut progress = self.start_progress();
+
+        for path in paths {
+            let path = path.as_ref();
+
+            if self.is_canceled() {
+                return;
+            }
+
+            let flow = match FileEntry::from_path(path) {
+                Ok(entry)
+                    if self.options.traversal.exclude.excludes_below(
+                        Path::new(""),
+                        path,
+                        entry.file_type().is_dir(),
+                    ) =>
+                {
+                    ControlFlow::Continue(())
+                }
+                Ok(entry) => self.search_entry(&entry, None, &mut progress, &mut callback),
+                Err(why) => callback(SearchEvent::Warning(SearchWarning {
+                    path: Some(path.to_path_buf()),
+                    error: SearchError::IO(why),
+                })),
+            };
+
+            if flow.is_break() {
+                return;
+            }
+        }
+    }
+
+    /// Answers the search from `index`, roots it does not cover are searched on disk.
+    #[must_use]
+    pub fn search_index(&self, index: &FileIndex, roots: &[SearchRoot]) -> SearchOutput {
+        let mut output = SearchOutput::default();
+        self.search_index_with(index, roots, |x| output.push(x));
+        output
+    }
+
+    /// Answers the search from `index` and hands each result and warning to
+    /// `callback`, see [`FileSearcher::search_roots_with`].
+    pub fn search_index_with(
+        &self,
+        index: &FileIndex,
+        roots: &[SearchRoot],
+        mut callback: impl FnMut(SearchEvent) -> ControlFlow<()>,
+    ) {
+        let mut progress = self.start_progress();
+
+        for root in roots {
+            if self.is_canceled() {
+                return;
+            }
+
+            let flow = match fs::canonicalize(&root.path) {
+                Ok(path) if index.covers(&path) => {
+                    self.search_indexed_path(index, &path, &root.options, &mut callback)
+                }
+                _ => self.search_path(
+                    &root.path,
+                    &root.path,
+                    &root.options,
+                    &mut progress,
+                    &mut callback,
+                ),
+            };
+
+            if flow.is_break() {
+                return;
+            }
+        }
+    }
+
+    fn search_indexed_path(
+        &self,
+        index: &FileIndex,
+        root: &Path,
+        options: &TraversalOptions,
+        callback: &mut impl FnMut(SearchEvent) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let entries = index.entries().iter().filter(|x| {
+            x.path
+                .strip_prefix(root)
+                .is_ok_and(|p| options.contains_depth(p.components().count()))
+                && !options.exclude.excludes_below(
+                    root,
+                    &x.path,
+                    x.kind == EntryKind::Directory,
+                )
+        });
+
+        for index_entry in entries {
+            if self.is_canceled() {
+                return ControlFlow::Break(());
+            }
+
+            let result = self.check_index_filters(index_entry, root);
+
+            for warning in self.take_warnings() {
+                callback(SearchEvent::Warning(warning))?;
+            }
+
+            if let Some(result) = result {
+                callback(SearchEvent::Result(Box::new(result)))?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// Searches `start`, the root or a directory below it, depth limits and
+    /// exclusions are applied relative to the root.
+    fn search_path(
+        &self,
+        root: &Path,
+        start: &Path,
+        options: &TraversalOptions,
+        progress: &mut ProgressTracker,
+        callback: &mut impl FnMut(SearchEvent) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let depth = start.strip_prefix(root).map_or(0, |x| x.components().count());
+        if depth > options.effective_max_depth() {
+            return ControlFlow::Continue(());
+        }
+
+        let shared_root: Arc<Path> = Arc::from(root);
+
+        for entry in options.walk_dir(root, start, depth) {
+            if self.is_canceled() {
+                return ControlFlow::Break(());
+            }
+
+            let entry = match entry {
+                Ok(e) => e,
+                Err(why) => {
+                    callback(SearchEvent::Warning(SearchWarning {
+                        path: why.path().map(Path::to_path_buf),
+                        error: SearchError::WalkDir(why),
+                    }))?;
+                    continue;
+                }
+            };
+
+            let mut entry = FileEntry::from_dir_entry(&entry, &shared_root);
+            entry.depth += depth;
+
+            self.search_entry(&entry, Some(root), progress, callback)?;
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    fn search_entry(
+        &self,
+        entry: &FileEntry,
+        root: Option<&Path>,
+        progress: &mut ProgressTracker,
+        callback: &mut impl FnMut(SearchEvent) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let matches = self.check_filters(entry);
+        self.report_progress(progress, entry);
+
+        for warning in self.take_warnings() {
+            callback(SearchEvent::Warning(warning))?;
+        }
+
+        if matches {
+            callback(SearchEvent::Result(Box::new(map_filetype(entry, root))))?;
+        }
+
+        if self.options.search_archives && entry.file_type().is_file() {
+            self.search_archive(entry.path(), root, callback)?;
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    fn start_progress(&self) -> ProgressTracker {
+        ProgressTracker {
+            progress: SearchProgress::default(),
+            initial_bytes_read: self.bytes_read(),
+        }
+    }
+
+    fn report_progress(&self, tracker: &mut ProgressTracker, entry: &FileEntry) {
+        let Some(callback) = &self.progress else {
+            return;
         };
+
+        let progress = &mut tracker.progress;
+        if entry.file_type().is_dir() {
+            progress.directories_visited += 1;
+        }
+        progress.entries_checked += 1;
+        progress.bytes_read = self.bytes_read() - tracker.initial_bytes_read;
+        entry.path().clone_into(&mut progress.current_path);
+
+        callback(progress);
     }
 
-    if dir_entry.file_type().is_dir() {
-        return SearchResult::Directory {
-            path: dir_entry.path().as_os_str().to_os_string(),
-            name: dir_entry.file_name().to_os_string(),
-            metadata: dir_entry.metadata().ok(),
+    fn bytes_read(&self) -> u64 {
+        self.filters.iter().map(|x| x.bytes_read()).sum()
+    }
+
+    fn take_warnings(&self) -> Vec<SearchWarning> {
+        self.filters.iter().flat_map(|x| x.take_warnings()).collect()
+    }
+
+    fn search_archive(
+        &self,
+        path: &Path,
+        root: Option<&Path>,
+        callback: &mut impl FnMut(SearchEvent) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let Some(kind) = ArchiveKind::detect(path) else {
+            return ControlFlow::Continue(());
         };
+
+        let mut flow = ControlFlow::Continue(());
+        let read = archive::read_members(
+            path,
+            kind,
+            |member| self.needs_archive_content(member),
+            |member| {
+                if self.check_archive_filters(&member) {
+                    flow = callback(SearchEvent::Result(Box::new(map_archive_entry(
+                        &member, root,
+                    ))));
+                }
+                flow
+            },
+        );
+        flow?;
+
+        match read {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(why) => callback(SearchEvent::Warning(SearchWarning {
+                path: Some(path.to_path_buf()),
+                error: SearchError::IO(why),
+            })),
+        }
+    }
+
+    /// Stops at the first filter that does not match, the filters run in the
+    /// order they were given in.
+    fn check_filters(&self, entry: &FileEntry) -> bool {
+        self.filters.iter().all(|x| x.check_filter(entry))
     }
 
-    SearchResult::SymLink {
-        path: dir_entry.path().as_os_str().to_os_string(),
-        name: dir_entry.file_name().to_os_string(),
-        metadata: dir_entry.metadata().ok(),
+    /// Checks a single entry against the filters, `None` if it does not match or no longer exists.
+    #[must_use]
+    pub fn check_path(&self, path: &Path) -> Option<SearchResult> {
+        let entry = FileEntry::from_path(path).ok()?;
+        let matches = self.check_filters(&entry);
+        // a single path has no output to report warnings in
+        self.take_warnings();
+
+        if !matches {
+            return None;
+        }
+
+        Some(map_filetype(&entry, None))
+    }
+
+    #[must_use]
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    /// Filters that can not be answered from the index are checked on disk.
+    fn check_index_filters(&self, index_entry: &IndexEntry, root: &Path) -> Option<SearchResult> {
+        let mut needs_filesystem = false;
+
+        for filter in &self.filters {
+            match filter.check_index_entry(index_entry) {
+                Some(false) => return None,
+                Some(true) => {}
+                None => needs_filesystem = true,
+            }
+        }
+
+        let mut entry = FileEntry::from_path(&index_entry.path).ok()?;
+        entry.root = Some(Arc::from(root));
+
+        if needs_filesystem && !self.check_filters(&entry) {
+            return None;
+        }
+
+        Some(map_filetype(&entry, Some(root)))
+    }
+
+    fn check_archive_filters(&self, archive_entry: &ArchiveEntry) -> bool {
+        self.filters
+            .iter()
+            .all(|x| x.check_archive_entry(archive_entry))
+    }
+
+    /// Whether a filter needs the content of the member and all other filters match.
+    fn needs_archive_content(&self, archive_entry: &ArchiveEntry) -> bool {
+        let (content, other): (Vec<_>, Vec<_>) = self
+            .filters
+            .iter()
+            .partition(|x| x.needs_archive_content(archive_entry));
+
+        !content.is_empty() && other.iter().all(|x| x.check_archive_entry(archive_entry))
+    }
+}
+
+impl SearchWarning {
+    /// The reason the entry could not be searched, without the path.
+    #[must_use]
+    pub fn cause(&self) -> String {
+        match &self.error {
+            SearchError::WalkDir(e) => e
+                .io_error()
+                .map_or_else(|| e.to_string(), ToString::to_string),
+            SearchError::IO(e) => e.to_string(),
+        }
+    }
+}
+
+impl Display for SearchWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(p) => write!(f, "{p:?}: {}", self.cause()),
+            None => write!(f, "{}", self.cause()),
+        }
+    }
+}
+
+fn map_archive_entry(archive_entry: &ArchiveEntry, root: Option<&Path>) -> SearchResult {
+    SearchResult {
+        path: archive_entry.path(),
+        root: root.map(Path::to_path_buf),
+        name: OsString::from(&archive_entry.name),
+        kind: archive_entry.kind,
+        metadata: None,
+        archive: Some(ArchiveOrigin {
+            archive: archive_entry.archive.clone(),
+            member: archive_entry.member_path.clone(),
+            size: archive_entry.size,
+        }),
+    }
+}
+
+fn map_filetype(entry: &FileEntry, root: Option<&Path>) -> SearchResult {
+    SearchResult {
+        path: entry.path().to_path_buf(),
+        root: root.map(Path::to_path_buf),
+        name: entry.file_name().to_os_string(),
+        kind: entry.kind(),
+        metadata: entry.metadata().cloned(),
+        archive: None,
     }
 }
 
+/// Reads a list of paths, e.g. the output of `git ls-files` or `find`.
+///
+/// The paths are separated by NUL if the list contains one, as written by
+/// `find -print0`, and by newlines otherwise. Empty lines are skipped.
+pub fn read_file_list(mut reader: impl Read) -> io::Result<Vec<PathBuf>> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+
+    let separator = if content.contains(&0) { 0 } else { b'\n' };
+
+    Ok(content
+        .split(|x| *x == separator)
+        .map(|x| match separator {
+            b'\n' => x.strip_suffix(b"\r").unwrap_or(x),
+            _ => x,
+        })
+        .filter(|x| !x.is_empty())
+        .map(|x| bytes_to_path(x.to_vec()))
+        .collect())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Directory,
+    File,
+    SymLink,
+}
+
+/// Where a search result inside of an archive comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveOrigin {
+    pub archive: PathBuf,
+    pub member: String,
+    pub size: Option<u64>,
+}
+
 #[derive(Clone, Debug)]
-pub enum SearchResult {
-    Directory {
-        path: OsString,
-        name: OsString,
-        metadata: Option<Metadata>,
-    },
-    File {
-        path: OsString,
-        name: OsString,
-        metadata: Option<Metadata>,
-    },
-    SymLink {
-        path: OsString,
-        name: OsString,
-        metadata: Option<Metadata>,
-    },
+pub struct SearchResult {
+    path: PathBuf,
+    root: Option<PathBuf>,
+    name: OsString,
+    kind: EntryKind,
+    metadata: Option<Metadata>,
+    archive: Option<ArchiveOrigin>,
 }
 
 impl SearchResult {
+    /// Recreates a result that was found earlier, e.g. one loaded from the
+    /// history, the metadata is read again from the filesystem.
     #[must_use]
-    pub fn path(&self) -> OsString {
-        match self {
-            SearchResult::Directory {
-                path,
-                name: _,
-                metadata: _,
-            } | SearchResult::File {
-                path,
-                name: _,
-                metadata: _,
-            } | SearchResult::SymLink {
-                path,
-                name: _,
-                metadata: _,
-            } => path.clone(),
+    pub fn restore(
+        path: PathBuf,
+        root: Option<PathBuf>,
+        kind: EntryKind,
+        archive: Option<ArchiveOrigin>,
+    ) -> Self {
+        let name = match &archive {
+            Some(a) => OsString::from(a.member.rsplit('/').next().unwrap_or(&a.member)),
+            None => path
+                .file_name()
+                .map_or_else(|| path.as_os_str().to_os_string(), ToOwned::to_owned),
+        };
+        let metadata = match archive {
+            Some(_) => None,
+            None => fs::symlink_metadata(&path).ok(),
+        };
+
+        Self {
+            path,
+            root,
+            name,
+            kind,
+            metadata,
+            archive,
+        }
+    }
+
+    /// Path of the entry, for archive members it looks like `bundle.tar.gz!/dir/file.txt`.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The search path the entry was found under, `None` if it was checked on
+    /// its own, e.g. read with `--from-stdin`.
+    #[must_use]
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Path of the entry below its search root, e.g. `app/x.log` for
+    /// `/var/log/app/x.log` found under `/var/log`. Without a root it is the
+    /// path without its leading `/`, the root itself has an empty path.
+    #[must_use]
+    pub fn relative_path(&self) -> PathBuf {
+        let path = self
+            .root
+            .as_ref()
+            .and_then(|x| self.fs_path().strip_prefix(x).ok())
+            .unwrap_or(self.fs_path());
+
+        path.components()
+            .filter(|x| matches!(x, Component::Normal(_)))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
+
+    /// Metadata of the entry, `None` for archive members or if it could not be read.
+    #[must_use]
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// The archive containing the entry, `None` for entries on the filesystem.
+    #[must_use]
+    pub fn archive(&self) -> Option<&ArchiveOrigin> {
+        self.archive.as_ref()
+    }
+
+    #[must_use]
+    pub fn size(&self) -> Option<u64> {
+        match &self.archive {
+            Some(a) => a.size,
+            None => self.metadata.as_ref().map(Metadata::len),
+        }
+    }
+
+    /// Device and inode of the entry, shared by its hard links, `None` for archive members.
+    #[must_use]
+    pub fn file_id(&self) -> Option<FileId> {
+        self.metadata().and_then(FileId::from_metadata)
+    }
+
+    /// Number of hard links to the entry, `None` for archive members.
+    #[must_use]
+    pub fn link_count(&self) -> Option<u64> {
+        self.metadata().map(links::link_count)
+    }
+
+    /// Path of the entry on disk, for archive members this is the archive itself.
+    #[must_use]
+    pub fn fs_path(&self) -> &Path {
+        match &self.archive {
+            Some(a) => &a.archive,
+            None => &self.path,
         }
     }
 }
 
-impl Display for SearchResult {
+impl Display for EntryKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SearchResult::Directory {
-                path,
-                name,
-                metadata: _,
-            } => write!(
-                f,
-                "(D): {:?}, path: {:?}",
-                name,
-                truncate(path.to_str().unwrap_or("Not parsable"), 50)
-            ),
-            SearchResult::File {
-                path,
-                name,
-                metadata: _,
-            } => write!(
-                f,
-                "(f): {:?}, path: {:?}",
-                name,
-                truncate(path.to_str().unwrap_or("Not parsable"), 50)
-            ),
-            SearchResult::SymLink {
-                path,
-                name,
-                metadata: _,
-            } => write!(
-                f,
-                "(s): {:?}, path: {:?}",
-                name,
-                truncate(path.to_str().unwrap_or("Not parsable"), 50)
-            ),
+            EntryKind::Directory => write!(f, "directory"),
+            EntryKind::File => write!(f, "file"),
+            EntryKind::SymLink => write!(f, "symlink"),
         }
     }
 }
 
+impl Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match (self.kind, self.archive.is_some()) {
+            (_, true) => "a",
+            (EntryKind::Directory, false) => "D",
+            (EntryKind::File, false) => "f",
+            (EntryKind::SymLink, false) => "s",
+        };
+
+        write!(
+            f,
+            "({kind}): {:?}, path: {:?}",
+            self.name,
+            truncate(&self.path.to_string_lossy(), 50)
+        )
+    }
+}
+
 fn truncate(s: &str, max_chars: usize) -> &str {
     match s.char_indices().nth(max_chars) {
         None => s,
         Some((idx, _)) => &s[..idx],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::SearchBuilder,
+        cli::{MatchOption, ResultFilter},
+    };
+
+    #[test]
+    fn read_file_list_by_newline() {
+        let paths = read_file_list(&b"a\r\nb c\n\nd/e\n"[..]).unwrap();
+
+        assert_eq!(paths, ["a", "b c", "d/e"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn read_file_list_by_nul() {
+        // a NUL separated list keeps newlines and carriage returns in the names
+        let paths = read_file_list(&b"a\nb\0c\r\0\0d"[..]).unwrap();
+
+        assert_eq!(paths, ["a\nb", "c\r", "d"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn read_file_list_empty() {
+        assert!(read_file_list(&b""[..]).unwrap().is_empty());
+        assert!(read_file_list(&b"\n\r\n"[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn content_filter_runs_last() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-search-order-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "needle").unwrap();
+
+        let search = |builder: SearchBuilder| {
+            let search = builder
+                .root(&dir)
+                .contents(["needle"], MatchOption::Any)
+                .build();
+            let found = search.run().results.len();
+            (found, search.searcher().bytes_read())
+        };
+        let matched = search(SearchBuilder::new());
+        let too_small = search(SearchBuilder::new().min_size(100));
+        let other_extension = search(SearchBuilder::new().extensions(["rs"]));
+        let directories = search(SearchBuilder::new().kind(ResultFilter::Directory));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(matched, (1, 6));
+        assert_eq!(too_small, (0, 0));
+        assert_eq!(other_extension, (0, 0));
+        assert_eq!(directories, (0, 0));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    mem,
+    ops::ControlFlow,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::{sync::mpsc, task};
+
+use crate::{
+    builder::PreparedSearch,
+    search::{
+        CancellationToken, FileSearcher, SearchEvent, SearchResult, SearchRoot, SearchWarning,
+    },
+};
+
+/// Number of results buffered by [`search_stream`] before the search waits for the consumer.
+pub static DEFAULT_CAPACITY: usize = 64;
+
+/// Results of a search running on the blocking thread pool of tokio.
+///
+/// The search waits while `capacity` results are buffered and stops when the
+/// stream is dropped or its [`CancellationToken`] is canceled.
+pub struct SearchStream {
+    receiver: mpsc::Receiver<SearchResult>,
+    cancellation: CancellationToken,
+    warnings: Arc<Mutex<Vec<SearchWarning>>>,
+}
+
+/// Starts searching `roots` on the blocking thread pool, must be called from
+/// within a tokio runtime.
+///
+/// The filters are the same as for the synchronous [`FileSearcher::search_roots`],
+/// a cancellation token of the searcher is shared with the stream.
+#[must_use]
+pub fn search_stream(
+    mut searcher: FileSearcher,
+    roots: Vec<SearchRoot>,
+    capacity: usize,
+) -> SearchStream {
+    let cancellation = searcher.cancellation_token();
+
+    spawn_search(capacity, cancellation, move |callback| {
+        searcher.search_roots_with(&roots, callback);
+    })
+}
+
+/// Starts a search created with the builder, including its files.
+pub(crate) fn prepared_search_stream(mut search: PreparedSearch, capacity: usize) -> SearchStream {
+    let cancellation = search.cancellation_token();
+
+    spawn_search(capacity, cancellation, move |callback| search.run_with(callback))
+}
+
+/// The searcher has to stop once `cancellation` is canceled, so a walk that
+/// finds no results also ends when the stream is dropped.
+fn spawn_search(
+    capacity: usize,
+    cancellation: CancellationToken,
+    search: impl FnOnce(&mut dyn FnMut(SearchEvent) -> ControlFlow<()>) + Send + 'static,
+) -> SearchStream {
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+
+    let token = cancellation.clone();
+    let found_warnings = Arc::clone(&warnings);
+    task::spawn_blocking(move || {
+        search(&mut |event| {
+            if token.is_canceled() {
+                return ControlFlow::Break(());
+            }
+
+            match event {
+                SearchEvent::Result(r) => match sender.blocking_send(*r) {
+                    Ok(()) => ControlFlow::Continue(()),
+                    Err(_) => ControlFlow::Break(()),
+                },
+                SearchEvent::Warning(w) => {
+                    if let Ok(mut warnings) = found_warnings.lock() {
+                        warnings.push(w);
+                    }
+                    ControlFlow::Continue(())
+                }
+            }
+        });
+    });
+
+    SearchStream {
+        receiver,
+        cancellation,
+        warnings,
+    }
+}
+
+impl SearchStream {
+    /// Token that stops the search, it can be handed to other tasks.
+    #[must_use]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Takes the entries that could not be searched so far.
+    #[must_use]
+    pub fn take_warnings(&self) -> Vec<SearchWarning> {
+        self.warnings
+            .lock()
+            .map(|mut x| mem::take(&mut *x))
+            .unwrap_or_default()
+    }
+}
+
+impl Stream for SearchStream {
+    type Item = SearchResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.cancellation.is_canceled() {
+            // Wakes up the search if it waits for buffer space.
+            self.receiver.close();
+            return Poll::Ready(None);
+        }
+
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for SearchStream {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropping_the_stream_cancels_the_searcher() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("runtime can be built");
+        let _guard = runtime.enter();
+
+        let token = CancellationToken::new();
+        let searcher = FileSearcher::new(Vec::new(), usize::MAX).with_cancellation(token.clone());
+        let stream = search_stream(searcher, Vec::new(), 1);
+
+        assert!(!token.is_canceled());
+        drop(stream);
+        assert!(token.is_canceled());
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::Metadata,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    links::link_count,
+    search::{EntryKind, SearchResult},
+};
+
+/// Accumulated sizes of a directory, file or file extension.
+#[derive(Clone, Debug, Default)]
+pub struct UsageEntry {
+    pub name: String,
+    /// Path of the directory or file, `None` for extensions and the total.
+    pub path: Option<PathBuf>,
+    pub apparent_size: u64,
+    pub disk_size: u64,
+    pub files: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UsageReport {
+    pub total: UsageEntry,
+    pub directories: Vec<UsageEntry>,
+    pub files: Vec<UsageEntry>,
+    pub extensions: Vec<UsageEntry>,
+}
+
+impl UsageEntry {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    fn for_path(path: &Path) -> Self {
+        Self {
+            name: path.to_string_lossy().to_string(),
+            path: Some(path.to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, apparent_size: u64, disk_size: u64) {
+        self.apparent_size += apparent_size;
+        self.disk_size += disk_size;
+        self.files += 1;
+    }
+}
+
+/// Aggregates the sizes of the files in `results` per directory and extension.
+///
+/// Directory sizes include all files below them up to the search root they were
+/// found in. Only the `top` largest directories, files and extensions are kept.
+/// Files with several hard links are counted at their first path only, unless
+/// `count_links` is set, like `du -l`.
+#[must_use]
+pub fn analyze_usage<P: AsRef<Path>>(
+    results: &[SearchResult],
+    roots: &[P],
+    top: usize,
+    count_links: bool,
+) -> UsageReport {
+    let mut total = UsageEntry::new(String::from("Total"));
+    let mut directories: HashMap<&Path, UsageEntry> = HashMap::new();
+    let mut extensions: HashMap<String, UsageEntry> = HashMap::new();
+    let mut files = Vec::new();
+    let mut counted = HashSet::new();
+
+    for result in results {
+        let (EntryKind::File, None, Some(metadata)) =
+            (result.kind(), result.archive(), result.metadata())
+        else {
+            continue;
+        };
+
+        if !count_links && link_count(metadata) > 1 {
+            if let Some(id) = result.file_id() {
+                if !counted.insert(id) {
+                    continue;
+                }
+            }
+        }
+
+        let path = result.path();
+        let apparent_size = metadata.len();
+        let disk_size = disk_size(metadata);
+
+        total.add(apparent_size, disk_size);
+
+        let root = roots
+            .iter()
+            .map(AsRef::as_ref)
+            .find(|x| path.starts_with(x));
+
+        for ancestor in path.ancestors().skip(1) {
+            if root.is_some_and(|r| !ancestor.starts_with(r)) || ancestor.as_os_str().is_empty() {
+                break;
+            }
+
+            directories
+                .entry(ancestor)
+                .or_insert_with(|| UsageEntry::for_path(ancestor))
+                .add(apparent_size, disk_size);
+        }
+
+        let extension = path
+            .extension()
+            .map_or_else(|| String::from("(none)"), |x| x.to_string_lossy().to_lowercase());
+
+        extensions
+            .entry(extension.clone())
+            .or_insert_with(|| UsageEntry::new(extension))
+            .add(apparent_size, disk_size);
+
+        let mut file = UsageEntry::for_path(path);
+        file.add(apparent_size, disk_size);
+        files.push(file);
+    }
+
+    UsageReport {
+        total,
+        directories: largest(directories.into_values().collect(), top),
+        files: largest(files, top),
+        extensions: largest(extensions.into_values().collect(), top),
+    }
+}
+
+fn largest(mut entries: Vec<UsageEntry>, top: usize) -> Vec<UsageEntry> {
+    entries.sort_by(|a, b| {
+        b.apparent_size
+            .cmp(&a.apparent_size)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    entries.truncate(top);
+    entries
+}
+
+#[cfg(unix)]
+fn disk_size(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_size(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SearchBuilder;
+    use std::fs;
+
+    fn sizes(entries: &[UsageEntry]) -> Vec<(String, u64, usize)> {
+        entries
+            .iter()
+            .map(|x| (x.name.clone(), x.apparent_size, x.files))
+            .collect()
+    }
+
+    #[test]
+    fn analyze_usage_per_directory_and_extension() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-usage-{}", std::process::id()));
+        fs::create_dir_all(dir.join("logs/old")).unwrap();
+        fs::write(dir.join("logs/a.log"), vec![0; 300]).unwrap();
+        fs::write(dir.join("logs/old/b.LOG"), vec![0; 200]).unwrap();
+        fs::write(dir.join("notes.txt"), vec![0; 100]).unwrap();
+        fs::write(dir.join("README"), vec![0; 10]).unwrap();
+
+        let results = SearchBuilder::new().root(&dir).build().run().results;
+        let report = analyze_usage(&results, &[&dir], 2, false);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let name = |path: &str| dir.join(path).to_string_lossy().into_owned();
+        assert_eq!((report.total.apparent_size, report.total.files), (610, 4));
+        assert_eq!(
+            sizes(&report.directories),
+            [(dir.to_string_lossy().into_owned(), 610, 4), (name("logs"), 500, 2)]
+        );
+        assert_eq!(
+            sizes(&report.files),
+            [(name("logs/a.log"), 300, 1), (name("logs/old/b.LOG"), 200, 1)]
+        );
+        assert_eq!(
+            sizes(&report.extensions),
+            [("log".into(), 500, 2), ("txt".into(), 100, 1)]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn analyze_usage_counts_hard_links_once() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-usage-links-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a"), vec![0; 100]).unwrap();
+        fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+
+        let results = SearchBuilder::new().root(&dir).build().run().results;
+        let once = analyze_usage(&results, &[&dir], 10, false);
+        let every = analyze_usage(&results, &[&dir], 10, true);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((once.total.apparent_size, once.total.files), (100, 1));
+        assert_eq!((every.total.apparent_size, every.total.files), (200, 2));
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use notify::{
+    event::{EventKind, ModifyKind},
+    RecommendedWatcher, RecursiveMode, Watcher,
+};
+
+use crate::{
+    error::{FsOperation, FsRsError},
+    search::{FileSearcher, SearchResult, SearchRoot},
+};
+
+#[derive(Clone, Debug)]
+pub enum WatchEvent {
+    Added(Box<SearchResult>),
+    Removed(PathBuf),
+}
+
+/// Keeps the results of a search up to date with changes of the filesystem.
+pub struct SearchWatcher {
+    searcher: FileSearcher,
+    roots: Vec<SearchRoot>,
+    matches: HashMap<PathBuf, SearchResult>,
+}
+
+impl SearchWatcher {
+    /// Creates a watcher for `roots`, their paths are canonicalized so that
+    /// events and search results use the same paths.
+    pub fn new(searcher: FileSearcher, roots: Vec<SearchRoot>) -> Result<Self, FsRsError> {
+        let roots = roots
+            .into_iter()
+            .map(|x| {
+                Ok(SearchRoot {
+                    path: fs::canonicalize(&x.path)
+                        .map_err(|e| FsRsError::filesystem(FsOperation::Resolve, &x.path, e))?,
+                    options: x.options,
+                })
+            })
+            .collect::<Result<Vec<_>, FsRsError>>()?;
+
+        Ok(Self {
+            searcher,
+            roots,
+            matches: HashMap::new(),
+        })
+    }
+
+    /// Runs the search once and then reports every change of the results to
+    /// `callback` until the watcher fails or the callback returns an error.
+    pub fn watch(
+        &mut self,
+        mut callback: impl FnMut(&WatchEvent) -> Result<(), FsRsError>,
+    ) -> Result<(), FsRsError> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = RecommendedWatcher::new(sender, notify::Config::default())?;
+
+        for root in &self.roots {
+            watcher.watch(&root.path, RecursiveMode::Recursive)?;
+        }
+
+        for result in self.searcher.search_roots(&self.roots).results {
+            self.add(result, &mut callback)?;
+        }
+
+        for event in receiver {
+            let event = event?;
+
+            let rescan = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+            );
+
+            for path in &event.paths {
+                self.update(path, rescan, &mut callback)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        path: &Path,
+        rescan: bool,
+        callback: &mut impl FnMut(&WatchEvent) -> Result<(), FsRsError>,
+    ) -> Result<(), FsRsError> {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            let removed: Vec<PathBuf> = self
+                .matches
+                .keys()
+                .filter(|x| x.starts_with(path))
+                .cloned()
+                .collect();
+
+            for r in removed {
+                self.remove(&r, callback)?;
+            }
+
+            return Ok(());
+        };
+
+        if rescan && metadata.is_dir() {
+            // the directory itself may be too shallow, its entries not
+            if let Some(root) = self.root_above(path).cloned() {
+                for result in self.searcher.search_below(&root, path).results {
+                    self.add(result, callback)?;
+                }
+            }
+        }
+
+        if self.root_of(path).is_none() {
+            return Ok(());
+        }
+
+        match self.searcher.check_path(path) {
+            Some(result) => self.add(result, callback)?,
+            None => self.remove(path, callback)?,
+        }
+
+        Ok(())
+    }
+
+    /// The root `path` is searched in, within its depth limits and not excluded.
+    fn root_of(&self, path: &Path) -> Option<&SearchRoot> {
+        self.roots.iter().find(|x| {
+            path.strip_prefix(&x.path)
+                .is_ok_and(|p| x.options.contains_depth(p.components().count()))
+                && !x.options.exclude.excludes_below(&x.path, path, path.is_dir())
+        })
+    }
+
+    /// The root the directory at `path` is below, not above its maximum depth
+    /// and not excluded.
+    fn root_above(&self, path: &Path) -> Option<&SearchRoot> {
+        self.roots.iter().find(|x| {
+            path.strip_prefix(&x.path)
+                .is_ok_and(|p| p.components().count() <= x.options.effective_max_depth())
+                && !x.options.exclude.excludes_below(&x.path, path, true)
+        })
+    }
+
+    fn add(
+        &mut self,
+        result: SearchResult,
+        callback: &mut impl FnMut(&WatchEvent) -> Result<(), FsRsError>,
+    ) -> Result<(), FsRsError> {
+        if self.matches.contains_key(result.path()) {
+            return Ok(());
+        }
+
+        self.matches
+            .insert(result.path().to_path_buf(), result.clone());
+        callback(&WatchEvent::Added(Box::new(result)))
+    }
+
+    fn remove(
+        &mut self,
+        path: &Path,
+        callback: &mut impl FnMut(&WatchEvent) -> Result<(), FsRsError>,
+    ) -> Result<(), FsRsError> {
+        match self.matches.remove(path) {
+            Some(_) => callback(&WatchEvent::Removed(path.to_path_buf())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SearchBuilder;
+
+    #[test]
+    fn rescan_measures_depth_from_the_root() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = fs::canonicalize(dir).unwrap();
+
+        let (searcher, roots) = SearchBuilder::new()
+            .root(&dir)
+            .min_depth(2)
+            .max_depth(3)
+            .build()
+            .into_parts();
+        let mut watcher = SearchWatcher::new(searcher, roots).unwrap();
+
+        // created after the search, reported with one event for `new`
+        fs::create_dir_all(dir.join("new/deep/deeper")).unwrap();
+        fs::write(dir.join("new/a"), "").unwrap();
+        fs::write(dir.join("new/deep/b"), "").unwrap();
+        fs::write(dir.join("new/deep/deeper/c"), "").unwrap();
+
+        let mut added = Vec::new();
+        let updated = watcher.update(&dir.join("new"), true, &mut |event| {
+            if let WatchEvent::Added(result) = event {
+                added.push(result.path().strip_prefix(&dir).unwrap().to_path_buf());
+            }
+            Ok(())
+        });
+        fs::remove_dir_all(&dir).unwrap();
+
+        updated.unwrap();
+        added.sort();
+        assert_eq!(
+            added,
+            ["new/a", "new/deep", "new/deep/b", "new/deep/deeper"].map(PathBuf::from)
+        );
+    }
+}
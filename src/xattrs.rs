@@ -0,0 +1,177 @@
+use std::{ffi::OsString, fmt::Display, path::Path};
+
+use globset::{Glob, GlobMatcher};
+
+/// Extended attributes holding the POSIX ACLs of an entry on Linux.
+static ACL_ATTRIBUTES: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+/// Extended attribute holding the SELinux context of an entry.
+static SELINUX_ATTRIBUTE: &str = "security.selinux";
+
+/// An extended attribute to look for, `NAME` or `NAME=VALUE`, the name may be
+/// a glob like `user.*`.
+#[derive(Clone, Debug)]
+pub struct XattrPattern {
+    name: Glob,
+    matcher: GlobMatcher,
+    value: Option<Vec<u8>>,
+}
+
+impl XattrPattern {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (name, expected) = match value.split_once('=') {
+            Some((name, expected)) => (name, Some(expected.as_bytes().to_vec())),
+            None => (value, None),
+        };
+
+        if name.is_empty() {
+            return Err(format!("invalid attribute '{value}': the name is empty"));
+        }
+
+        let name = Glob::new(name).map_err(|why| format!("invalid attribute '{value}': {why}"))?;
+
+        Ok(Self {
+            matcher: name.compile_matcher(),
+            name,
+            value: expected,
+        })
+    }
+
+    /// Whether the entry at `path` has a matching attribute, symlinks are not followed.
+    #[must_use]
+    pub fn matches(&self, path: &Path) -> bool {
+        list_names(path)
+            .iter()
+            .filter(|x| self.matcher.is_match(x))
+            .any(|x| match &self.value {
+                Some(expected) => read(path, x).is_some_and(|v| trim_nul(&v) == expected),
+                None => true,
+            })
+    }
+}
+
+impl Display for XattrPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.name, String::from_utf8_lossy(value)),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// The extended attributes of the entry at `path` with their values, sorted by name.
+#[must_use]
+pub fn list_xattrs(path: &Path) -> Vec<(OsString, Vec<u8>)> {
+    let mut attributes: Vec<(OsString, Vec<u8>)> = list_names(path)
+        .into_iter()
+        .filter_map(|x| read(path, &x).map(|v| (x, v)))
+        .collect();
+    attributes.sort();
+    attributes
+}
+
+/// Whether the entry at `path` has a POSIX access or default ACL.
+#[must_use]
+pub fn has_acl(path: &Path) -> bool {
+    list_names(path)
+        .iter()
+        .any(|x| ACL_ATTRIBUTES.iter().any(|a| x == a))
+}
+
+/// The SELinux context of the entry at `path`, e.g. `system_u:object_r:etc_t:s0`.
+#[must_use]
+pub fn security_context(path: &Path) -> Option<String> {
+    read(path, SELINUX_ATTRIBUTE.as_ref()).map(|x| String::from_utf8_lossy(trim_nul(&x)).into())
+}
+
+/// Formats an attribute value as text if it is printable, as its size otherwise.
+#[must_use]
+pub fn format_xattr_value(value: &[u8]) -> String {
+    match std::str::from_utf8(trim_nul(value)) {
+        Ok(text) if !text.chars().any(char::is_control) => format!("{text:?}"),
+        _ => format!("<{} bytes>", value.len()),
+    }
+}
+
+/// Values written by C programs often end with the NUL of the string.
+fn trim_nul(value: &[u8]) -> &[u8] {
+    value.strip_suffix(&[0]).unwrap_or(value)
+}
+
+#[cfg(unix)]
+fn list_names(path: &Path) -> Vec<OsString> {
+    match xattr::list(path) {
+        Ok(names) => names.collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(not(unix))]
+fn list_names(_: &Path) -> Vec<OsString> {
+    Vec::new()
+}
+
+#[cfg(unix)]
+fn read(path: &Path, name: &std::ffi::OsStr) -> Option<Vec<u8>> {
+    xattr::get(path, name).ok().flatten()
+}
+
+#[cfg(not(unix))]
+fn read(_: &Path, _: &std::ffi::OsStr) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_parse() {
+        assert_eq!(XattrPattern::parse("user.*").unwrap().to_string(), "user.*");
+        assert_eq!(XattrPattern::parse("user.tag=a=b").unwrap().to_string(), "user.tag=a=b");
+        assert!(XattrPattern::parse("").is_err());
+        assert!(XattrPattern::parse("=value").is_err());
+        assert!(XattrPattern::parse("user.[").is_err());
+    }
+
+    #[test]
+    fn format_values() {
+        assert_eq!(format_xattr_value(b"text\0"), "\"text\"");
+        assert_eq!(format_xattr_value(b"\x01\x02"), "<2 bytes>");
+        assert_eq!(format_xattr_value(b"\xff"), "<1 bytes>");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pattern_matches_attributes() {
+        let dir = std::env::temp_dir().join(format!("fs-rs-xattrs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tagged = dir.join("tagged");
+        let plain = dir.join("plain");
+        std::fs::write(&tagged, "").unwrap();
+        std::fs::write(&plain, "").unwrap();
+
+        // not every file system of the temporary directory has user attributes
+        if xattr::set(&tagged, "user.tag", b"value\0").is_err() {
+            std::fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+
+        let matches = |pattern: &str, path: &Path| {
+            XattrPattern::parse(pattern).unwrap().matches(path)
+        };
+        let results = [
+            matches("user.tag", &tagged),
+            matches("user.*", &tagged),
+            matches("user.tag=value", &tagged),
+            matches("user.tag=other", &tagged),
+            matches("user.other", &tagged),
+            matches("user.*", &plain),
+        ];
+        let listed = list_xattrs(&tagged);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results, [true, true, true, false, false, false]);
+        assert_eq!(listed, [(OsString::from("user.tag"), b"value\0".to_vec())]);
+    }
+}